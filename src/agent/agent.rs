@@ -0,0 +1,428 @@
+use crate::model::{
+    CompletionRequestStatus, LanguageModel, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelRequest, LanguageModelRequestMessage,
+    LanguageModelRequestTool, LanguageModelResponseBuilder, LanguageModelToolResult,
+    LanguageModelToolUse, MessageContent, Role, StopReason,
+};
+use crate::tool::ToolRegistry;
+use futures::channel::mpsc;
+use futures::{FutureExt, StreamExt, future, stream};
+use futures_core::stream::BoxStream;
+use std::sync::Arc;
+
+/// The default maximum number of completion requests made by a single [`Agent`] run.
+pub const DEFAULT_MAX_TURNS: usize = 25;
+
+/// An event emitted while an [`Agent`] drives a conversation to completion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentEvent {
+    /// A new completion request is about to be sent to the model.
+    TurnStarted { turn: usize },
+    /// An event streamed back by the language model.
+    Completion(LanguageModelCompletionEvent),
+    /// A tool requested by the model is about to run.
+    ToolCallStarted(LanguageModelToolUse),
+    /// A tool finished running, successfully or not.
+    ToolCallFinished(LanguageModelToolResult),
+    /// The run is over. `messages` is the full transcript, including the
+    /// messages of the original request.
+    Finished {
+        messages: Vec<LanguageModelRequestMessage>,
+        stop_reason: StopReason,
+    },
+}
+
+/// Drives a [`LanguageModel`] and a [`ToolRegistry`] in a loop: every tool the
+/// model asks for is run and its result sent back, until the model ends its
+/// turn or the maximum number of turns is reached.
+#[derive(Clone)]
+pub struct Agent {
+    model: Arc<dyn LanguageModel>,
+    tools: Arc<ToolRegistry>,
+    max_turns: usize,
+}
+
+impl Agent {
+    pub fn new(model: Arc<dyn LanguageModel>, tools: Arc<ToolRegistry>) -> Self {
+        Self {
+            model,
+            tools,
+            max_turns: DEFAULT_MAX_TURNS,
+        }
+    }
+
+    /// Sets the maximum number of completion requests made by a single run.
+    pub fn with_max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = max_turns.max(1);
+        self
+    }
+
+    pub fn model(&self) -> &Arc<dyn LanguageModel> {
+        &self.model
+    }
+
+    pub fn tools(&self) -> &Arc<ToolRegistry> {
+        &self.tools
+    }
+
+    /// Runs the agent loop, streaming every [`AgentEvent`] as it happens.
+    ///
+    /// The stream ends with [`AgentEvent::Finished`], or with the first error
+    /// returned by the model.
+    pub fn stream(
+        &self,
+        request: LanguageModelRequest,
+    ) -> BoxStream<'static, Result<AgentEvent, LanguageModelCompletionError>> {
+        let (tx, rx) = mpsc::unbounded();
+        let driver = self
+            .clone()
+            .run_loop(request, tx)
+            .into_stream()
+            .filter_map(|()| future::ready(None));
+        stream::select(rx, driver).boxed()
+    }
+
+    /// Runs the agent loop to completion and returns the final transcript.
+    pub async fn run(
+        &self,
+        request: LanguageModelRequest,
+    ) -> Result<Vec<LanguageModelRequestMessage>, LanguageModelCompletionError> {
+        let mut events = self.stream(request);
+        while let Some(event) = events.next().await {
+            if let AgentEvent::Finished { messages, .. } = event? {
+                return Ok(messages);
+            }
+        }
+        Err(anyhow::anyhow!("agent stopped without finishing").into())
+    }
+
+    async fn run_loop(
+        self,
+        mut request: LanguageModelRequest,
+        tx: mpsc::UnboundedSender<Result<AgentEvent, LanguageModelCompletionError>>,
+    ) {
+        let emit = |event| tx.unbounded_send(event).is_ok();
+
+        if request.tools.is_empty() {
+            request.tools = self.request_tools();
+        }
+
+        let mut turn = 0;
+        loop {
+            turn += 1;
+            if !emit(Ok(AgentEvent::TurnStarted { turn })) {
+                return;
+            }
+
+            let mut events = match self.model.stream_completion(request.clone()).await {
+                Ok(events) => events,
+                Err(error) => {
+                    emit(Err(error));
+                    return;
+                }
+            };
+
+            let mut response = LanguageModelResponseBuilder::new();
+            while let Some(event) = events.next().await {
+                match event {
+                    Ok(event) => {
+                        response.push_event(&event);
+                        if !emit(Ok(AgentEvent::Completion(event))) {
+                            return;
+                        }
+                    }
+                    Err(error) => {
+                        emit(Err(error));
+                        return;
+                    }
+                }
+            }
+            drop(events);
+
+            let response = response.build();
+            let stop_reason = response.stop_reason;
+            if response.tool_uses().next().is_none() {
+                if !response.message.content.is_empty() {
+                    request.messages.push(response.message);
+                }
+                emit(Ok(AgentEvent::Finished {
+                    messages: request.messages,
+                    stop_reason,
+                }));
+                return;
+            }
+
+            // The tools the model asked for in this turn run concurrently.
+            for tool_use in response.tool_uses() {
+                if !emit(Ok(AgentEvent::ToolCallStarted(tool_use.clone()))) {
+                    return;
+                }
+            }
+            let results = self.tools.run_tool_uses(&response).await;
+            for result in &results {
+                if !emit(Ok(AgentEvent::ToolCallFinished(result.clone()))) {
+                    return;
+                }
+            }
+            request.messages.push(response.message);
+            request.messages.push(LanguageModelRequestMessage {
+                role: Role::User,
+                content: results
+                    .into_iter()
+                    .map(MessageContent::ToolResult)
+                    .collect(),
+                cache: false,
+            });
+
+            if turn >= self.max_turns {
+                emit(Ok(AgentEvent::Completion(
+                    LanguageModelCompletionEvent::StatusUpdate(
+                        CompletionRequestStatus::ToolUseLimitReached,
+                    ),
+                )));
+                emit(Ok(AgentEvent::Finished {
+                    messages: request.messages,
+                    stop_reason: StopReason::ToolUse,
+                }));
+                return;
+            }
+        }
+    }
+
+    fn request_tools(&self) -> Vec<LanguageModelRequestTool> {
+        let format = self.model.tool_input_format();
+        let mut tools = self
+            .tools
+            .tools()
+            .into_iter()
+            .filter_map(|tool| match tool.input_schema(format) {
+                Ok(input_schema) => Some(LanguageModelRequestTool {
+                    name: tool.name(),
+                    description: tool.description(),
+                    input_schema,
+                }),
+                Err(error) => {
+                    log::error!(
+                        "failed to build input schema for tool {}: {error}",
+                        tool.name()
+                    );
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        // Keep the tool list stable across turns so that prompt caching can kick in.
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+        tools
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::FakeLanguageModel;
+    use crate::tool::{Tool, ToolResultContent};
+
+    struct EchoTool;
+
+    impl Tool for EchoTool {
+        const NAME: &'static str = "echo";
+
+        fn description(&self) -> String {
+            "Echoes its input".into()
+        }
+
+        fn needs_confirmation(&self, _: &serde_json::Value) -> bool {
+            false
+        }
+
+        fn may_perform_edits(&self) -> bool {
+            false
+        }
+
+        fn ui_text(&self, _: &serde_json::Value) -> String {
+            "Echo".into()
+        }
+
+        async fn run(&self, input: serde_json::Value) -> anyhow::Result<ToolResultContent> {
+            Ok(ToolResultContent::Text(
+                input["text"].as_str().unwrap_or_default().into(),
+            ))
+        }
+    }
+
+    /// Waits until `parties` calls are running at once, so it only finishes
+    /// if its calls run concurrently.
+    struct BarrierTool(tokio::sync::Barrier);
+
+    impl Tool for BarrierTool {
+        const NAME: &'static str = "barrier";
+
+        fn description(&self) -> String {
+            "Waits for the other calls".into()
+        }
+
+        fn needs_confirmation(&self, _: &serde_json::Value) -> bool {
+            false
+        }
+
+        fn may_perform_edits(&self) -> bool {
+            false
+        }
+
+        fn ui_text(&self, _: &serde_json::Value) -> String {
+            "Barrier".into()
+        }
+
+        async fn run(&self, input: serde_json::Value) -> anyhow::Result<ToolResultContent> {
+            self.0.wait().await;
+            let delay = input["delay_ms"].as_u64().unwrap_or_default();
+            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+            Ok(ToolResultContent::Text(
+                input["text"].as_str().unwrap_or_default().into(),
+            ))
+        }
+    }
+
+    fn tool_use(id: &str, name: &str, input: serde_json::Value) -> LanguageModelCompletionEvent {
+        LanguageModelCompletionEvent::ToolUse(LanguageModelToolUse {
+            id: id.into(),
+            name: name.into(),
+            raw_input: input.to_string(),
+            input,
+            is_input_complete: true,
+        })
+    }
+
+    fn user_request(text: &str) -> LanguageModelRequest {
+        LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text(text.into())],
+                cache: false,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_runs_tools_until_end_turn() {
+        let model = Arc::new(FakeLanguageModel::new());
+        model.push_response([
+            LanguageModelCompletionEvent::Text("Let me ".into()),
+            LanguageModelCompletionEvent::Text("check.".into()),
+            tool_use("call_1", "echo", serde_json::json!({ "text": "hi" })),
+            tool_use("call_2", "missing", serde_json::json!({})),
+            LanguageModelCompletionEvent::Stop(StopReason::ToolUse),
+        ]);
+        model.push_response([
+            LanguageModelCompletionEvent::Text("Done".into()),
+            LanguageModelCompletionEvent::Stop(StopReason::EndTurn),
+        ]);
+        let tools = Arc::new(ToolRegistry::new());
+        tools.register_tool(EchoTool);
+
+        let agent = Agent::new(model.clone(), tools);
+        let messages = agent.run(user_request("hello")).await.unwrap();
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1].string_contents(), "Let me check.");
+        let MessageContent::ToolResult(echo) = &messages[2].content[0] else {
+            panic!("expected a tool result");
+        };
+        assert!(!echo.is_error);
+        assert_eq!(echo.content.to_str(), Some("hi"));
+        let MessageContent::ToolResult(missing) = &messages[2].content[1] else {
+            panic!("expected a tool result");
+        };
+        assert!(missing.is_error);
+        assert_eq!(messages[3].string_contents(), "Done");
+
+        let requests = model.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].tools.len(), 1);
+        assert_eq!(requests[1].messages.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_agent_stops_at_max_turns() {
+        let model = Arc::new(FakeLanguageModel::new());
+        for id in ["call_1", "call_2"] {
+            model.push_response([
+                tool_use(id, "echo", serde_json::json!({ "text": "again" })),
+                LanguageModelCompletionEvent::Stop(StopReason::ToolUse),
+            ]);
+        }
+        let tools = Arc::new(ToolRegistry::new());
+        tools.register_tool(EchoTool);
+
+        let agent = Agent::new(model.clone(), tools).with_max_turns(2);
+        let events = agent
+            .stream(user_request("loop"))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert!(events.contains(&AgentEvent::Completion(
+            LanguageModelCompletionEvent::StatusUpdate(
+                CompletionRequestStatus::ToolUseLimitReached
+            )
+        )));
+        let Some(AgentEvent::Finished {
+            messages,
+            stop_reason,
+        }) = events.last()
+        else {
+            panic!("expected the run to finish");
+        };
+        assert_eq!(*stop_reason, StopReason::ToolUse);
+        assert_eq!(messages.len(), 5);
+        assert_eq!(model.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_agent_runs_parallel_tool_calls_concurrently() {
+        let model = Arc::new(FakeLanguageModel::new());
+        model.push_response([
+            tool_use(
+                "call_1",
+                "barrier",
+                serde_json::json!({ "text": "slow", "delay_ms": 20 }),
+            ),
+            tool_use("call_2", "barrier", serde_json::json!({ "text": "fast" })),
+            LanguageModelCompletionEvent::Stop(StopReason::ToolUse),
+        ]);
+        model.push_response([LanguageModelCompletionEvent::Stop(StopReason::EndTurn)]);
+        let tools = Arc::new(ToolRegistry::new());
+        tools.register_tool(BarrierTool(tokio::sync::Barrier::new(2)));
+
+        let agent = Agent::new(model, tools);
+        let messages = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            agent.run(user_request("both")),
+        )
+        .await
+        .expect("the tool calls should run concurrently")
+        .unwrap();
+
+        // The results are sent back in the order of the calls.
+        let results = messages[2]
+            .content
+            .iter()
+            .map(|content| match content {
+                MessageContent::ToolResult(result) => {
+                    (result.tool_use_id.to_string(), result.content.to_str())
+                }
+                _ => panic!("expected a tool result"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                ("call_1".to_string(), Some("slow")),
+                ("call_2".to_string(), Some("fast")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,3 @@
+mod agent;
+
+pub use agent::*;
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::io;
 use std::str::FromStr;
 use std::time::Duration;
@@ -6,11 +7,17 @@ use anyhow::{Context as _, Result, anyhow};
 use chrono::{DateTime, Utc};
 use futures::{AsyncBufReadExt, AsyncReadExt, StreamExt, io::BufReader, stream::BoxStream};
 use crate::http_client::http::{self, HeaderMap, HeaderValue};
-use crate::http_client::{AsyncBody, HttpClient, Method, Request as HttpRequest, StatusCode};
+use crate::common::SecretString;
+use crate::http_client::{
+    AsyncBody, HttpClient, Method, Request as HttpRequest, Response as HttpResponse, StatusCode,
+    sensitive_header_value,
+};
 use serde::{Deserialize, Serialize};
 use strum::{EnumIter, EnumString};
 use thiserror::Error;
-use crate::model::{LanguageModelCompletionError, ANTHROPIC_PROVIDER_NAME};
+use crate::model::{
+    LanguageModelCompletionError, ReportedRateLimit, ReportedRateLimits, ANTHROPIC_PROVIDER_NAME,
+};
 
 pub const ANTHROPIC_API_URL: &str = "https://api.anthropic.com";
 
@@ -189,6 +196,48 @@ impl Model {
         }
     }
 
+    /// The id of the model on Amazon Bedrock. Custom models are expected to be
+    /// named by their Bedrock id.
+    pub fn bedrock_id(&self) -> &str {
+        match self {
+            Self::ClaudeOpus4 | Self::ClaudeOpus4Thinking => {
+                "anthropic.claude-opus-4-20250514-v1:0"
+            }
+            Self::ClaudeOpus4_1 | Self::ClaudeOpus4_1Thinking => {
+                "anthropic.claude-opus-4-1-20250805-v1:0"
+            }
+            Self::ClaudeSonnet4 | Self::ClaudeSonnet4Thinking => {
+                "anthropic.claude-sonnet-4-20250514-v1:0"
+            }
+            Self::Claude3_5Sonnet => "anthropic.claude-3-5-sonnet-20241022-v2:0",
+            Self::Claude3_7Sonnet | Self::Claude3_7SonnetThinking => {
+                "anthropic.claude-3-7-sonnet-20250219-v1:0"
+            }
+            Self::Claude3_5Haiku => "anthropic.claude-3-5-haiku-20241022-v1:0",
+            Self::Claude3Opus => "anthropic.claude-3-opus-20240229-v1:0",
+            Self::Claude3Sonnet => "anthropic.claude-3-sonnet-20240229-v1:0",
+            Self::Claude3Haiku => "anthropic.claude-3-haiku-20240307-v1:0",
+            Self::Custom { name, .. } => name,
+        }
+    }
+
+    /// The id of the model on Vertex AI. Custom models are expected to be
+    /// named by their Vertex AI id.
+    pub fn vertex_id(&self) -> &str {
+        match self {
+            Self::ClaudeOpus4 | Self::ClaudeOpus4Thinking => "claude-opus-4@20250514",
+            Self::ClaudeOpus4_1 | Self::ClaudeOpus4_1Thinking => "claude-opus-4-1@20250805",
+            Self::ClaudeSonnet4 | Self::ClaudeSonnet4Thinking => "claude-sonnet-4@20250514",
+            Self::Claude3_5Sonnet => "claude-3-5-sonnet-v2@20241022",
+            Self::Claude3_7Sonnet | Self::Claude3_7SonnetThinking => "claude-3-7-sonnet@20250219",
+            Self::Claude3_5Haiku => "claude-3-5-haiku@20241022",
+            Self::Claude3Opus => "claude-3-opus@20240229",
+            Self::Claude3Sonnet => "claude-3-sonnet@20240229",
+            Self::Claude3Haiku => "claude-3-haiku@20240307",
+            Self::Custom { name, .. } => name,
+        }
+    }
+
     pub fn display_name(&self) -> &str {
         match self {
             Self::ClaudeOpus4 => "Claude Opus 4",
@@ -316,6 +365,26 @@ impl Model {
         }
     }
 
+    /// Returns whether the model can think before answering, whatever its mode.
+    pub fn supports_thinking(&self) -> bool {
+        match self {
+            Self::ClaudeOpus4
+            | Self::ClaudeOpus4_1
+            | Self::ClaudeOpus4Thinking
+            | Self::ClaudeOpus4_1Thinking
+            | Self::ClaudeSonnet4
+            | Self::ClaudeSonnet4Thinking
+            | Self::Claude3_7Sonnet
+            | Self::Claude3_7SonnetThinking => true,
+            Self::Claude3_5Sonnet
+            | Self::Claude3_5Haiku
+            | Self::Claude3Opus
+            | Self::Claude3Sonnet
+            | Self::Claude3Haiku => false,
+            Self::Custom { mode, .. } => matches!(mode, AnthropicModelMode::Thinking { .. }),
+        }
+    }
+
     pub const DEFAULT_BETA_HEADERS: &'static[&'static str] = &["prompt-caching-2024-07-31"];
 
     pub fn beta_headers(&self) -> String {
@@ -362,20 +431,39 @@ impl Model {
 pub async fn complete(
     client: &dyn HttpClient,
     api_url: &str,
-    api_key: &str,
+    api_key: &SecretString,
     request: Request,
 ) -> Result<Response, AnthropicError> {
+    complete_with_rate_limit_info(client, api_url, api_key, &BTreeMap::new(), request)
+        .await
+        .map(|output| output.0)
+}
+
+/// Like [`stream_completion_with_rate_limit_info`], but returns the whole
+/// response at once.
+pub async fn complete_with_rate_limit_info(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<(Response, RateLimitInfo), AnthropicError> {
     let uri = format!("{api_url}/v1/messages");
     let beta_headers = Model::from_id(&request.model)
         .map(|model| model.beta_headers())
         .unwrap_or_else(|_| Model::DEFAULT_BETA_HEADERS.join(","));
-    let request_builder = HttpRequest::builder()
+    let api_key_header = sensitive_header_value(api_key.expose())
+        .map_err(|error| AnthropicError::BuildRequestBody(error.into()))?;
+    let mut request_builder = HttpRequest::builder()
         .method(Method::POST)
         .uri(uri)
         .header("Anthropic-Version", "2023-06-01")
         .header("Anthropic-Beta", beta_headers)
-        .header("X-Api-Key", api_key)
+        .header("X-Api-Key", api_key_header)
         .header("Content-Type", "application/json");
+    for (name, value) in extra_headers {
+        request_builder = request_builder.header(name, value);
+    }
 
     let serialized_request =
         serde_json::to_string(&request).map_err(AnthropicError::SerializeRequest)?;
@@ -387,33 +475,19 @@ pub async fn complete(
         .send(request)
         .await
         .map_err(AnthropicError::HttpSend)?;
-    let status_code = response.status();
+    let rate_limits = RateLimitInfo::from_headers(response.headers());
+    if !response.status().is_success() {
+        return Err(read_error_response(response, &rate_limits).await);
+    }
+
     let mut body = String::new();
     response
         .body_mut()
         .read_to_string(&mut body)
         .await
         .map_err(AnthropicError::ReadResponse)?;
-
-    if status_code.is_success() {
-        Ok(serde_json::from_str(&body).map_err(AnthropicError::DeserializeResponse)?)
-    } else {
-        Err(AnthropicError::HttpResponseError {
-            status_code,
-            message: body,
-        })
-    }
-}
-
-pub async fn stream_completion(
-    client: &dyn HttpClient,
-    api_url: &str,
-    api_key: &str,
-    request: Request,
-) -> Result<BoxStream<'static, Result<Event, AnthropicError>>, AnthropicError> {
-    stream_completion_with_rate_limit_info(client, api_url, api_key, request)
-        .await
-        .map(|output| output.0)
+    let response = serde_json::from_str(&body).map_err(AnthropicError::DeserializeResponse)?;
+    Ok((response, rate_limits))
 }
 
 /// An individual rate limit.
@@ -447,6 +521,16 @@ impl RateLimit {
     }
 }
 
+impl RateLimit {
+    fn to_reported(&self) -> ReportedRateLimit {
+        ReportedRateLimit {
+            limit: self.limit as u64,
+            remaining: self.remaining as u64,
+            reset_in: (self.reset - Utc::now()).to_std().unwrap_or_default(),
+        }
+    }
+}
+
 /// <https://docs.anthropic.com/en/api/rate-limits#response-headers>
 #[derive(Debug)]
 pub struct RateLimitInfo {
@@ -484,6 +568,18 @@ impl RateLimitInfo {
     }
 }
 
+impl From<&RateLimitInfo> for ReportedRateLimits {
+    fn from(info: &RateLimitInfo) -> Self {
+        Self {
+            retry_after: info.retry_after,
+            requests: info.requests.as_ref().map(RateLimit::to_reported),
+            tokens: info.tokens.as_ref().map(RateLimit::to_reported),
+            input_tokens: info.input_tokens.as_ref().map(RateLimit::to_reported),
+            output_tokens: info.output_tokens.as_ref().map(RateLimit::to_reported),
+        }
+    }
+}
+
 /// Parses the Retry-After header value as an integer number of seconds (anthropic always uses
 /// seconds). Note that other services might specify an HTTP date or some other format for this
 /// header. Returns `None` if the header is not present or cannot be parsed.
@@ -502,10 +598,13 @@ fn get_header<'a>(key: &str, headers: &'a HeaderMap) -> anyhow::Result<&'a str>
         .to_str()?)
 }
 
+/// Streams a Messages API completion, along with the rate limits reported in
+/// the response headers. `extra_headers` are sent along with every request.
 pub async fn stream_completion_with_rate_limit_info(
     client: &dyn HttpClient,
     api_url: &str,
-    api_key: &str,
+    api_key: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
     request: Request,
 ) -> Result<
     (
@@ -522,23 +621,44 @@ pub async fn stream_completion_with_rate_limit_info(
     let beta_headers = Model::from_id(&request.base.model)
         .map(|model| model.beta_headers())
         .unwrap_or_else(|_| Model::DEFAULT_BETA_HEADERS.join(","));
-    let request_builder = HttpRequest::builder()
+    let api_key_header = sensitive_header_value(api_key.expose())
+        .map_err(|error| AnthropicError::BuildRequestBody(error.into()))?;
+    let mut request_builder = HttpRequest::builder()
         .method(Method::POST)
         .uri(uri)
         .header("Anthropic-Version", "2023-06-01")
         .header("Anthropic-Beta", beta_headers)
-        .header("X-Api-Key", api_key)
+        .header("X-Api-Key", api_key_header)
         .header("Content-Type", "application/json");
+    for (name, value) in extra_headers {
+        request_builder = request_builder.header(name, value);
+    }
     let serialized_request =
         serde_json::to_string(&request).map_err(AnthropicError::SerializeRequest)?;
     let request = request_builder
         .body(AsyncBody::from(serialized_request))
         .map_err(AnthropicError::BuildRequestBody)?;
 
-    let mut response = client
+    let response = client
         .send(request)
         .await
         .map_err(AnthropicError::HttpSend)?;
+    read_streaming_response(response)
+        .await
+        .map(|(stream, rate_limits)| (stream, Some(rate_limits)))
+}
+
+/// Reads the server-sent events of a streaming Messages API response, or the
+/// error it carries instead.
+pub(crate) async fn read_streaming_response(
+    response: HttpResponse<AsyncBody>,
+) -> Result<
+    (
+        BoxStream<'static, Result<Event, AnthropicError>>,
+        RateLimitInfo,
+    ),
+    AnthropicError,
+> {
     let rate_limits = RateLimitInfo::from_headers(response.headers());
     if response.status().is_success() {
         let reader = BufReader::new(response.into_body());
@@ -557,28 +677,36 @@ pub async fn stream_completion_with_rate_limit_info(
                 }
             })
             .boxed();
-        Ok((stream, Some(rate_limits)))
-    } else if response.status().as_u16() == 529 {
-        Err(AnthropicError::ServerOverloaded {
-            retry_after: rate_limits.retry_after,
-        })
-    } else if let Some(retry_after) = rate_limits.retry_after {
-        Err(AnthropicError::RateLimit { retry_after })
+        Ok((stream, rate_limits))
     } else {
-        let mut body = String::new();
-        response
-            .body_mut()
-            .read_to_string(&mut body)
-            .await
-            .map_err(AnthropicError::ReadResponse)?;
-
-        match serde_json::from_str::<Event>(&body) {
-            Ok(Event::Error { error }) => Err(AnthropicError::ApiError(error)),
-            Ok(_) | Err(_) => Err(AnthropicError::HttpResponseError {
-                status_code: response.status(),
-                message: body,
-            }),
-        }
+        Err(read_error_response(response, &rate_limits).await)
+    }
+}
+
+/// Turns an unsuccessful Messages API response into an error.
+async fn read_error_response(
+    mut response: HttpResponse<AsyncBody>,
+    rate_limits: &RateLimitInfo,
+) -> AnthropicError {
+    if response.status().as_u16() == 529 {
+        return AnthropicError::ServerOverloaded {
+            retry_after: rate_limits.retry_after,
+        };
+    }
+    if let Some(retry_after) = rate_limits.retry_after {
+        return AnthropicError::RateLimit { retry_after };
+    }
+
+    let mut body = String::new();
+    if let Err(error) = response.body_mut().read_to_string(&mut body).await {
+        return AnthropicError::ReadResponse(error);
+    }
+    match serde_json::from_str::<Event>(&body) {
+        Ok(Event::Error { error }) => AnthropicError::ApiError(error),
+        Ok(_) | Err(_) => AnthropicError::HttpResponseError {
+            status_code: response.status(),
+            message: body,
+        },
     }
 }
 
@@ -669,7 +797,13 @@ pub enum ResponseContent {
     #[serde(rename = "text")]
     Text { text: String },
     #[serde(rename = "thinking")]
-    Thinking { thinking: String },
+    Thinking {
+        thinking: String,
+        /// Only complete in a response that isn't streamed; streams send it
+        /// as a delta.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
     #[serde(rename = "redacted_thinking")]
     RedactedThinking { data: String },
     #[serde(rename = "tool_use")]
@@ -698,12 +832,25 @@ pub struct Tool {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ToolChoice {
-    Auto,
-    Any,
-    Tool { name: String },
+    Auto {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    Any {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
+    Tool {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
     None,
 }
 
+/// The smallest thinking budget the API accepts.
+pub const MIN_THINKING_BUDGET_TOKENS: u32 = 1_024;
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum Thinking {
@@ -749,6 +896,24 @@ struct StreamingRequest {
     pub stream: bool,
 }
 
+/// Serializes `request` for a cloud platform, which takes the model from the
+/// URL and the API version from the body.
+pub(crate) fn serialize_platform_request(
+    request: Request,
+    anthropic_version: &str,
+    stream: bool,
+) -> Result<String, AnthropicError> {
+    let mut body = serde_json::to_value(request).map_err(AnthropicError::SerializeRequest)?;
+    if let Some(body) = body.as_object_mut() {
+        body.remove("model");
+        body.insert("anthropic_version".into(), anthropic_version.into());
+        if stream {
+            body.insert("stream".into(), true.into());
+        }
+    }
+    serde_json::to_string(&body).map_err(AnthropicError::SerializeRequest)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Metadata {
     pub user_id: Option<String>,
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::Utc;
+use futures::{AsyncReadExt, StreamExt, stream::BoxStream};
+use serde::Deserialize;
+
+use crate::anthropic::{AnthropicError, ApiError, Event, Request, serialize_platform_request};
+use crate::aws::{
+    AwsCredentials, EventStreamMessage, SigningParams, decode_event_stream, sign_request,
+    uri_encode,
+};
+use crate::http_client::http::{HeaderMap, HeaderName, HeaderValue, Uri};
+use crate::http_client::{AsyncBody, HttpClient, Method, Request as HttpRequest};
+
+/// Sent in the body instead of the `Anthropic-Version` header.
+pub const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+/// Where a Claude model is invoked on Amazon Bedrock.
+pub struct BedrockEndpoint<'a> {
+    pub region: &'a str,
+    /// The model id, e.g. `anthropic.claude-sonnet-4-20250514-v1:0`, or the id
+    /// or ARN of an inference profile.
+    pub model_id: &'a str,
+    /// Replaces the regional runtime endpoint, e.g. for a VPC endpoint.
+    pub url: Option<&'a str>,
+}
+
+impl BedrockEndpoint<'_> {
+    fn stream_url(&self) -> String {
+        let base_url = match self.url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("https://bedrock-runtime.{}.amazonaws.com", self.region),
+        };
+        format!(
+            "{base_url}/model/{}/invoke-with-response-stream",
+            uri_encode(self.model_id)
+        )
+    }
+}
+
+/// Streams a completion from Claude on Amazon Bedrock.
+///
+/// The request is signed with `credentials`, and the events of the Messages
+/// API are unwrapped from Bedrock's event stream. `request.model` is ignored
+/// in favor of `endpoint.model_id`.
+pub async fn stream_bedrock_completion(
+    client: &dyn HttpClient,
+    endpoint: &BedrockEndpoint<'_>,
+    credentials: &AwsCredentials,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<BoxStream<'static, Result<Event, AnthropicError>>, AnthropicError> {
+    let uri = endpoint
+        .stream_url()
+        .parse::<Uri>()
+        .map_err(|error| AnthropicError::BuildRequestBody(error.into()))?;
+    let body = serialize_platform_request(request, BEDROCK_ANTHROPIC_VERSION, false)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", HeaderValue::from_static("application/json"));
+    headers.insert(
+        "accept",
+        HeaderValue::from_static("application/vnd.amazon.eventstream"),
+    );
+    for (name, value) in extra_headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|error| AnthropicError::BuildRequestBody(error.into()))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|error| AnthropicError::BuildRequestBody(error.into()))?;
+        headers.insert(name, value);
+    }
+    sign_request(
+        &Method::POST,
+        &uri,
+        &mut headers,
+        body.as_bytes(),
+        &SigningParams {
+            credentials,
+            region: endpoint.region,
+            service: "bedrock",
+            time: Utc::now(),
+        },
+    )
+    .map_err(|error| AnthropicError::BuildRequestBody(error.into()))?;
+
+    let mut request = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .body(AsyncBody::from(body))
+        .map_err(AnthropicError::BuildRequestBody)?;
+    *request.headers_mut() = headers;
+
+    let mut response = client
+        .send(request)
+        .await
+        .map_err(AnthropicError::HttpSend)?;
+    if response.status().is_success() {
+        let stream = decode_event_stream(response.into_body())
+            .filter_map(|message| async move {
+                match message {
+                    Ok(message) => parse_message(message).transpose(),
+                    Err(error) => Some(Err(AnthropicError::ReadResponse(error))),
+                }
+            })
+            .boxed();
+        Ok(stream)
+    } else {
+        let status_code = response.status();
+        let error_type = response
+            .headers()
+            .get("x-amzn-errortype")
+            .and_then(|value| value.to_str().ok())
+            // e.g. `ValidationException:http://internal.amazon.com/coral/...`
+            .map(|value| value.split(':').next().unwrap_or_default().to_string());
+        let mut body = String::new();
+        response
+            .body_mut()
+            .read_to_string(&mut body)
+            .await
+            .map_err(AnthropicError::ReadResponse)?;
+
+        match (error_type, serde_json::from_str::<ExceptionBody>(&body)) {
+            (Some(error_type), Ok(exception)) => {
+                let error = api_error(&error_type, exception.message);
+                if error.code().is_some() {
+                    return Err(AnthropicError::ApiError(error));
+                }
+                Err(AnthropicError::HttpResponseError {
+                    status_code,
+                    message: error.message,
+                })
+            }
+            _ => Err(AnthropicError::HttpResponseError {
+                status_code,
+                message: body,
+            }),
+        }
+    }
+}
+
+/// A `chunk` event, wrapping an event of the Messages API.
+#[derive(Deserialize)]
+struct Chunk {
+    bytes: String,
+}
+
+#[derive(Deserialize)]
+struct ExceptionBody {
+    #[serde(alias = "Message")]
+    message: String,
+}
+
+fn parse_message(message: EventStreamMessage) -> Result<Option<Event>, AnthropicError> {
+    match message.header(":message-type") {
+        Some("event") if message.header(":event-type") == Some("chunk") => {
+            let chunk: Chunk = serde_json::from_slice(&message.payload)
+                .map_err(AnthropicError::DeserializeResponse)?;
+            let bytes = BASE64.decode(chunk.bytes).map_err(|error| {
+                AnthropicError::ReadResponse(io::Error::new(io::ErrorKind::InvalidData, error))
+            })?;
+            serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(AnthropicError::DeserializeResponse)
+        }
+        Some("exception") => {
+            let error_type = message.header(":exception-type").unwrap_or_default();
+            let text = serde_json::from_slice::<ExceptionBody>(&message.payload)
+                .map(|exception| exception.message)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&message.payload).into_owned());
+            Err(AnthropicError::ApiError(api_error(error_type, text)))
+        }
+        Some("error") => Err(AnthropicError::ApiError(api_error(
+            message.header(":error-code").unwrap_or_default(),
+            message
+                .header(":error-message")
+                .unwrap_or_default()
+                .to_string(),
+        ))),
+        // Other events, like metrics, carry nothing for the caller.
+        _ => Ok(None),
+    }
+}
+
+/// Translates a Bedrock exception to the Anthropic error of the same meaning.
+/// Unknown exceptions keep their name.
+fn api_error(exception_type: &str, message: String) -> ApiError {
+    let error_type = [
+        ("ValidationException", "invalid_request_error"),
+        ("UnrecognizedClientException", "authentication_error"),
+        ("InvalidSignatureException", "authentication_error"),
+        ("ExpiredTokenException", "authentication_error"),
+        ("AccessDeniedException", "permission_error"),
+        ("ResourceNotFoundException", "not_found_error"),
+        ("ThrottlingException", "rate_limit_error"),
+        ("ServiceQuotaExceededException", "rate_limit_error"),
+        ("ServiceUnavailableException", "overloaded_error"),
+        ("ModelNotReadyException", "overloaded_error"),
+        ("InternalServerException", "api_error"),
+        ("ModelStreamErrorException", "api_error"),
+        ("ModelTimeoutException", "api_error"),
+    ]
+    .iter()
+    // Exceptions within the stream are named in camel case.
+    .find(|(exception, _)| exception.eq_ignore_ascii_case(exception_type))
+    .map_or(exception_type, |(_, error_type)| error_type);
+    ApiError {
+        error_type: error_type.to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anthropic::ApiErrorCode;
+    use crate::aws::encode_event_stream_message;
+
+    #[test]
+    fn test_parse_exception() {
+        let message = EventStreamMessage {
+            headers: vec![
+                (":message-type".into(), "exception".into()),
+                (":exception-type".into(), "throttlingException".into()),
+            ],
+            payload: br#"{"message":"Too many requests"}"#.to_vec(),
+        };
+        let Err(AnthropicError::ApiError(error)) = parse_message(message) else {
+            panic!("expected an API error");
+        };
+        assert_eq!(error.code(), Some(ApiErrorCode::RateLimitError));
+        assert_eq!(error.message, "Too many requests");
+
+        let bytes = encode_event_stream_message(
+            &[(":message-type", "event"), (":event-type", "chunk")],
+            format!(r#"{{"bytes":"{}"}}"#, BASE64.encode(r#"{"type":"ping"}"#)).as_bytes(),
+        );
+        let mut decoder = crate::aws::EventStreamDecoder::default();
+        decoder.push(&bytes);
+        let message = decoder.next_message().unwrap().unwrap();
+        assert!(matches!(parse_message(message), Ok(Some(Event::Ping))));
+    }
+}
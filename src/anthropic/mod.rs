@@ -1,2 +1,6 @@
 mod anthropic;
-pub (crate) use anthropic::*;
\ No newline at end of file
+mod bedrock;
+mod vertex;
+pub (crate) use anthropic::*;
+pub use bedrock::*;
+pub use vertex::*;
\ No newline at end of file
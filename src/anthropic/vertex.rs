@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use futures::stream::BoxStream;
+
+use crate::anthropic::{
+    AnthropicError, Event, RateLimitInfo, Request, read_streaming_response,
+    serialize_platform_request,
+};
+use crate::common::SecretString;
+use crate::http_client::{
+    AsyncBody, HttpClient, Method, Request as HttpRequest, sensitive_header_value,
+};
+
+/// Sent in the body instead of the `Anthropic-Version` header.
+pub const VERTEX_ANTHROPIC_VERSION: &str = "vertex-2023-10-16";
+
+/// Where a Claude model is served on Google Cloud Vertex AI.
+pub struct VertexEndpoint<'a> {
+    pub project_id: &'a str,
+    /// A region such as `us-east5`, or `global`.
+    pub location: &'a str,
+    /// The model id, e.g. `claude-sonnet-4@20250514`.
+    pub model_id: &'a str,
+    /// Replaces the Vertex AI endpoint of the location.
+    pub url: Option<&'a str>,
+}
+
+impl VertexEndpoint<'_> {
+    fn stream_url(&self) -> String {
+        let base_url = match self.url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None if self.location == "global" => "https://aiplatform.googleapis.com".to_string(),
+            None => format!("https://{}-aiplatform.googleapis.com", self.location),
+        };
+        format!(
+            "{base_url}/v1/projects/{}/locations/{}/publishers/anthropic/models/{}:streamRawPredict",
+            self.project_id, self.location, self.model_id
+        )
+    }
+}
+
+/// Streams a completion from Claude on Vertex AI.
+///
+/// `access_token` is an OAuth token, e.g. from a
+/// [`crate::google::ServiceAccountCredentialProvider`]. `request.model` is
+/// ignored in favor of `endpoint.model_id`.
+pub async fn stream_vertex_completion(
+    client: &dyn HttpClient,
+    endpoint: &VertexEndpoint<'_>,
+    access_token: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<
+    (
+        BoxStream<'static, Result<Event, AnthropicError>>,
+        RateLimitInfo,
+    ),
+    AnthropicError,
+> {
+    let body = serialize_platform_request(request, VERTEX_ANTHROPIC_VERSION, true)?;
+    let authorization = sensitive_header_value(&format!("Bearer {}", access_token.expose()))
+        .map_err(|error| AnthropicError::BuildRequestBody(error.into()))?;
+    let mut request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(endpoint.stream_url())
+        .header("Authorization", authorization)
+        .header("Content-Type", "application/json");
+    for (name, value) in extra_headers {
+        request_builder = request_builder.header(name, value);
+    }
+    let request = request_builder
+        .body(AsyncBody::from(body))
+        .map_err(AnthropicError::BuildRequestBody)?;
+
+    let response = client
+        .send(request)
+        .await
+        .map_err(AnthropicError::HttpSend)?;
+    read_streaming_response(response).await
+}
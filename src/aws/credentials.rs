@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result, anyhow};
+
+use crate::common::SecretString;
+
+const DEFAULT_PROFILE: &str = "default";
+
+/// AWS access keys, as found in the environment or `~/.aws/credentials`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: SecretString,
+    /// Set for temporary credentials, e.g. those of an assumed role.
+    pub session_token: Option<SecretString>,
+}
+
+impl AwsCredentials {
+    pub fn new(
+        access_key_id: impl Into<String>,
+        secret_access_key: impl Into<SecretString>,
+    ) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+        }
+    }
+
+    pub fn with_session_token(mut self, session_token: impl Into<SecretString>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    /// Reads `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+    /// `AWS_SESSION_TOKEN`.
+    pub fn from_env() -> Option<Self> {
+        let access_key_id = env_var("AWS_ACCESS_KEY_ID")?;
+        let secret_access_key = env_var("AWS_SECRET_ACCESS_KEY")?;
+        let mut credentials = Self::new(access_key_id, secret_access_key);
+        credentials.session_token = env_var("AWS_SESSION_TOKEN").map(SecretString::from);
+        Some(credentials)
+    }
+
+    /// Reads `profile` from the shared credentials file, which is
+    /// `~/.aws/credentials` unless `AWS_SHARED_CREDENTIALS_FILE` says otherwise.
+    pub async fn from_profile(profile: &str) -> Result<Option<Self>> {
+        let Some(path) = shared_file("AWS_SHARED_CREDENTIALS_FILE", "credentials") else {
+            return Ok(None);
+        };
+        let Some(mut sections) = read_ini(&path).await? else {
+            return Ok(None);
+        };
+        let Some(mut section) = sections.remove(profile) else {
+            return Ok(None);
+        };
+        let (Some(access_key_id), Some(secret_access_key)) = (
+            section.remove("aws_access_key_id"),
+            section.remove("aws_secret_access_key"),
+        ) else {
+            return Ok(None);
+        };
+        let mut credentials = Self::new(access_key_id, secret_access_key);
+        credentials.session_token = section.remove("aws_session_token").map(SecretString::from);
+        Ok(Some(credentials))
+    }
+
+    /// Loads credentials the way the AWS CLI does for access keys.
+    ///
+    /// With an explicit `profile`, only that profile is read. Otherwise the
+    /// environment is checked first, then the profile named by `AWS_PROFILE`,
+    /// or `default`.
+    pub async fn load(profile: Option<&str>) -> Result<Self> {
+        if profile.is_none()
+            && let Some(credentials) = Self::from_env()
+        {
+            return Ok(credentials);
+        }
+        let profile = resolve_profile(profile);
+        Self::from_profile(&profile)
+            .await?
+            .ok_or_else(|| anyhow!("no AWS credentials found for profile `{profile}`"))
+    }
+}
+
+/// Returns the region from `AWS_REGION` or `AWS_DEFAULT_REGION`, or else the
+/// `region` of the profile in `~/.aws/config`.
+pub async fn load_aws_region(profile: Option<&str>) -> Result<Option<String>> {
+    if let Some(region) = env_var("AWS_REGION").or_else(|| env_var("AWS_DEFAULT_REGION")) {
+        return Ok(Some(region));
+    }
+    let Some(path) = shared_file("AWS_CONFIG_FILE", "config") else {
+        return Ok(None);
+    };
+    let Some(mut sections) = read_ini(&path).await? else {
+        return Ok(None);
+    };
+    // Apart from `default`, profiles in the config file are prefixed.
+    let profile = resolve_profile(profile);
+    let section = if profile == DEFAULT_PROFILE {
+        sections.remove(DEFAULT_PROFILE)
+    } else {
+        sections.remove(&format!("profile {profile}"))
+    };
+    Ok(section.and_then(|mut section| section.remove("region")))
+}
+
+fn resolve_profile(profile: Option<&str>) -> String {
+    profile
+        .map(str::to_string)
+        .or_else(|| env_var("AWS_PROFILE"))
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn shared_file(var: &str, name: &str) -> Option<PathBuf> {
+    if let Some(path) = env_var(var) {
+        return Some(path.into());
+    }
+    let home = env_var("HOME").or_else(|| env_var("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".aws").join(name))
+}
+
+type IniSections = HashMap<String, HashMap<String, String>>;
+
+async fn read_ini(path: &Path) -> Result<Option<IniSections>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => Ok(Some(parse_ini(&contents))),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn parse_ini(contents: &str) -> IniSections {
+    let mut sections = IniSections::new();
+    let mut current = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+            sections.entry(name.clone()).or_default();
+            current = Some(name);
+        } else if let Some((key, value)) = line.split_once('=')
+            && let Some(section) = current.as_ref().and_then(|name| sections.get_mut(name))
+        {
+            section.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ini() {
+        let sections = parse_ini(
+            "# comment\n\
+             [default]\n\
+             aws_access_key_id = AKIDEFAULT\n\
+             aws_secret_access_key=secret\n\
+             \n\
+             [profile  work]\n\
+             region = eu-west-1\n\
+             ; another comment\n",
+        );
+        assert_eq!(sections["default"]["aws_access_key_id"], "AKIDEFAULT");
+        assert_eq!(sections["default"]["aws_secret_access_key"], "secret");
+        assert_eq!(sections["profile work"]["region"], "eu-west-1");
+        assert_eq!(sections.len(), 2);
+    }
+}
@@ -0,0 +1,234 @@
+use std::io;
+
+use futures::{AsyncRead, AsyncReadExt, Stream, stream};
+
+/// The prelude holds the total and header lengths and their checksum.
+const PRELUDE_LEN: usize = 12;
+/// The prelude and the trailing message checksum.
+const MIN_MESSAGE_LEN: usize = PRELUDE_LEN + 4;
+
+/// A message of the `application/vnd.amazon.eventstream` framing used by
+/// streaming AWS APIs.
+/// <https://docs.aws.amazon.com/transcribe/latest/dg/streaming-setting-up.html#streaming-event-stream>
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct EventStreamMessage {
+    /// The headers of type string, such as `:event-type`. Headers of other
+    /// types aren't used by the APIs we talk to and are skipped.
+    pub headers: Vec<(String, String)>,
+    pub payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits bytes, as they arrive, into [`EventStreamMessage`]s.
+#[derive(Default)]
+pub(crate) struct EventStreamDecoder {
+    buffer: Vec<u8>,
+}
+
+impl EventStreamDecoder {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Returns the next complete message, or `None` if more bytes are needed.
+    pub fn next_message(&mut self) -> io::Result<Option<EventStreamMessage>> {
+        if self.buffer.len() < PRELUDE_LEN {
+            return Ok(None);
+        }
+        let total_len = read_u32(&self.buffer[0..4]) as usize;
+        let headers_len = read_u32(&self.buffer[4..8]) as usize;
+        if crc32(&self.buffer[0..8]) != read_u32(&self.buffer[8..12]) {
+            return Err(invalid_data("event stream prelude checksum mismatch"));
+        }
+        if total_len < MIN_MESSAGE_LEN || headers_len > total_len - MIN_MESSAGE_LEN {
+            return Err(invalid_data("invalid event stream message length"));
+        }
+        if self.buffer.len() < total_len {
+            return Ok(None);
+        }
+
+        let message = self.buffer.drain(..total_len).collect::<Vec<_>>();
+        let (message, checksum) = message.split_at(total_len - 4);
+        if crc32(message) != read_u32(checksum) {
+            return Err(invalid_data("event stream message checksum mismatch"));
+        }
+        let (headers, payload) = message[PRELUDE_LEN..].split_at(headers_len);
+        Ok(Some(EventStreamMessage {
+            headers: parse_headers(headers)?,
+            payload: payload.to_vec(),
+        }))
+    }
+}
+
+/// Decodes the messages read from `reader`, e.g. a response body.
+pub(crate) fn decode_event_stream<R>(
+    reader: R,
+) -> impl Stream<Item = io::Result<EventStreamMessage>>
+where
+    R: AsyncRead + Unpin,
+{
+    let state = Some((reader, EventStreamDecoder::default()));
+    stream::unfold(state, |state| async move {
+        let (mut reader, mut decoder) = state?;
+        loop {
+            match decoder.next_message() {
+                Ok(Some(message)) => return Some((Ok(message), Some((reader, decoder)))),
+                Ok(None) => {}
+                Err(error) => return Some((Err(error), None)),
+            }
+            let mut chunk = [0; 8192];
+            match reader.read(&mut chunk).await {
+                Ok(0) if decoder.is_empty() => return None,
+                Ok(0) => {
+                    let error = io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "event stream ended within a message",
+                    );
+                    return Some((Err(error), None));
+                }
+                Ok(len) => decoder.push(&chunk[..len]),
+                Err(error) => return Some((Err(error), None)),
+            }
+        }
+    })
+}
+
+fn parse_headers(mut bytes: &[u8]) -> io::Result<Vec<(String, String)>> {
+    let mut headers = Vec::new();
+    while !bytes.is_empty() {
+        let name_len = take(&mut bytes, 1)?[0] as usize;
+        let name = utf8(take(&mut bytes, name_len)?)?;
+        let value_type = take(&mut bytes, 1)?[0];
+        let value_len = match value_type {
+            // true, false
+            0 | 1 => 0,
+            // byte, short, integer, long, timestamp, uuid
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            // byte array, string
+            6 | 7 => {
+                let len = take(&mut bytes, 2)?;
+                u16::from_be_bytes([len[0], len[1]]) as usize
+            }
+            _ => return Err(invalid_data("unknown event stream header type")),
+        };
+        let value = take(&mut bytes, value_len)?;
+        if value_type == 7 {
+            headers.push((name, utf8(value)?));
+        }
+    }
+    Ok(headers)
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(invalid_data("truncated event stream header"));
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn utf8(bytes: &[u8]) -> io::Result<String> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// CRC-32 (IEEE), as used by the prelude and message checksums.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Frames a message with string headers, as an AWS service would.
+#[cfg(test)]
+pub(crate) fn encode_event_stream_message(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+    let mut encoded_headers = Vec::new();
+    for (name, value) in headers {
+        encoded_headers.push(name.len() as u8);
+        encoded_headers.extend_from_slice(name.as_bytes());
+        encoded_headers.push(7);
+        encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        encoded_headers.extend_from_slice(value.as_bytes());
+    }
+    let total_len = MIN_MESSAGE_LEN + encoded_headers.len() + payload.len();
+
+    let mut message = Vec::with_capacity(total_len);
+    message.extend_from_slice(&(total_len as u32).to_be_bytes());
+    message.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+    message.extend_from_slice(&crc32(&message).to_be_bytes());
+    message.extend_from_slice(&encoded_headers);
+    message.extend_from_slice(payload);
+    message.extend_from_slice(&crc32(&message).to_be_bytes());
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_decode_event_stream() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut bytes = encode_event_stream_message(
+            &[(":message-type", "event"), (":event-type", "chunk")],
+            b"{}",
+        );
+        bytes.extend(encode_event_stream_message(&[], b"second"));
+        let messages = decode_event_stream(bytes.as_slice())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(messages.len(), 2);
+        let first = messages[0].as_ref().unwrap();
+        assert_eq!(first.header(":event-type"), Some("chunk"));
+        assert_eq!(first.payload, b"{}");
+        assert_eq!(messages[1].as_ref().unwrap().payload, b"second");
+
+        // A corrupted payload fails the message checksum.
+        let mut corrupted = encode_event_stream_message(&[], b"payload");
+        corrupted[PRELUDE_LEN] ^= 1;
+        let mut decoder = EventStreamDecoder::default();
+        decoder.push(&corrupted);
+        assert!(decoder.next_message().is_err());
+
+        let truncated = &bytes[..bytes.len() - 1];
+        let messages = decode_event_stream(truncated).collect::<Vec<_>>().await;
+        assert_eq!(messages[0].as_ref().unwrap().payload, b"{}");
+        assert!(messages[1].is_err());
+    }
+}
@@ -0,0 +1,6 @@
+mod credentials;
+mod event_stream;
+mod sigv4;
+pub use credentials::*;
+pub(crate) use event_stream::*;
+pub(crate) use sigv4::*;
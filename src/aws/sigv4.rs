@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ring::{digest, hmac};
+
+use crate::aws::AwsCredentials;
+use crate::http_client::http::header::InvalidHeaderValue;
+use crate::http_client::http::{HeaderMap, HeaderValue, Method, Uri};
+use crate::http_client::sensitive_header_value;
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+pub(crate) struct SigningParams<'a> {
+    pub credentials: &'a AwsCredentials,
+    pub region: &'a str,
+    pub service: &'a str,
+    pub time: DateTime<Utc>,
+}
+
+/// Signs a request with AWS Signature Version 4 by adding the `x-amz-date`,
+/// `x-amz-security-token` and `authorization` headers.
+///
+/// All headers in `headers`, plus the host of `uri`, are signed, so nothing
+/// may be added to them afterwards.
+/// <https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html>
+pub(crate) fn sign_request(
+    method: &Method,
+    uri: &Uri,
+    headers: &mut HeaderMap,
+    payload: &[u8],
+    params: &SigningParams,
+) -> Result<(), InvalidHeaderValue> {
+    let timestamp = params.time.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = &timestamp[..8];
+    headers.insert("x-amz-date", HeaderValue::from_str(&timestamp)?);
+    if let Some(session_token) = &params.credentials.session_token {
+        headers.insert(
+            "x-amz-security-token",
+            sensitive_header_value(session_token.expose())?,
+        );
+    }
+
+    let mut canonical_headers = BTreeMap::new();
+    if let Some(host) = uri.authority() {
+        canonical_headers.insert("host".to_string(), host.as_str().to_string());
+    }
+    for name in headers.keys() {
+        if name == "authorization" {
+            continue;
+        }
+        let values = headers
+            .get_all(name)
+            .iter()
+            .map(|value| {
+                let value = String::from_utf8_lossy(value.as_bytes());
+                value.split_whitespace().collect::<Vec<_>>().join(" ")
+            })
+            .collect::<Vec<_>>();
+        canonical_headers.insert(name.as_str().to_string(), values.join(","));
+    }
+    let signed_headers = canonical_headers
+        .keys()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = [
+        method.as_str().to_string(),
+        canonical_uri(uri.path()),
+        canonical_query(uri.query().unwrap_or_default()),
+        canonical_headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{value}\n"))
+            .collect(),
+        signed_headers.clone(),
+        hex(digest::digest(&digest::SHA256, payload).as_ref()),
+    ]
+    .join("\n");
+
+    let scope = format!("{date}/{}/{}/aws4_request", params.region, params.service);
+    let string_to_sign = [
+        ALGORITHM,
+        &timestamp,
+        &scope,
+        &hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref()),
+    ]
+    .join("\n");
+
+    let secret = format!("AWS4{}", params.credentials.secret_access_key.expose());
+    let signing_key = [date, params.region, params.service, "aws4_request"]
+        .iter()
+        .fold(secret.into_bytes(), |key, data| {
+            hmac_sha256(&key, data.as_bytes())
+        });
+    let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+    let authorization = format!(
+        "{ALGORITHM} Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        params.credentials.access_key_id
+    );
+    headers.insert("authorization", sensitive_header_value(&authorization)?);
+    Ok(())
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+pub(crate) fn uri_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Services other than S3 expect each path segment to be encoded again, even
+/// if it already is.
+fn canonical_uri(path: &str) -> String {
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_query(query: &str) -> String {
+    let mut parameters = query
+        .split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| match parameter.split_once('=') {
+            Some((name, value)) => (name, value),
+            None => (parameter, ""),
+        })
+        .collect::<Vec<_>>();
+    parameters.sort();
+    parameters
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// The `get-vanilla` case of the AWS Signature Version 4 test suite.
+    #[test]
+    fn test_sign_request() {
+        let credentials =
+            AwsCredentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY");
+        let mut headers = HeaderMap::new();
+        sign_request(
+            &Method::GET,
+            &"https://example.amazonaws.com/".parse().unwrap(),
+            &mut headers,
+            b"",
+            &SigningParams {
+                credentials: &credentials,
+                region: "us-east-1",
+                service: "service",
+                time: Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap(),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(headers["x-amz-date"], "20150830T123600Z");
+        assert_eq!(
+            headers["authorization"],
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+        assert_eq!(
+            canonical_uri("/model/claude-v2%3A0/invoke"),
+            "/model/claude-v2%253A0/invoke"
+        );
+    }
+}
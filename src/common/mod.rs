@@ -1,6 +1,8 @@
 mod shared_string;
+mod secret_string;
 mod serde_utils;
 mod utils;
 pub use shared_string::*;
+pub use secret_string::*;
 pub use serde_utils::*;
 pub use utils::*;
\ No newline at end of file
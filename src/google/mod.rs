@@ -0,0 +1,4 @@
+mod google;
+mod service_account;
+pub use google::*;
+pub use service_account::*;
@@ -3,9 +3,12 @@ pub use anyhow::{Result, anyhow};
 use derive_more::Deref;
 pub use http::{self, Method, Request, Response, StatusCode, Uri};
 
+#[cfg(any(test, feature = "test-support"))]
+use futures::AsyncReadExt;
 use futures::future::BoxFuture;
+use http::header::InvalidHeaderValue;
 use http::request::Builder;
-#[cfg(feature = "test-support")]
+use http::{HeaderMap, HeaderValue};
 use std::fmt;
 use std::{
     any::type_name,
@@ -34,6 +37,40 @@ impl HttpRequestExt for http::request::Builder {
     }
 }
 
+/// Headers carrying credentials, whose values are never logged.
+const CREDENTIAL_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "api-key",
+    "x-goog-api-key",
+];
+
+/// Creates a header value for a credential, marked as sensitive so that it is
+/// masked when the request is formatted with `Debug`.
+pub fn sensitive_header_value(value: &str) -> Result<HeaderValue, InvalidHeaderValue> {
+    let mut value = HeaderValue::from_str(value)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// Formats a [`HeaderMap`] for logging, masking credentials.
+pub struct RedactedHeaders<'a>(pub &'a HeaderMap);
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.0 {
+            if value.is_sensitive() || CREDENTIAL_HEADERS.contains(&name.as_str()) {
+                map.entry(name, &"[REDACTED]");
+            } else {
+                map.entry(name, value);
+            }
+        }
+        map.finish()
+    }
+}
+
 pub trait HttpClient: 'static + Send + Sync {
     fn type_name(&self) -> &'static str;
 
@@ -290,7 +327,7 @@ impl HttpClient for BlockedHttpClient {
     }
 }
 
-#[cfg(feature = "test-support")]
+#[cfg(any(test, feature = "test-support"))]
 type FakeHttpHandler = Box<
     dyn Fn(Request<AsyncBody>) -> BoxFuture<'static, anyhow::Result<Response<AsyncBody>>>
         + Send
@@ -298,12 +335,33 @@ type FakeHttpHandler = Box<
         + 'static,
 >;
 
-#[cfg(feature = "test-support")]
+#[cfg(any(test, feature = "test-support"))]
 pub struct FakeHttpClient {
     handler: FakeHttpHandler,
 }
 
-#[cfg(feature = "test-support")]
+/// A request received by a client created with [`FakeHttpClient::replay`].
+#[cfg(any(test, feature = "test-support"))]
+#[derive(Debug)]
+pub struct RecordedRequest {
+    pub parts: http::request::Parts,
+    pub body: String,
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl RecordedRequest {
+    /// Parses the body as JSON, panicking if it isn't.
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// The requests received by a client created with [`FakeHttpClient::replay`],
+/// in the order they were sent.
+#[cfg(any(test, feature = "test-support"))]
+pub type RecordedRequests = Arc<parking_lot::Mutex<Vec<RecordedRequest>>>;
+
+#[cfg(any(test, feature = "test-support"))]
 impl FakeHttpClient {
     pub fn create<Fut, F>(handler: F) -> Arc<HttpClientWithUrl>
     where
@@ -321,6 +379,47 @@ impl FakeHttpClient {
         })
     }
 
+    /// Answers every request with a 200 response carrying `body`, and records
+    /// the requests.
+    pub fn replay(body: impl Into<Vec<u8>>) -> (Arc<HttpClientWithUrl>, RecordedRequests) {
+        Self::replay_responses([(200, body)])
+    }
+
+    /// Answers the requests in turn with the `(status, body)` pairs of
+    /// `responses`, repeating the last one once they run out, and records the
+    /// requests.
+    pub fn replay_responses<B: Into<Vec<u8>>>(
+        responses: impl IntoIterator<Item = (u16, B)>,
+    ) -> (Arc<HttpClientWithUrl>, RecordedRequests) {
+        let responses = Arc::new(
+            responses
+                .into_iter()
+                .map(|(status, body)| (status, body.into()))
+                .collect::<Vec<_>>(),
+        );
+        assert!(!responses.is_empty(), "no responses to replay");
+        let requests = RecordedRequests::default();
+        let client = Self::create({
+            let requests = requests.clone();
+            move |request| {
+                let requests = requests.clone();
+                let responses = responses.clone();
+                async move {
+                    let (parts, mut body) = request.into_parts();
+                    let mut text = String::new();
+                    body.read_to_string(&mut text).await?;
+                    let mut requests = requests.lock();
+                    requests.push(RecordedRequest { parts, body: text });
+                    let (status, body) = &responses[(requests.len() - 1).min(responses.len() - 1)];
+                    Ok(Response::builder()
+                        .status(*status)
+                        .body(AsyncBody::from(body.clone()))?)
+                }
+            }
+        });
+        (client, requests)
+    }
+
     pub fn with_404_response() -> Arc<HttpClientWithUrl> {
         Self::create(|_| async move {
             Ok(Response::builder()
@@ -340,14 +439,14 @@ impl FakeHttpClient {
     }
 }
 
-#[cfg(feature = "test-support")]
+#[cfg(any(test, feature = "test-support"))]
 impl fmt::Debug for FakeHttpClient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FakeHttpClient").finish()
     }
 }
 
-#[cfg(feature = "test-support")]
+#[cfg(any(test, feature = "test-support"))]
 impl HttpClient for FakeHttpClient {
     fn send(
         &self,
@@ -1,3 +1,5 @@
+mod agent;
+mod aws;
 mod common;
 mod http_client;
 pub mod model;
@@ -6,18 +8,23 @@ mod reqwest_client;
 pub use reqwest_client::*;
 pub mod anthropic;
 pub mod openai;
+pub mod google;
+pub mod ollama;
 mod tool;
 
 pub use models::*;
 pub use model::*;
 pub use http_client::*;
 pub use tool::*;
+pub use agent::*;
+pub use common::SecretString;
+pub use aws::{AwsCredentials, load_aws_region};
 #[cfg(test)]
 mod tests {
     use crate::model::{LanguageModelRequest, LanguageModelRequestMessage, MessageContent, Role};
     use crate::models::{AnthropicLanguageModelProvider, OpenAiLanguageModelProvider};
     use crate::openai::Model;
-    use crate::{anthropic, reqwest_client, AnthropicSettings};
+    use crate::{anthropic, reqwest_client, AnthropicSettings, OpenAiSettings};
     use futures_util::StreamExt;
     use std::sync::Arc;
     use crate::anthropic::AnthropicModelMode;
@@ -26,7 +33,12 @@ mod tests {
     async fn test_openai_language_model() {
         dotenvy::dotenv().ok();
         let client = Arc::new(reqwest_client::ReqwestClient::new());
-        let provider = OpenAiLanguageModelProvider::new(client);
+        let settings = OpenAiSettings {
+            api_url: std::env::var("OPENAI_API_BASE_URL").unwrap_or_default(),
+            api_key: std::env::var("OPENAI_API_KEY").unwrap_or_default().into(),
+            ..Default::default()
+        };
+        let provider = OpenAiLanguageModelProvider::new(client, Some(settings));
         let model = provider.create_language_model(Model::Custom {
             // name: "kimi-thinking-preview".to_string(),
             // name: "kimi-k2-0711-preview".to_string(),
@@ -65,11 +77,11 @@ mod tests {
         let api_key = std::env::var("ANTHROPIC_API_KEY").unwrap();
         let anthropic_settings = AnthropicSettings{
             api_url: api_url,
-            api_key: api_key
+            api_key: api_key.into(),
+            ..Default::default()
         };
-        global_registry::register_arc!(AnthropicSettings, anthropic_settings);
         let client = Arc::new(reqwest_client::ReqwestClient::new());
-        let provider = AnthropicLanguageModelProvider::new(client);
+        let provider = AnthropicLanguageModelProvider::new(client, Some(anthropic_settings));
         let model = provider.create_language_model(anthropic::Model::Custom {
             name: "moonshot-v1-8k".to_string(),
             display_name: Some("kimi-k2-turbo-preview".into()),
@@ -0,0 +1,282 @@
+use crate::common::SecretString;
+use crate::model::{LanguageModelCompletionError, LanguageModelProviderName};
+use anyhow::{Context as _, Result, anyhow};
+use parking_lot::Mutex;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A source of API keys.
+///
+/// Implementations return `Ok(None)` when the source has no key, and an error
+/// when it could not be read.
+#[async_trait::async_trait]
+pub trait CredentialProvider: Send + Sync {
+    async fn api_key(&self) -> Result<Option<SecretString>>;
+
+    /// Drops any cached key, e.g. after the API rejected it.
+    fn invalidate(&self) {}
+}
+
+/// Reads the key from an environment variable, e.g. `ANTHROPIC_API_KEY`.
+pub struct EnvCredentialProvider {
+    var: String,
+}
+
+impl EnvCredentialProvider {
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for EnvCredentialProvider {
+    async fn api_key(&self) -> Result<Option<SecretString>> {
+        match std::env::var(&self.var) {
+            Ok(key) if !key.is_empty() => Ok(Some(key.into())),
+            Ok(_) | Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(error) => Err(error).with_context(|| format!("failed to read ${}", self.var)),
+        }
+    }
+}
+
+/// Reads the key from a file.
+///
+/// Files ending in `.json` must contain an object with the key stored under
+/// `name`; any other file is parsed as a dotenv file with a `name=...` entry.
+pub struct FileCredentialProvider {
+    path: PathBuf,
+    name: String,
+}
+
+impl FileCredentialProvider {
+    pub fn new(path: impl Into<PathBuf>, name: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            name: name.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for FileCredentialProvider {
+    async fn api_key(&self) -> Result<Option<SecretString>> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read {}", self.path.display()));
+            }
+        };
+
+        let key = if self.path.extension().is_some_and(|ext| ext == "json") {
+            let value: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", self.path.display()))?;
+            value
+                .get(&self.name)
+                .and_then(|key| key.as_str())
+                .map(str::to_string)
+        } else {
+            let mut key = None;
+            for entry in dotenvy::from_read_iter(contents.as_bytes()) {
+                let (name, value) =
+                    entry.with_context(|| format!("failed to parse {}", self.path.display()))?;
+                if name == self.name {
+                    key = Some(value);
+                }
+            }
+            key
+        };
+        Ok(key.filter(|key| !key.is_empty()).map(SecretString::from))
+    }
+}
+
+/// Runs an external command, like `op read op://vault/openai/key` or
+/// `pass show anthropic`, and uses the first line of its output as the key.
+pub struct CommandCredentialProvider {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandCredentialProvider {
+    pub fn new(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for CommandCredentialProvider {
+    async fn api_key(&self) -> Result<Option<SecretString>> {
+        let output = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .with_context(|| format!("failed to run `{}`", self.program))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "`{}` exited with {}: {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let stdout = String::from_utf8(output.stdout)
+            .with_context(|| format!("`{}` printed invalid UTF-8", self.program))?;
+        Ok(stdout
+            .lines()
+            .next()
+            .map(|line| line.trim())
+            .filter(|key| !key.is_empty())
+            .map(SecretString::from))
+    }
+}
+
+/// Caches the key of another [`CredentialProvider`], so that files and
+/// commands aren't consulted on every request.
+pub struct CachedCredentialProvider {
+    inner: Arc<dyn CredentialProvider>,
+    ttl: Option<Duration>,
+    cached: Mutex<Option<(SecretString, Instant)>>,
+}
+
+impl CachedCredentialProvider {
+    pub fn new(inner: Arc<dyn CredentialProvider>) -> Self {
+        Self {
+            inner,
+            ttl: None,
+            cached: Mutex::default(),
+        }
+    }
+
+    /// Reloads the key once it is older than `ttl`. By default the key is kept
+    /// until [`CredentialProvider::invalidate`] is called.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Reloads the key from the inner provider.
+    pub async fn refresh(&self) -> Result<Option<SecretString>> {
+        self.invalidate();
+        self.api_key().await
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for CachedCredentialProvider {
+    async fn api_key(&self) -> Result<Option<SecretString>> {
+        if let Some((key, loaded_at)) = self.cached.lock().as_ref()
+            && self.ttl.is_none_or(|ttl| loaded_at.elapsed() < ttl)
+        {
+            return Ok(Some(key.clone()));
+        }
+
+        let key = self.inner.api_key().await?;
+        *self.cached.lock() = key.clone().map(|key| (key, Instant::now()));
+        Ok(key)
+    }
+
+    fn invalidate(&self) {
+        self.cached.lock().take();
+        self.inner.invalidate();
+    }
+}
+
+/// Returns `configured` if it is set, or the key of `credentials` otherwise.
+pub(crate) async fn resolve_api_key(
+    configured: &SecretString,
+    credentials: &dyn CredentialProvider,
+    provider: LanguageModelProviderName,
+) -> Result<SecretString, LanguageModelCompletionError> {
+    if !configured.is_empty() {
+        return Ok(configured.clone());
+    }
+    credentials
+        .api_key()
+        .await?
+        .ok_or(LanguageModelCompletionError::NoApiKey { provider })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingProvider(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl CredentialProvider for CountingProvider {
+        async fn api_key(&self) -> Result<Option<SecretString>> {
+            let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(Some(format!("key-{count}").into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_credential_provider() {
+        let inner = Arc::new(CountingProvider(AtomicUsize::new(0)));
+        let cached = CachedCredentialProvider::new(inner.clone());
+
+        assert_eq!(cached.api_key().await.unwrap(), Some("key-1".into()));
+        assert_eq!(cached.api_key().await.unwrap(), Some("key-1".into()));
+        assert_eq!(cached.refresh().await.unwrap(), Some("key-2".into()));
+        cached.invalidate();
+        assert_eq!(cached.api_key().await.unwrap(), Some("key-3".into()));
+
+        let cached = CachedCredentialProvider::new(inner).with_ttl(Duration::ZERO);
+        assert_eq!(cached.api_key().await.unwrap(), Some("key-4".into()));
+        assert_eq!(cached.api_key().await.unwrap(), Some("key-5".into()));
+    }
+
+    #[tokio::test]
+    async fn test_file_credential_provider() {
+        let dir = std::env::temp_dir().join(format!("credentials-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let env_path = dir.join(".env");
+        std::fs::write(&env_path, "# keys\nOTHER=1\nOPENAI_API_KEY=\"sk-env\"\n").unwrap();
+        let json_path = dir.join("keys.json");
+        std::fs::write(&json_path, r#"{"OPENAI_API_KEY": "sk-json"}"#).unwrap();
+
+        let key = FileCredentialProvider::new(&env_path, "OPENAI_API_KEY")
+            .api_key()
+            .await
+            .unwrap();
+        assert_eq!(key, Some("sk-env".into()));
+        let key = FileCredentialProvider::new(&json_path, "OPENAI_API_KEY")
+            .api_key()
+            .await
+            .unwrap();
+        assert_eq!(key, Some("sk-json".into()));
+        let key = FileCredentialProvider::new(dir.join("missing"), "OPENAI_API_KEY")
+            .api_key()
+            .await
+            .unwrap();
+        assert_eq!(key, None);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_credential_provider() {
+        let key = CommandCredentialProvider::new("echo", ["sk-command"])
+            .api_key()
+            .await
+            .unwrap();
+        assert_eq!(key, Some("sk-command".into()));
+        assert!(
+            CommandCredentialProvider::new("false", Vec::<String>::new())
+                .api_key()
+                .await
+                .is_err()
+        );
+    }
+}
@@ -54,6 +54,11 @@ pub enum LanguageModelCompletionError {
         provider: LanguageModelProviderName,
         message: String,
     },
+    #[error("{provider} refused the request because of its content policy: {message}")]
+    ContentFiltered {
+        provider: LanguageModelProviderName,
+        message: String,
+    },
     #[error("language model provider API endpoint not found")]
     ApiEndpointNotFound { provider: LanguageModelProviderName },
     #[error("I/O error reading response from {provider}'s API")]
@@ -92,3 +97,66 @@ pub enum LanguageModelCompletionError {
     Other(#[from] anyhow::Error),
 }
 
+
+impl LanguageModelCompletionError {
+    /// Returns whether sending the same request again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimitExceeded { .. }
+            | Self::ServerOverloaded { .. }
+            | Self::ApiInternalServerError { .. }
+            | Self::HttpSend { .. }
+            | Self::ApiReadResponseError { .. } => true,
+            Self::UpstreamProviderError { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            Self::HttpResponseError { status_code, .. } => {
+                *status_code == StatusCode::TOO_MANY_REQUESTS || status_code.is_server_error()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether the provider rejected the API key.
+    pub fn is_authentication_error(&self) -> bool {
+        match self {
+            Self::AuthenticationError { .. } => true,
+            Self::HttpResponseError { status_code, .. } => *status_code == StatusCode::UNAUTHORIZED,
+            _ => false,
+        }
+    }
+
+    /// Returns how long the provider asked us to wait before retrying, if it said so.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimitExceeded { retry_after, .. }
+            | Self::ServerOverloaded { retry_after, .. }
+            | Self::UpstreamProviderError { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// A short, stable identifier for the kind of error, suitable for status updates.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::PromptTooLarge { .. } => "prompt_too_large",
+            Self::NoApiKey { .. } => "no_api_key",
+            Self::RateLimitExceeded { .. } => "rate_limit_exceeded",
+            Self::ServerOverloaded { .. } => "server_overloaded",
+            Self::ApiInternalServerError { .. } => "api_internal_server_error",
+            Self::UpstreamProviderError { .. } => "upstream_provider_error",
+            Self::HttpResponseError { .. } => "http_response_error",
+            Self::BadRequestFormat { .. } => "bad_request_format",
+            Self::AuthenticationError { .. } => "authentication_error",
+            Self::PermissionError { .. } => "permission_error",
+            Self::ContentFiltered { .. } => "content_filtered",
+            Self::ApiEndpointNotFound { .. } => "api_endpoint_not_found",
+            Self::ApiReadResponseError { .. } => "api_read_response_error",
+            Self::SerializeRequest { .. } => "serialize_request",
+            Self::BuildRequestBody { .. } => "build_request_body",
+            Self::HttpSend { .. } => "http_send",
+            Self::DeserializeResponse { .. } => "deserialize_response",
+            Self::Other(_) => "other",
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::common::SecretString;
+use crate::model::{CredentialProvider, LanguageModelProvider};
+use crate::model::{
+    LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelId,
+    LanguageModelName, LanguageModelProviderId, LanguageModelProviderName, LanguageModelRequest,
+};
+use futures::StreamExt;
+use futures_core::stream::BoxStream;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+pub const FAKE_PROVIDER_ID: LanguageModelProviderId = LanguageModelProviderId::new("fake");
+pub const FAKE_PROVIDER_NAME: LanguageModelProviderName = LanguageModelProviderName::new("Fake");
+
+enum FakeResponse {
+    Events(Vec<LanguageModelCompletionEvent>),
+    Error(LanguageModelCompletionError),
+}
+
+/// A scripted [`LanguageModel`] for tests.
+///
+/// Each call to `stream_completion` pops the next queued response and records
+/// the request it was called with.
+pub struct FakeLanguageModel {
+    id: LanguageModelId,
+    responses: Mutex<VecDeque<FakeResponse>>,
+    requests: Mutex<Vec<LanguageModelRequest>>,
+}
+
+impl Default for FakeLanguageModel {
+    fn default() -> Self {
+        Self::with_id("fake")
+    }
+}
+
+impl FakeLanguageModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_id(id: &str) -> Self {
+        Self {
+            id: LanguageModelId::from(id.to_string()),
+            responses: Mutex::default(),
+            requests: Mutex::default(),
+        }
+    }
+
+    /// Queues the events returned by the next call to `stream_completion`.
+    pub fn push_response(&self, events: impl IntoIterator<Item = LanguageModelCompletionEvent>) {
+        self.responses
+            .lock()
+            .push_back(FakeResponse::Events(events.into_iter().collect()));
+    }
+
+    /// Makes the next call to `stream_completion` fail with `error`.
+    pub fn push_error(&self, error: LanguageModelCompletionError) {
+        self.responses.lock().push_back(FakeResponse::Error(error));
+    }
+
+    /// Returns every request the model has been called with so far.
+    pub fn requests(&self) -> Vec<LanguageModelRequest> {
+        self.requests.lock().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl LanguageModel for FakeLanguageModel {
+    fn id(&self) -> LanguageModelId {
+        self.id.clone()
+    }
+
+    fn name(&self) -> LanguageModelName {
+        LanguageModelName(self.id.0.clone())
+    }
+
+    fn provider_id(&self) -> LanguageModelProviderId {
+        FAKE_PROVIDER_ID
+    }
+
+    fn provider_name(&self) -> LanguageModelProviderName {
+        FAKE_PROVIDER_NAME
+    }
+
+    fn max_token_count(&self) -> u64 {
+        1_000_000
+    }
+
+    async fn stream_completion(
+        &self,
+        request: LanguageModelRequest,
+    ) -> Result<
+        BoxStream<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+        LanguageModelCompletionError,
+    > {
+        self.requests.lock().push(request);
+        let response = self
+            .responses
+            .lock()
+            .pop_front()
+            .ok_or_else(|| anyhow::anyhow!("no response queued for FakeLanguageModel"))?;
+        match response {
+            FakeResponse::Events(events) => {
+                Ok(futures::stream::iter(events.into_iter().map(Ok)).boxed())
+            }
+            FakeResponse::Error(error) => Err(error),
+        }
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    fn supports_burn_mode(&self) -> bool {
+        false
+    }
+}
+
+/// A [`LanguageModelProvider`] serving a fixed list of [`FakeLanguageModel`]s.
+///
+/// The first model is the default one and the last one the default fast one.
+pub struct FakeLanguageModelProvider {
+    id: LanguageModelProviderId,
+    models: Vec<Arc<FakeLanguageModel>>,
+}
+
+impl Default for FakeLanguageModelProvider {
+    fn default() -> Self {
+        Self::new(FAKE_PROVIDER_ID, vec![Arc::new(FakeLanguageModel::new())])
+    }
+}
+
+impl FakeLanguageModelProvider {
+    pub fn new(id: LanguageModelProviderId, models: Vec<Arc<FakeLanguageModel>>) -> Self {
+        Self { id, models }
+    }
+}
+
+impl LanguageModelProvider for FakeLanguageModelProvider {
+    fn id(&self) -> LanguageModelProviderId {
+        self.id.clone()
+    }
+
+    fn name(&self) -> LanguageModelProviderName {
+        LanguageModelProviderName(self.id.0.clone())
+    }
+
+    fn default_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        self.models
+            .first()
+            .map(|model| model.clone() as Arc<dyn LanguageModel>)
+    }
+
+    fn default_fast_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        self.models
+            .last()
+            .map(|model| model.clone() as Arc<dyn LanguageModel>)
+    }
+
+    fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>> {
+        self.models
+            .iter()
+            .map(|model| model.clone() as Arc<dyn LanguageModel>)
+            .collect()
+    }
+}
+
+/// A [`CredentialProvider`] that always returns the same key.
+pub struct FakeCredentialProvider(pub SecretString);
+
+#[async_trait::async_trait]
+impl CredentialProvider for FakeCredentialProvider {
+    async fn api_key(&self) -> anyhow::Result<Option<SecretString>> {
+        Ok(Some(self.0.clone()))
+    }
+}
+
+/// Registers a value in `global_registry` until dropped, then restores the
+/// value registered before, if any.
+pub struct GlobalRegistration<T: Send + Sync + 'static> {
+    previous: Option<Arc<T>>,
+}
+
+impl<T: Send + Sync + 'static> GlobalRegistration<T> {
+    pub fn new(value: T) -> Self {
+        let registry = global_registry::TypeRegistry::global();
+        let previous = registry.get::<T>().ok();
+        registry.register_arc_or_replace(Arc::new(value)).unwrap();
+        Self { previous }
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for GlobalRegistration<T> {
+    fn drop(&mut self) {
+        let registry = global_registry::TypeRegistry::global();
+        match self.previous.take() {
+            Some(previous) => registry.register_arc_or_replace(previous).ok(),
+            None => registry.unregister::<T>().ok(),
+        };
+    }
+}
@@ -8,8 +8,12 @@ pub trait LanguageModelProvider: 'static {
     fn name(&self) -> LanguageModelProviderName;
     fn default_model(&self) -> Option<Arc<dyn LanguageModel>>;
     fn default_fast_model(&self) -> Option<Arc<dyn LanguageModel>> {
-        todo!()
+        None
     }
     fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>>;
-    // async fn authenticate(&self) -> anyhow::Result<()>;
+    /// Checks that credentials are available, so that apps can report a missing
+    /// API key up front instead of on the first completion.
+    async fn authenticate(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
@@ -3,13 +3,26 @@ mod errors;
 mod types;
 mod language_provider;
 mod request;
+mod response;
 mod rate_limiter;
+mod registry;
+mod retry;
+mod credentials;
+#[cfg(any(test, feature = "test-support"))]
+mod fake_provider;
 
 pub use types::*;
 pub use language_provider::*;
 pub use model::*;
 pub use request::*;
+pub use response::*;
 pub use errors::*;
+pub use retry::*;
+pub use rate_limiter::*;
+pub use registry::*;
+pub use credentials::*;
+#[cfg(any(test, feature = "test-support"))]
+pub use fake_provider::*;
 
 
 pub const ANTHROPIC_PROVIDER_ID: LanguageModelProviderId =
@@ -21,6 +34,10 @@ pub const GOOGLE_PROVIDER_ID: LanguageModelProviderId = LanguageModelProviderId:
 pub const GOOGLE_PROVIDER_NAME: LanguageModelProviderName =
     LanguageModelProviderName::new("Google AI");
 
+pub const OLLAMA_PROVIDER_ID: LanguageModelProviderId = LanguageModelProviderId::new("ollama");
+pub const OLLAMA_PROVIDER_NAME: LanguageModelProviderName =
+    LanguageModelProviderName::new("Ollama");
+
 pub const OPEN_AI_PROVIDER_ID: LanguageModelProviderId = LanguageModelProviderId::new("openai");
 pub const OPEN_AI_PROVIDER_NAME: LanguageModelProviderName =
     LanguageModelProviderName::new("OpenAI");
\ No newline at end of file
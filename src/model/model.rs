@@ -3,9 +3,12 @@ use crate::model::types::{
     LanguageModelCompletionEvent, LanguageModelId, LanguageModelName, LanguageModelProviderId,
     LanguageModelProviderName, LanguageModelToolSchemaFormat,
 };
+use futures::StreamExt;
 use futures_core::stream::BoxStream;
 use crate::CompletionMode;
-use crate::model::LanguageModelRequest;
+use crate::model::{
+    LanguageModelRequest, LanguageModelResponse, LanguageModelResponseBuilder, ThinkingSupport,
+};
 
 #[async_trait::async_trait]
 pub trait LanguageModel: Send + Sync {
@@ -27,8 +30,24 @@ pub trait LanguageModel: Send + Sync {
         BoxStream<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         LanguageModelCompletionError,
     >;
+    /// Sends the request and waits for the whole response, aggregating the
+    /// streamed events into a single assistant message.
+    async fn complete(
+        &self,
+        request: LanguageModelRequest,
+    ) -> Result<LanguageModelResponse, LanguageModelCompletionError> {
+        let mut events = self.stream_completion(request).await?;
+        let mut response = LanguageModelResponseBuilder::new();
+        while let Some(event) = events.next().await {
+            response.push_event(&event?);
+        }
+        Ok(response.build())
+    }
     fn supports_tools(&self) -> bool;
     fn supports_burn_mode(&self) -> bool;
+    fn thinking_support(&self) -> ThinkingSupport {
+        ThinkingSupport::default()
+    }
     fn max_token_count_in_burn_mode(&self) -> Option<u64> {
         None
     }
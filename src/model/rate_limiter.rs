@@ -1 +1,509 @@
-//todo
\ No newline at end of file
+use crate::model::{
+    LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelId,
+    LanguageModelRequest,
+};
+use futures::StreamExt;
+use futures_core::stream::BoxStream;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits applied to every request sent through a [`RateLimiter`].
+///
+/// `None` means unlimited, and nothing is limited by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateLimits {
+    /// How many streams may be in flight at once across all models of a provider.
+    pub max_concurrent_requests: Option<usize>,
+    /// How many streams may be in flight at once for a single model.
+    pub max_concurrent_requests_per_model: Option<usize>,
+    /// How many requests may be started in any 60 second window.
+    pub requests_per_minute: Option<u32>,
+    /// How many tokens may be consumed in any 60 second window.
+    ///
+    /// Requests reserve an estimate of their size when they start, which is
+    /// replaced by the actual usage reported by the provider.
+    pub tokens_per_minute: Option<u64>,
+}
+
+/// A single rate limit as reported by a provider in its response headers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportedRateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    /// How long until `remaining` is replenished back to `limit`.
+    pub reset_in: Duration,
+}
+
+/// The rate limits a provider reported alongside a response.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReportedRateLimits {
+    pub retry_after: Option<Duration>,
+    pub requests: Option<ReportedRateLimit>,
+    pub tokens: Option<ReportedRateLimit>,
+    pub input_tokens: Option<ReportedRateLimit>,
+    pub output_tokens: Option<ReportedRateLimit>,
+}
+
+/// The budget left according to the provider, and when it resets.
+#[derive(Clone, Copy, Debug)]
+struct RemoteBudget {
+    remaining: u64,
+    reset_at: Instant,
+}
+
+impl RemoteBudget {
+    fn new(limit: &ReportedRateLimit, now: Instant) -> Self {
+        Self {
+            remaining: limit.remaining,
+            reset_at: now + limit.reset_in,
+        }
+    }
+
+    /// Returns how long to wait before `amount` fits in the budget.
+    fn wait_for(&self, amount: u64, now: Instant) -> Duration {
+        if self.remaining >= amount.max(1) {
+            Duration::ZERO
+        } else {
+            self.reset_at.saturating_duration_since(now)
+        }
+    }
+}
+
+/// What the provider last told us about its limits.
+#[derive(Default)]
+struct RemoteState {
+    blocked_until: Option<Instant>,
+    requests: Option<RemoteBudget>,
+    input_tokens: Option<RemoteBudget>,
+    output_tokens: Option<RemoteBudget>,
+}
+
+impl RemoteState {
+    fn block_for(&mut self, duration: Duration, now: Instant) {
+        let until = now + duration;
+        self.blocked_until = Some(
+            self.blocked_until
+                .map_or(until, |current| current.max(until)),
+        );
+    }
+
+    fn wait_for(&self, estimated_tokens: u64, now: Instant) -> Duration {
+        let blocked = self
+            .blocked_until
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or_default();
+        [
+            blocked,
+            self.requests
+                .map(|budget| budget.wait_for(1, now))
+                .unwrap_or_default(),
+            self.input_tokens
+                .map(|budget| budget.wait_for(estimated_tokens, now))
+                .unwrap_or_default(),
+            self.output_tokens
+                .map(|budget| budget.wait_for(1, now))
+                .unwrap_or_default(),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default()
+    }
+
+    /// Counts a request that is about to be sent against the known budgets,
+    /// until the next response reports fresh numbers.
+    fn spend(&mut self, estimated_tokens: u64) {
+        if let Some(budget) = &mut self.requests {
+            budget.remaining = budget.remaining.saturating_sub(1);
+        }
+        if let Some(budget) = &mut self.input_tokens {
+            budget.remaining = budget.remaining.saturating_sub(estimated_tokens);
+        }
+    }
+}
+
+/// Bounds the requests a provider sends, shared by every model it creates.
+///
+/// Cloning a [`RateLimiter`] returns a handle to the same limits.
+#[derive(Clone)]
+pub struct RateLimiter {
+    state: Arc<RateLimiterState>,
+}
+
+struct RateLimiterState {
+    limits: RateLimits,
+    provider_semaphore: Option<Arc<Semaphore>>,
+    model_semaphores: Mutex<HashMap<LanguageModelId, Arc<Semaphore>>>,
+    window: Mutex<UsageWindow>,
+    remote: Mutex<RemoteState>,
+    remote_changed: Notify,
+}
+
+/// The requests started during the last [`WINDOW`].
+#[derive(Default)]
+struct UsageWindow {
+    next_id: u64,
+    entries: VecDeque<WindowEntry>,
+}
+
+struct WindowEntry {
+    id: u64,
+    started_at: Instant,
+    tokens: u64,
+}
+
+impl UsageWindow {
+    fn prune(&mut self, now: Instant) {
+        while let Some(entry) = self.entries.front() {
+            if now.duration_since(entry.started_at) >= WINDOW {
+                self.entries.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn tokens(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.tokens).sum()
+    }
+
+    /// Returns when the oldest entry leaves the window.
+    fn next_expiry(&self, now: Instant) -> Duration {
+        self.entries
+            .front()
+            .map(|entry| WINDOW.saturating_sub(now.duration_since(entry.started_at)))
+            .unwrap_or_default()
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimits::default())
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            state: Arc::new(RateLimiterState {
+                provider_semaphore: limits
+                    .max_concurrent_requests
+                    .map(|limit| Arc::new(Semaphore::new(limit.max(1)))),
+                model_semaphores: Mutex::default(),
+                window: Mutex::default(),
+                remote: Mutex::default(),
+                remote_changed: Notify::new(),
+                limits,
+            }),
+        }
+    }
+
+    pub fn limits(&self) -> &RateLimits {
+        &self.state.limits
+    }
+
+    /// Records the limits reported by the provider, so that later requests are
+    /// delayed until `reset` once a budget runs out instead of being rejected.
+    pub fn update(&self, reported: &ReportedRateLimits) {
+        let now = Instant::now();
+        let mut remote = self.state.remote.lock();
+        if let Some(retry_after) = reported.retry_after {
+            remote.block_for(retry_after, now);
+        }
+        if let Some(requests) = &reported.requests {
+            remote.requests = Some(RemoteBudget::new(requests, now));
+        }
+        // Prefer the most specific budget the provider gives for input tokens.
+        let input_tokens = [&reported.input_tokens, &reported.tokens]
+            .into_iter()
+            .flatten()
+            .min_by_key(|limit| limit.remaining);
+        if let Some(input_tokens) = input_tokens {
+            remote.input_tokens = Some(RemoteBudget::new(input_tokens, now));
+        }
+        if let Some(output_tokens) = &reported.output_tokens {
+            remote.output_tokens = Some(RemoteBudget::new(output_tokens, now));
+        }
+        drop(remote);
+        self.state.remote_changed.notify_waiters();
+    }
+
+    /// Waits until a request for `model_id` is allowed to start.
+    ///
+    /// The returned guard holds the concurrency slots until it is dropped.
+    pub async fn acquire(
+        &self,
+        model_id: &LanguageModelId,
+        estimated_tokens: u64,
+    ) -> RateLimitGuard {
+        let provider_permit = match &self.state.provider_semaphore {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        let model_permit = match self.model_semaphore(model_id) {
+            Some(semaphore) => semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+        self.wait_for_remote_budget(estimated_tokens).await;
+        let window_entry = self.reserve(estimated_tokens).await;
+
+        RateLimitGuard {
+            state: self.state.clone(),
+            window_entry,
+            _provider_permit: provider_permit,
+            _model_permit: model_permit,
+        }
+    }
+
+    /// Runs `future` once the request is allowed to start, and keeps the
+    /// concurrency slots taken until the returned stream is dropped.
+    pub async fn stream<'a, Fut>(
+        &self,
+        model_id: &LanguageModelId,
+        estimated_tokens: u64,
+        future: Fut,
+    ) -> Result<
+        BoxStream<'a, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+        LanguageModelCompletionError,
+    >
+    where
+        Fut: Future<
+            Output = Result<
+                BoxStream<'a, Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+                LanguageModelCompletionError,
+            >,
+        >,
+    {
+        let guard = self.acquire(model_id, estimated_tokens).await;
+        let stream = match future.await {
+            Ok(stream) => stream,
+            Err(error) => {
+                if let Some(retry_after) = error.retry_after() {
+                    self.state
+                        .remote
+                        .lock()
+                        .block_for(retry_after, Instant::now());
+                }
+                return Err(error);
+            }
+        };
+        Ok(stream
+            .inspect(move |event| {
+                if let Ok(LanguageModelCompletionEvent::UsageUpdate(usage)) = event {
+                    guard.record_usage(
+                        usage.input_tokens
+                            + usage.output_tokens
+                            + usage.cache_creation_input_tokens,
+                    );
+                }
+            })
+            .boxed())
+    }
+
+    fn model_semaphore(&self, model_id: &LanguageModelId) -> Option<Arc<Semaphore>> {
+        let limit = self.state.limits.max_concurrent_requests_per_model?;
+        Some(
+            self.state
+                .model_semaphores
+                .lock()
+                .entry(model_id.clone())
+                .or_insert_with(|| Arc::new(Semaphore::new(limit.max(1))))
+                .clone(),
+        )
+    }
+
+    async fn wait_for_remote_budget(&self, estimated_tokens: u64) {
+        loop {
+            let notified = self.state.remote_changed.notified();
+            futures::pin_mut!(notified);
+            notified.as_mut().enable();
+
+            let wait = {
+                let now = Instant::now();
+                let mut remote = self.state.remote.lock();
+                let wait = remote.wait_for(estimated_tokens, now);
+                if wait.is_zero() {
+                    remote.spend(estimated_tokens);
+                    return;
+                }
+                wait
+            };
+            log::info!("provider rate limit nearly exhausted, waiting {wait:?} for it to reset");
+            // Fresh numbers from another response may lift the limit early.
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = notified => {}
+            }
+        }
+    }
+
+    async fn reserve(&self, estimated_tokens: u64) -> Option<u64> {
+        let limits = &self.state.limits;
+        if limits.requests_per_minute.is_none() && limits.tokens_per_minute.is_none() {
+            return None;
+        }
+
+        loop {
+            let wait = {
+                let now = Instant::now();
+                let mut window = self.state.window.lock();
+                window.prune(now);
+
+                let requests_full = limits
+                    .requests_per_minute
+                    .is_some_and(|limit| window.entries.len() >= limit as usize);
+                // A request larger than the whole budget is let through once
+                // the window is empty, rather than waiting forever.
+                let tokens_full = limits.tokens_per_minute.is_some_and(|limit| {
+                    !window.entries.is_empty() && window.tokens() + estimated_tokens > limit
+                });
+
+                if !requests_full && !tokens_full {
+                    let id = window.next_id;
+                    window.next_id += 1;
+                    window.entries.push_back(WindowEntry {
+                        id,
+                        started_at: now,
+                        tokens: estimated_tokens,
+                    });
+                    return Some(id);
+                }
+                window.next_expiry(now)
+            };
+            log::debug!("rate limit reached, waiting {wait:?} before sending request");
+            tokio::time::sleep(wait.max(Duration::from_millis(10))).await;
+        }
+    }
+}
+
+/// Keeps a request's concurrency slots until dropped.
+pub struct RateLimitGuard {
+    state: Arc<RateLimiterState>,
+    window_entry: Option<u64>,
+    _provider_permit: Option<OwnedSemaphorePermit>,
+    _model_permit: Option<OwnedSemaphorePermit>,
+}
+
+impl RateLimitGuard {
+    /// Replaces the token estimate reserved for this request with its actual usage.
+    pub fn record_usage(&self, tokens: u64) {
+        let Some(id) = self.window_entry else {
+            return;
+        };
+        let mut window = self.state.window.lock();
+        if let Some(entry) = window.entries.iter_mut().find(|entry| entry.id == id) {
+            entry.tokens = tokens;
+        }
+    }
+}
+
+/// Roughly estimates the number of tokens in a request, at four bytes per token.
+pub fn estimate_request_tokens(request: &LanguageModelRequest) -> u64 {
+    let bytes = request
+        .messages
+        .iter()
+        .map(|message| message.string_contents().len())
+        .sum::<usize>()
+        + request
+            .tools
+            .iter()
+            .map(|tool| {
+                tool.name.len() + tool.description.len() + tool.input_schema.to_string().len()
+            })
+            .sum::<usize>();
+    (bytes / 4) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrency_is_shared_per_model() {
+        let limiter = RateLimiter::new(RateLimits {
+            max_concurrent_requests: Some(2),
+            max_concurrent_requests_per_model: Some(1),
+            ..Default::default()
+        });
+        let model_a = LanguageModelId::from("a".to_string());
+        let model_b = LanguageModelId::from("b".to_string());
+
+        let guard_a = limiter.acquire(&model_a, 0).await;
+        let _guard_b = limiter.clone().acquire(&model_b, 0).await;
+
+        let second_a = limiter.acquire(&model_a, 0);
+        futures::pin_mut!(second_a);
+        assert!(futures::poll!(second_a.as_mut()).is_pending());
+
+        drop(guard_a);
+        assert!(futures::poll!(second_a.as_mut()).is_ready());
+    }
+
+    #[tokio::test]
+    async fn test_default_is_unlimited() {
+        let limiter = RateLimiter::default();
+        let model = LanguageModelId::from("a".to_string());
+
+        let mut guards = Vec::new();
+        for _ in 0..16 {
+            let acquire = limiter.acquire(&model, 1_000_000);
+            futures::pin_mut!(acquire);
+            match futures::poll!(acquire.as_mut()) {
+                std::task::Poll::Ready(guard) => guards.push(guard),
+                std::task::Poll::Pending => panic!("default limits should not block"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_reported_limits_to_reset() {
+        let limiter = RateLimiter::new(RateLimits::default());
+        let model = LanguageModelId::from("a".to_string());
+        limiter.update(&ReportedRateLimits {
+            requests: Some(ReportedRateLimit {
+                limit: 50,
+                remaining: 1,
+                reset_in: Duration::from_secs(30),
+            }),
+            ..Default::default()
+        });
+
+        drop(limiter.acquire(&model, 0).await);
+        let second = limiter.acquire(&model, 0);
+        futures::pin_mut!(second);
+        assert!(futures::poll!(second.as_mut()).is_pending());
+
+        limiter.update(&ReportedRateLimits {
+            requests: Some(ReportedRateLimit {
+                limit: 50,
+                remaining: 50,
+                reset_in: Duration::ZERO,
+            }),
+            ..Default::default()
+        });
+        assert!(
+            tokio::time::timeout(Duration::from_secs(5), second)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requests_per_minute() {
+        let limiter = RateLimiter::new(RateLimits {
+            requests_per_minute: Some(1),
+            ..Default::default()
+        });
+        let model = LanguageModelId::from("a".to_string());
+
+        drop(limiter.acquire(&model, 0).await);
+        let second = limiter.acquire(&model, 0);
+        futures::pin_mut!(second);
+        assert!(futures::poll!(second.as_mut()).is_pending());
+    }
+}
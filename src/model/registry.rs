@@ -0,0 +1,276 @@
+use crate::model::language_provider::LanguageModelProvider;
+use crate::model::types::{ConfiguredModel, LanguageModelId, LanguageModelProviderId};
+use anyhow::{Context as _, Result};
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A model reference of the form `provider/model`, e.g. `anthropic/claude-sonnet-4`.
+///
+/// Everything after the first `/` is the model id, so ids that contain slashes
+/// themselves (like OpenRouter's `openrouter/anthropic/claude-sonnet-4`) work too.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SelectedModel {
+    pub provider: LanguageModelProviderId,
+    pub model: LanguageModelId,
+}
+
+impl FromStr for SelectedModel {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let (provider, model) = value
+            .split_once('/')
+            .with_context(|| format!("invalid model `{value}`, expected `provider/model`"))?;
+        anyhow::ensure!(
+            !provider.is_empty() && !model.is_empty(),
+            "invalid model `{value}`, expected `provider/model`"
+        );
+        Ok(Self {
+            provider: LanguageModelProviderId::from(provider.to_string()),
+            model: LanguageModelId::from(model.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for SelectedModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.provider, self.model)
+    }
+}
+
+#[derive(Default)]
+struct LanguageModelRegistryState {
+    providers: BTreeMap<LanguageModelProviderId, Arc<dyn LanguageModelProvider + Send + Sync>>,
+    default_model: Option<ConfiguredModel>,
+    default_fast_model: Option<ConfiguredModel>,
+}
+
+impl LanguageModelRegistryState {
+    fn clear_defaults_of(&mut self, provider_id: &LanguageModelProviderId) {
+        for default in [&mut self.default_model, &mut self.default_fast_model] {
+            if default
+                .as_ref()
+                .is_some_and(|model| model.provider.id() == *provider_id)
+            {
+                *default = None;
+            }
+        }
+    }
+}
+
+/// Holds every registered [`LanguageModelProvider`] and resolves model
+/// references into [`ConfiguredModel`]s.
+#[derive(Default)]
+pub struct LanguageModelRegistry {
+    state: RwLock<LanguageModelRegistryState>,
+}
+
+impl LanguageModelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a provider, replacing any provider with the same id.
+    pub fn register_provider(&self, provider: Arc<dyn LanguageModelProvider + Send + Sync>) {
+        let id = provider.id();
+        let mut state = self.state.write();
+        state.providers.insert(id.clone(), provider);
+        // Defaults may point at models of the provider being replaced.
+        state.clear_defaults_of(&id);
+    }
+
+    pub fn unregister_provider(&self, id: &LanguageModelProviderId) {
+        let mut state = self.state.write();
+        state.providers.remove(id);
+        state.clear_defaults_of(id);
+    }
+
+    pub fn provider(
+        &self,
+        id: &LanguageModelProviderId,
+    ) -> Option<Arc<dyn LanguageModelProvider + Send + Sync>> {
+        self.state.read().providers.get(id).cloned()
+    }
+
+    /// Returns every registered provider, ordered by id.
+    pub fn providers(&self) -> Vec<Arc<dyn LanguageModelProvider + Send + Sync>> {
+        self.state.read().providers.values().cloned().collect()
+    }
+
+    /// Lists the models of every registered provider, e.g. for a model picker.
+    pub fn available_models(&self) -> Vec<ConfiguredModel> {
+        self.providers()
+            .into_iter()
+            .flat_map(|provider| {
+                provider
+                    .provided_models()
+                    .into_iter()
+                    .map(move |model| ConfiguredModel {
+                        provider: provider.clone(),
+                        model,
+                    })
+            })
+            .collect()
+    }
+
+    /// Resolves a `provider/model` reference into a [`ConfiguredModel`].
+    pub fn select_model(&self, model: &str) -> Result<ConfiguredModel> {
+        self.resolve(&model.parse()?)
+    }
+
+    pub fn resolve(&self, selected: &SelectedModel) -> Result<ConfiguredModel> {
+        let provider = self
+            .provider(&selected.provider)
+            .with_context(|| format!("unknown language model provider `{}`", selected.provider))?;
+        let model = provider
+            .provided_models()
+            .into_iter()
+            .find(|model| model.id() == selected.model)
+            .with_context(|| {
+                format!(
+                    "provider `{}` has no model `{}`",
+                    selected.provider, selected.model
+                )
+            })?;
+        Ok(ConfiguredModel { provider, model })
+    }
+
+    /// Sets the model returned by [`LanguageModelRegistry::default_model`].
+    pub fn set_default_model(&self, model: Option<&str>) -> Result<()> {
+        let model = model.map(|model| self.select_model(model)).transpose()?;
+        self.state.write().default_model = model;
+        Ok(())
+    }
+
+    /// Sets the model returned by [`LanguageModelRegistry::default_fast_model`].
+    pub fn set_default_fast_model(&self, model: Option<&str>) -> Result<()> {
+        let model = model.map(|model| self.select_model(model)).transpose()?;
+        self.state.write().default_fast_model = model;
+        Ok(())
+    }
+
+    /// Returns the configured default model, falling back to the default model
+    /// of the first provider that has one.
+    pub fn default_model(&self) -> Option<ConfiguredModel> {
+        if let Some(model) = self.state.read().default_model.clone() {
+            return Some(model);
+        }
+        self.providers().into_iter().find_map(|provider| {
+            let model = provider.default_model()?;
+            Some(ConfiguredModel { provider, model })
+        })
+    }
+
+    /// Returns the configured fast model, falling back to the provider of the
+    /// default model and then to the default model itself.
+    pub fn default_fast_model(&self) -> Option<ConfiguredModel> {
+        if let Some(model) = self.state.read().default_fast_model.clone() {
+            return Some(model);
+        }
+        let default = self.default_model()?;
+        match default.provider.default_fast_model() {
+            Some(model) => Some(ConfiguredModel {
+                provider: default.provider,
+                model,
+            }),
+            None => Some(default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        FakeLanguageModel, FakeLanguageModelProvider, LanguageModel, LanguageModelProviderName,
+    };
+
+    fn registry() -> LanguageModelRegistry {
+        let registry = LanguageModelRegistry::new();
+        registry.register_provider(Arc::new(FakeLanguageModelProvider::new(
+            LanguageModelProviderId::new("acme"),
+            vec![
+                Arc::new(FakeLanguageModel::with_id("big")),
+                Arc::new(FakeLanguageModel::with_id("small")),
+            ],
+        )));
+        registry.register_provider(Arc::new(FakeLanguageModelProvider::new(
+            LanguageModelProviderId::new("router"),
+            vec![Arc::new(FakeLanguageModel::with_id("acme/big"))],
+        )));
+        registry
+    }
+
+    /// Relies on the trait's default for `default_fast_model`.
+    struct SingleModelProvider(Arc<FakeLanguageModel>);
+
+    impl LanguageModelProvider for SingleModelProvider {
+        fn id(&self) -> LanguageModelProviderId {
+            LanguageModelProviderId::new("single")
+        }
+
+        fn name(&self) -> LanguageModelProviderName {
+            LanguageModelProviderName::new("Single")
+        }
+
+        fn default_model(&self) -> Option<Arc<dyn LanguageModel>> {
+            Some(self.0.clone())
+        }
+
+        fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>> {
+            vec![self.0.clone()]
+        }
+    }
+
+    #[test]
+    fn test_select_model() {
+        let registry = registry();
+
+        let model = registry.select_model("acme/small").unwrap();
+        assert_eq!(model.provider.id(), LanguageModelProviderId::new("acme"));
+        assert_eq!(model.model.id(), LanguageModelId::from("small".to_string()));
+
+        let model = registry.select_model("router/acme/big").unwrap();
+        assert_eq!(model.provider.id(), LanguageModelProviderId::new("router"));
+
+        assert!(registry.select_model("acme").is_err());
+        assert!(registry.select_model("acme/huge").is_err());
+        assert!(registry.select_model("other/big").is_err());
+        assert_eq!(registry.available_models().len(), 3);
+    }
+
+    #[test]
+    fn test_default_models() {
+        let registry = registry();
+
+        let default = registry.default_model().unwrap();
+        assert_eq!(default.model.id(), LanguageModelId::from("big".to_string()));
+        let fast = registry.default_fast_model().unwrap();
+        assert_eq!(fast.model.id(), LanguageModelId::from("small".to_string()));
+
+        registry.set_default_model(Some("router/acme/big")).unwrap();
+        let default = registry.default_model().unwrap();
+        assert_eq!(
+            default.provider.id(),
+            LanguageModelProviderId::new("router")
+        );
+
+        registry.unregister_provider(&LanguageModelProviderId::new("router"));
+        let default = registry.default_model().unwrap();
+        assert_eq!(default.provider.id(), LanguageModelProviderId::new("acme"));
+    }
+
+    #[test]
+    fn test_default_fast_model_falls_back_to_default_model() {
+        let registry = LanguageModelRegistry::new();
+        registry.register_provider(Arc::new(SingleModelProvider(Arc::new(
+            FakeLanguageModel::with_id("only"),
+        ))));
+
+        let fast = registry.default_fast_model().unwrap();
+        assert_eq!(fast.model.id(), LanguageModelId::from("only".to_string()));
+    }
+}
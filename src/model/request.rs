@@ -97,4 +97,42 @@ pub struct LanguageModelRequest {
     pub stop: Vec<String>,
     pub temperature: Option<f32>,
     pub thinking_allowed: bool,
+    /// Overrides how much the model reasons. Without it, models think as
+    /// their mode says when `thinking_allowed`.
+    pub thinking: Option<ThinkingConfig>,
+    /// Whether the model may use several tools in one turn. Without it, the
+    /// provider's default applies, which is to allow it for most.
+    pub parallel_tool_calls: Option<bool>,
+}
+
+/// How much a model reasons before answering, in a form each provider
+/// translates to what its API takes.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ThinkingConfig {
+    /// As little reasoning as the model allows.
+    Off,
+    Effort {
+        effort: ReasoningEffort,
+    },
+    /// The most tokens to spend on reasoning.
+    Budget {
+        budget_tokens: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+/// The forms of [`ThinkingConfig`] a model takes as they are. The other form
+/// is approximated, and models taking neither ignore the config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ThinkingSupport {
+    pub effort: bool,
+    pub budget: bool,
 }
@@ -0,0 +1,202 @@
+use crate::model::request::{LanguageModelRequestMessage, MessageContent, Role};
+use crate::model::types::{
+    LanguageModelCompletionEvent, LanguageModelToolUse, LanguageModelToolUseId, StopReason,
+    TokenUsage,
+};
+use std::collections::HashMap;
+
+/// A complete, non-streamed answer from a language model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageModelResponse {
+    pub message_id: Option<String>,
+    /// The assistant message assembled from the streamed events.
+    pub message: LanguageModelRequestMessage,
+    pub usage: TokenUsage,
+    pub stop_reason: StopReason,
+    /// Tool uses whose input was not valid JSON, along with the parse error.
+    ///
+    /// These tool uses still appear in `message` with an empty input object.
+    pub tool_use_parse_errors: HashMap<LanguageModelToolUseId, String>,
+}
+
+impl LanguageModelResponse {
+    /// Returns the visible text of the response, without thinking blocks.
+    pub fn text(&self) -> String {
+        self.message
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                MessageContent::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn tool_uses(&self) -> impl Iterator<Item = &LanguageModelToolUse> {
+        self.message
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                MessageContent::ToolUse(tool_use) => Some(tool_use),
+                _ => None,
+            })
+    }
+}
+
+/// Accumulates [`LanguageModelCompletionEvent`]s into a [`LanguageModelResponse`].
+#[derive(Debug, Default)]
+pub struct LanguageModelResponseBuilder {
+    message_id: Option<String>,
+    content: Vec<MessageContent>,
+    usage: TokenUsage,
+    stop_reason: Option<StopReason>,
+    tool_use_parse_errors: HashMap<LanguageModelToolUseId, String>,
+}
+
+impl LanguageModelResponseBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_event(&mut self, event: &LanguageModelCompletionEvent) {
+        match event {
+            LanguageModelCompletionEvent::StartMessage { message_id } => {
+                self.message_id = Some(message_id.clone());
+            }
+            LanguageModelCompletionEvent::Text(text) => {
+                if let Some(MessageContent::Text(last)) = self.content.last_mut() {
+                    last.push_str(text);
+                } else {
+                    self.content.push(MessageContent::Text(text.clone()));
+                }
+            }
+            LanguageModelCompletionEvent::Thinking { text, signature } => {
+                if let Some(MessageContent::Thinking {
+                    text: last_text,
+                    signature: last_signature,
+                }) = self.content.last_mut()
+                {
+                    last_text.push_str(text);
+                    if signature.is_some() {
+                        *last_signature = signature.clone();
+                    }
+                } else {
+                    self.content.push(MessageContent::Thinking {
+                        text: text.clone(),
+                        signature: signature.clone(),
+                    });
+                }
+            }
+            LanguageModelCompletionEvent::RedactedThinking { data } => {
+                self.content
+                    .push(MessageContent::RedactedThinking(data.clone()));
+            }
+            LanguageModelCompletionEvent::ToolUse(tool_use) => {
+                // Partial tool uses are only useful for rendering progress; the
+                // completed one carries the final input.
+                if tool_use.is_input_complete {
+                    self.content.push(MessageContent::ToolUse(tool_use.clone()));
+                }
+            }
+            LanguageModelCompletionEvent::ToolUseJsonParseError {
+                id,
+                tool_name,
+                raw_input,
+                json_parse_error,
+            } => {
+                self.content
+                    .push(MessageContent::ToolUse(LanguageModelToolUse {
+                        id: id.clone(),
+                        name: tool_name.clone(),
+                        raw_input: raw_input.to_string(),
+                        input: serde_json::Value::Object(serde_json::Map::default()),
+                        is_input_complete: true,
+                    }));
+                self.tool_use_parse_errors
+                    .insert(id.clone(), json_parse_error.clone());
+            }
+            LanguageModelCompletionEvent::UsageUpdate(usage) => {
+                // Providers report cumulative usage, so the latest update wins.
+                self.usage = *usage;
+            }
+            LanguageModelCompletionEvent::Stop(stop_reason) => {
+                self.stop_reason = Some(*stop_reason);
+            }
+            LanguageModelCompletionEvent::StatusUpdate(_) => {}
+        }
+    }
+
+    pub fn build(self) -> LanguageModelResponse {
+        LanguageModelResponse {
+            message_id: self.message_id,
+            message: LanguageModelRequestMessage {
+                role: Role::Assistant,
+                content: self.content,
+                cache: false,
+            },
+            usage: self.usage,
+            stop_reason: self.stop_reason.unwrap_or(StopReason::EndTurn),
+            tool_use_parse_errors: self.tool_use_parse_errors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{FakeLanguageModel, LanguageModel, LanguageModelRequest};
+
+    #[tokio::test]
+    async fn test_complete_aggregates_events() {
+        let model = FakeLanguageModel::new();
+        model.push_response([
+            LanguageModelCompletionEvent::StartMessage {
+                message_id: "msg_1".into(),
+            },
+            LanguageModelCompletionEvent::Thinking {
+                text: "Hmm, ".into(),
+                signature: None,
+            },
+            LanguageModelCompletionEvent::Thinking {
+                text: "ok.".into(),
+                signature: Some("sig".into()),
+            },
+            LanguageModelCompletionEvent::Text("Hello, ".into()),
+            LanguageModelCompletionEvent::Text("world".into()),
+            LanguageModelCompletionEvent::ToolUseJsonParseError {
+                id: "call_1".into(),
+                tool_name: "search".into(),
+                raw_input: "{".into(),
+                json_parse_error: "EOF".into(),
+            },
+            LanguageModelCompletionEvent::UsageUpdate(TokenUsage {
+                input_tokens: 10,
+                output_tokens: 5,
+                ..Default::default()
+            }),
+            LanguageModelCompletionEvent::Stop(StopReason::ToolUse),
+        ]);
+
+        let response = model
+            .complete(LanguageModelRequest::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response.message_id.as_deref(), Some("msg_1"));
+        assert_eq!(response.text(), "Hello, world");
+        assert_eq!(
+            response.message.content[0],
+            MessageContent::Thinking {
+                text: "Hmm, ok.".into(),
+                signature: Some("sig".into()),
+            }
+        );
+        assert_eq!(response.tool_uses().count(), 1);
+        assert_eq!(
+            response.tool_use_parse_errors.get(&"call_1".into()),
+            Some(&"EOF".to_string())
+        );
+        assert_eq!(response.usage.total_tokens(), 15);
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+    }
+}
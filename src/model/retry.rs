@@ -0,0 +1,281 @@
+use crate::model::{
+    CompletionRequestStatus, LanguageModel, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelId, LanguageModelName, LanguageModelProviderId,
+    LanguageModelProviderName, LanguageModelRequest, LanguageModelToolSchemaFormat,
+    ThinkingSupport,
+};
+use futures::channel::mpsc;
+use futures::{FutureExt, StreamExt, future, stream};
+use futures_core::stream::BoxStream;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryConfig {
+    /// The total number of attempts, including the first one.
+    pub max_attempts: usize,
+    /// The delay before the first retry. It doubles after every failed attempt.
+    pub base_delay: Duration,
+    /// The longest delay between two attempts, unless the provider asks for more
+    /// with a `retry-after`.
+    pub max_delay: Duration,
+    /// Whether to emit a [`CompletionRequestStatus::Failed`] update into the
+    /// stream before waiting to retry, so that UIs can show "retrying in 8s".
+    /// These updates always carry `retry_after` and are not final: the
+    /// request is retried after that delay.
+    pub emit_status_updates: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+            emit_status_updates: true,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Returns how long to wait after the given (1-based) failed attempt.
+    pub fn delay_for(&self, attempt: usize, error: &LanguageModelCompletionError) -> Duration {
+        if let Some(retry_after) = error.retry_after() {
+            return retry_after;
+        }
+        let exponent = attempt.saturating_sub(1).min(16) as u32;
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_delay);
+        // "Equal jitter": wait at least half of the backoff, so that clients
+        // that failed together don't all retry at the same instant.
+        backoff.mul_f64(0.5 + 0.5 * jitter())
+    }
+}
+
+/// Returns a pseudo-random number in `[0, 1)`.
+fn jitter() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A [`LanguageModel`] that retries requests failing with a retryable
+/// [`LanguageModelCompletionError`], with exponential backoff.
+///
+/// Only failures that happen before the first event is streamed are retried,
+/// so the consumer never sees a response twice.
+pub struct RetryingLanguageModel {
+    inner: Arc<dyn LanguageModel>,
+    config: RetryConfig,
+}
+
+impl RetryingLanguageModel {
+    pub fn new(inner: Arc<dyn LanguageModel>) -> Self {
+        Self {
+            inner,
+            config: RetryConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: RetryConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn inner(&self) -> &Arc<dyn LanguageModel> {
+        &self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl LanguageModel for RetryingLanguageModel {
+    fn id(&self) -> LanguageModelId {
+        self.inner.id()
+    }
+
+    fn name(&self) -> LanguageModelName {
+        self.inner.name()
+    }
+
+    fn provider_id(&self) -> LanguageModelProviderId {
+        self.inner.provider_id()
+    }
+
+    fn provider_name(&self) -> LanguageModelProviderName {
+        self.inner.provider_name()
+    }
+
+    fn max_token_count(&self) -> u64 {
+        self.inner.max_token_count()
+    }
+
+    fn max_output_tokens(&self) -> Option<u64> {
+        self.inner.max_output_tokens()
+    }
+
+    fn tool_input_format(&self) -> LanguageModelToolSchemaFormat {
+        self.inner.tool_input_format()
+    }
+
+    async fn stream_completion(
+        &self,
+        request: LanguageModelRequest,
+    ) -> Result<
+        BoxStream<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+        LanguageModelCompletionError,
+    > {
+        let inner = self.inner.clone();
+        let config = self.config.clone();
+        let (tx, rx) = mpsc::unbounded();
+        let driver = async move {
+            let request_id = Uuid::new_v4();
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                let error = match inner.stream_completion(request.clone()).await {
+                    Ok(mut events) => match events.next().await {
+                        Some(Err(error)) => error,
+                        Some(Ok(event)) => {
+                            if tx.unbounded_send(Ok(event)).is_ok() {
+                                while let Some(event) = events.next().await {
+                                    if tx.unbounded_send(event).is_err() {
+                                        break;
+                                    }
+                                }
+                            }
+                            return;
+                        }
+                        None => return,
+                    },
+                    Err(error) => error,
+                };
+
+                if attempt >= config.max_attempts || !error.is_retryable() {
+                    tx.unbounded_send(Err(error)).ok();
+                    return;
+                }
+
+                let delay = config.delay_for(attempt, &error);
+                log::warn!(
+                    "{} request failed (attempt {attempt}/{}), retrying in {delay:?}: {error}",
+                    inner.provider_name(),
+                    config.max_attempts,
+                );
+                if config.emit_status_updates {
+                    let status = CompletionRequestStatus::Failed {
+                        code: error.code().to_string(),
+                        message: error.to_string(),
+                        request_id,
+                        retry_after: Some(delay.as_secs_f64()),
+                    };
+                    if tx
+                        .unbounded_send(Ok(LanguageModelCompletionEvent::StatusUpdate(status)))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+
+                tokio::time::sleep(delay).await;
+            }
+        }
+        .into_stream()
+        .filter_map(|()| future::ready(None));
+        Ok(stream::select(rx, driver).boxed())
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.inner.supports_tools()
+    }
+
+    fn supports_burn_mode(&self) -> bool {
+        self.inner.supports_burn_mode()
+    }
+
+    fn thinking_support(&self) -> ThinkingSupport {
+        self.inner.thinking_support()
+    }
+
+    fn max_token_count_in_burn_mode(&self) -> Option<u64> {
+        self.inner.max_token_count_in_burn_mode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{FakeLanguageModel, StopReason};
+
+    fn test_config() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            emit_status_updates: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_retryable_errors() {
+        let fake = Arc::new(FakeLanguageModel::new());
+        fake.push_error(LanguageModelCompletionError::ServerOverloaded {
+            provider: fake.provider_name(),
+            retry_after: Some(Duration::from_millis(1)),
+        });
+        fake.push_response([
+            LanguageModelCompletionEvent::Text("hi".into()),
+            LanguageModelCompletionEvent::Stop(StopReason::EndTurn),
+        ]);
+        let model = RetryingLanguageModel::new(fake.clone()).with_config(test_config());
+
+        let events = model
+            .stream_completion(LanguageModelRequest::default())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            events[0],
+            Ok(LanguageModelCompletionEvent::StatusUpdate(
+                CompletionRequestStatus::Failed { ref code, retry_after: Some(_), .. }
+            )) if code == "server_overloaded"
+        ));
+        assert!(matches!(
+            events[1],
+            Ok(LanguageModelCompletionEvent::Text(_))
+        ));
+        assert_eq!(fake.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_other_errors() {
+        let fake = Arc::new(FakeLanguageModel::new());
+        fake.push_error(LanguageModelCompletionError::AuthenticationError {
+            provider: fake.provider_name(),
+            message: "invalid x-api-key".into(),
+        });
+        let model = RetryingLanguageModel::new(fake.clone()).with_config(test_config());
+
+        let events = model
+            .stream_completion(LanguageModelRequest::default())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(matches!(
+            events.as_slice(),
+            [Err(
+                LanguageModelCompletionError::AuthenticationError { .. }
+            )]
+        ));
+        assert_eq!(fake.requests().len(), 1);
+    }
+}
@@ -15,6 +15,11 @@ impl From<String> for LanguageModelId {
         Self(SharedString::from(value))
     }
 }
+impl fmt::Display for LanguageModelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Ord, PartialOrd)]
 pub struct LanguageModelName(pub SharedString);
 impl From<String> for LanguageModelName {
@@ -31,6 +36,16 @@ impl LanguageModelProviderId {
         Self(SharedString::new_static(id))
     }
 }
+impl From<String> for LanguageModelProviderId {
+    fn from(value: String) -> Self {
+        Self(SharedString::from(value))
+    }
+}
+impl fmt::Display for LanguageModelProviderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Ord, PartialOrd)]
 pub struct LanguageModelProviderName(pub SharedString);
@@ -256,6 +271,10 @@ pub struct TokenUsage {
     pub cache_creation_input_tokens: u64,
     #[serde(default, skip_serializing_if = "is_default")]
     pub cache_read_input_tokens: u64,
+    /// The part of `output_tokens` spent on reasoning, if the provider
+    /// reports it.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub reasoning_tokens: u64,
 }
 impl TokenUsage {
     pub fn total_tokens(&self) -> u64 {
@@ -275,6 +294,7 @@ impl Add<TokenUsage> for TokenUsage {
             cache_creation_input_tokens: self.cache_creation_input_tokens
                 + other.cache_creation_input_tokens,
             cache_read_input_tokens: self.cache_read_input_tokens + other.cache_read_input_tokens,
+            reasoning_tokens: self.reasoning_tokens + other.reasoning_tokens,
         }
     }
 }
@@ -289,6 +309,7 @@ impl Sub<TokenUsage> for TokenUsage {
             cache_creation_input_tokens: self.cache_creation_input_tokens
                 - other.cache_creation_input_tokens,
             cache_read_input_tokens: self.cache_read_input_tokens - other.cache_read_input_tokens,
+            reasoning_tokens: self.reasoning_tokens - other.reasoning_tokens,
         }
     }
 }
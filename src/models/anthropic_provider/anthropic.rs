@@ -7,12 +7,16 @@ use futures::Stream;
 use futures::{FutureExt, StreamExt, future::BoxFuture, stream::BoxStream};
 use std::collections::{BTreeMap, HashMap};
 
+use crate::aws::{AwsCredentials, load_aws_region};
+use crate::common::SecretString;
+use crate::google::ServiceAccountCredentialProvider;
 use crate::http_client::HttpClient;
 use crate::model::{
     self, LanguageModel, LanguageModelCompletionError, LanguageModelId, LanguageModelName,
     LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
-    LanguageModelRequest, LanguageModelToolChoice, LanguageModelToolResultContent, MessageContent,
-    Role,
+    CredentialProvider, EnvCredentialProvider, LanguageModelRequest, LanguageModelToolChoice,
+    LanguageModelToolResultContent, MessageContent, RateLimiter, RateLimits, ReasoningEffort,
+    Role, ThinkingConfig, ThinkingSupport, estimate_request_tokens, resolve_api_key,
 };
 use crate::model::{LanguageModelCompletionEvent, LanguageModelToolUse, StopReason};
 use schemars::JsonSchema;
@@ -29,9 +33,116 @@ const PROVIDER_NAME: LanguageModelProviderName = model::ANTHROPIC_PROVIDER_NAME;
 
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct AnthropicSettings {
+    /// Defaults to [`anthropic::ANTHROPIC_API_URL`] when empty.
     pub api_url: String,
-    // pub available_models: Vec<AvailableModel>,
-    pub api_key: String,
+    /// Models offered in addition to the built-in ones, or overriding them.
+    pub available_models: Vec<AvailableModel>,
+    pub api_key: SecretString,
+    /// Headers sent with every request, e.g. for a proxy in front of the API.
+    pub extra_headers: BTreeMap<String, String>,
+    /// Invokes the models on Amazon Bedrock instead of the Anthropic API.
+    pub bedrock: Option<BedrockSettings>,
+    /// Invokes the models on Google Cloud Vertex AI instead of the Anthropic
+    /// API. `api_key` then holds an OAuth access token, if any.
+    pub vertex: Option<VertexSettings>,
+    /// Requests whole responses from the Anthropic API instead of streaming
+    /// them, e.g. for a proxy that buffers server-sent events. Bedrock and
+    /// Vertex AI always stream.
+    pub disable_streaming: bool,
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct BedrockSettings {
+    /// Defaults to `AWS_REGION`, or the region of the profile.
+    pub region: Option<String>,
+    /// The profile in `~/.aws/credentials` and `~/.aws/config`. When unset,
+    /// credentials are taken from the environment before `AWS_PROFILE`.
+    pub profile: Option<String>,
+    /// Used instead of loading credentials from the environment or profile.
+    pub credentials: Option<AwsCredentials>,
+    /// Replaces the regional runtime endpoint, e.g. for a VPC endpoint.
+    pub endpoint_url: Option<String>,
+    /// Prepended to the model ids to use a cross-region inference profile,
+    /// e.g. `us`, which newer models require.
+    pub inference_profile_prefix: Option<String>,
+    /// Bedrock model ids by model id, overriding the built-in ones.
+    pub model_ids: BTreeMap<String, String>,
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct VertexSettings {
+    pub project_id: String,
+    /// Defaults to `us-east5`, where all Claude models are offered, when empty.
+    pub location: String,
+    /// Replaces the Vertex AI endpoint of the location.
+    pub endpoint_url: Option<String>,
+    /// Vertex AI model ids by model id, overriding the built-in ones.
+    pub model_ids: BTreeMap<String, String>,
+}
+
+impl VertexSettings {
+    pub fn location(&self) -> &str {
+        if self.location.is_empty() {
+            "us-east5"
+        } else {
+            &self.location
+        }
+    }
+
+    pub fn model_id(&self, model: &anthropic::Model) -> String {
+        self.model_ids
+            .get(model.id())
+            .map_or(model.vertex_id(), String::as_str)
+            .to_string()
+    }
+}
+
+impl BedrockSettings {
+    pub fn model_id(&self, model: &anthropic::Model) -> String {
+        if let Some(model_id) = self.model_ids.get(model.id()) {
+            return model_id.clone();
+        }
+        match &self.inference_profile_prefix {
+            Some(prefix) if !matches!(model, anthropic::Model::Custom { .. }) => {
+                format!("{prefix}.{}", model.bedrock_id())
+            }
+            _ => model.bedrock_id().to_string(),
+        }
+    }
+
+    async fn load_credentials(&self) -> anyhow::Result<AwsCredentials> {
+        match &self.credentials {
+            Some(credentials) => Ok(credentials.clone()),
+            None => AwsCredentials::load(self.profile.as_deref()).await,
+        }
+    }
+
+    async fn load_region(&self) -> anyhow::Result<String> {
+        if let Some(region) = &self.region {
+            return Ok(region.clone());
+        }
+        load_aws_region(self.profile.as_deref())
+            .await?
+            .context("no AWS region configured for Bedrock")
+    }
+}
+
+impl AnthropicSettings {
+    pub fn api_url(&self) -> &str {
+        if self.api_url.is_empty() {
+            anthropic::ANTHROPIC_API_URL
+        } else {
+            &self.api_url
+        }
+    }
+
+    /// Returns `settings`, or the globally registered settings without them.
+    fn resolve(settings: &Option<Arc<Self>>) -> Arc<Self> {
+        settings
+            .clone()
+            .or_else(|| global_registry::get!(AnthropicSettings).ok())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
@@ -85,6 +196,10 @@ impl From<AnthropicModelMode> for ModelMode {
 
 pub struct AnthropicLanguageModelProvider {
     http_client: Arc<dyn HttpClient>,
+    settings: Option<Arc<AnthropicSettings>>,
+    credentials: Arc<dyn CredentialProvider>,
+    vertex_credentials: Arc<dyn CredentialProvider>,
+    request_limiter: RateLimiter,
     // state: gpui::Entity<State>,
 }
 
@@ -92,8 +207,42 @@ const ANTHROPIC_API_KEY_VAR: &str = "ANTHROPIC_API_KEY";
 
 
 impl AnthropicLanguageModelProvider {
-    pub fn new(http_client: Arc<dyn HttpClient>) -> Self {
-        Self { http_client }
+    /// Creates a provider talking to the endpoint described by `settings`.
+    ///
+    /// Without settings, the [`AnthropicSettings`] registered in
+    /// `global_registry` are looked up on every request. When no API key is
+    /// configured, it is read from `ANTHROPIC_API_KEY`. For Vertex AI, access
+    /// tokens are minted with the service account key named by
+    /// `GOOGLE_APPLICATION_CREDENTIALS` instead.
+    pub fn new(http_client: Arc<dyn HttpClient>, settings: Option<AnthropicSettings>) -> Self {
+        Self {
+            vertex_credentials: Arc::new(ServiceAccountCredentialProvider::from_env(
+                http_client.clone(),
+            )),
+            http_client,
+            settings: settings.map(Arc::new),
+            credentials: Arc::new(EnvCredentialProvider::new(ANTHROPIC_API_KEY_VAR)),
+            request_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Replaces where the API key comes from when the settings have none.
+    pub fn with_credentials(mut self, credentials: Arc<dyn CredentialProvider>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Replaces where Vertex AI access tokens come from when the settings have
+    /// no API key.
+    pub fn with_vertex_credentials(mut self, credentials: Arc<dyn CredentialProvider>) -> Self {
+        self.vertex_credentials = credentials;
+        self
+    }
+
+    /// Replaces the limits shared by every model this provider creates.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.request_limiter = RateLimiter::new(limits);
+        self
     }
 
     pub fn create_language_model(&self, model: anthropic::Model) -> Arc<dyn LanguageModel> {
@@ -101,10 +250,15 @@ impl AnthropicLanguageModelProvider {
             id: LanguageModelId::from(model.id().to_string()),
             model,
             http_client: self.http_client.clone(),
+            settings: self.settings.clone(),
+            credentials: self.credentials.clone(),
+            vertex_credentials: self.vertex_credentials.clone(),
+            request_limiter: self.request_limiter.clone(),
         })
     }
 }
 
+#[async_trait::async_trait]
 impl LanguageModelProvider for AnthropicLanguageModelProvider {
     fn id(&self) -> LanguageModelProviderId {
         PROVIDER_ID
@@ -133,64 +287,64 @@ impl LanguageModelProvider for AnthropicLanguageModelProvider {
     // }
 
     fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>> {
-        // let mut models = BTreeMap::default();
-        //
-        // // Add base models from anthropic::Model::iter()
-        // for model in anthropic::Model::iter() {
-        //     if !matches!(model, anthropic::Model::Custom { .. }) {
-        //         models.insert(model.id().to_string(), model);
-        //     }
-        // }
-        //
-        // // Override with available models from settings
-        // for model in AllLanguageModelSettings::get_global(cx)
-        //     .anthropic
-        //     .available_models
-        //     .iter()
-        // {
-        //     models.insert(
-        //         model.name.clone(),
-        //         anthropic::Model::Custom {
-        //             name: model.name.clone(),
-        //             display_name: model.display_name.clone(),
-        //             max_tokens: model.max_tokens,
-        //             tool_override: model.tool_override.clone(),
-        //             cache_configuration: model.cache_configuration.as_ref().map(|config| {
-        //                 anthropic::AnthropicModelCacheConfiguration {
-        //                     max_cache_anchors: config.max_cache_anchors,
-        //                     should_speculate: config.should_speculate,
-        //                     min_total_token: config.min_total_token,
-        //                 }
-        //             }),
-        //             max_output_tokens: model.max_output_tokens,
-        //             default_temperature: model.default_temperature,
-        //             extra_beta_headers: model.extra_beta_headers.clone(),
-        //             mode: model.mode.clone().unwrap_or_default().into(),
-        //         },
-        //     );
-        // }
-        //
-        // models
-        //     .into_values()
-        //     .map(|model| self.create_language_model(model))
-        //     .collect()
-        todo!()
-    }
-
-    // fn is_authenticated(&self, cx: &App) -> bool {
-    //     self.state.read(cx).is_authenticated()
-    // }
+        let mut models = BTreeMap::default();
 
-    // fn authenticate(&self, cx: &mut App) -> Task<Result<(), AuthenticateError>> {
-    //     self.state.update(cx, |state, cx| state.authenticate(cx))
-    // }
+        // Add base models from anthropic::Model::iter()
+        for model in anthropic::Model::iter() {
+            if !matches!(model, anthropic::Model::Custom { .. }) {
+                models.insert(model.id().to_string(), model);
+            }
+        }
+
+        // Override with available models from settings
+        for model in &AnthropicSettings::resolve(&self.settings).available_models {
+            models.insert(
+                model.name.clone(),
+                anthropic::Model::Custom {
+                    name: model.name.clone(),
+                    display_name: model.display_name.clone(),
+                    max_tokens: model.max_tokens,
+                    tool_override: model.tool_override.clone(),
+                    cache_configuration: None,
+                    max_output_tokens: model.max_output_tokens,
+                    default_temperature: model.default_temperature,
+                    extra_beta_headers: model.extra_beta_headers.clone(),
+                    mode: model.mode.clone().unwrap_or_default().into(),
+                },
+            );
+        }
+
+        models
+            .into_values()
+            .map(|model| self.create_language_model(model))
+            .collect()
+    }
+
+    async fn authenticate(&self) -> anyhow::Result<()> {
+        let settings = AnthropicSettings::resolve(&self.settings);
+        if let Some(bedrock) = &settings.bedrock {
+            bedrock.load_credentials().await?;
+            bedrock.load_region().await?;
+            return Ok(());
+        }
+        let credentials = if settings.vertex.is_some() {
+            &self.vertex_credentials
+        } else {
+            &self.credentials
+        };
+        resolve_api_key(&settings.api_key, credentials.as_ref(), PROVIDER_NAME).await?;
+        Ok(())
+    }
 }
 
 pub struct AnthropicModel {
     id: LanguageModelId,
     model: anthropic::Model,
     http_client: Arc<dyn HttpClient>,
-    // request_limiter: RateLimiter,
+    settings: Option<Arc<AnthropicSettings>>,
+    credentials: Arc<dyn CredentialProvider>,
+    vertex_credentials: Arc<dyn CredentialProvider>,
+    request_limiter: RateLimiter,
 }
 
 pub fn count_anthropic_tokens(
@@ -257,6 +411,16 @@ pub fn count_anthropic_tokens(
 }
 
 impl AnthropicModel {
+    /// Returns where the key comes from for the endpoint `settings` target,
+    /// since global settings may switch to Vertex AI after the model is created.
+    fn credentials(&self, settings: &AnthropicSettings) -> &dyn CredentialProvider {
+        if settings.vertex.is_some() {
+            self.vertex_credentials.as_ref()
+        } else {
+            self.credentials.as_ref()
+        }
+    }
+
     async fn stream_completion(
         &self,
         request: anthropic::Request,
@@ -268,13 +432,117 @@ impl AnthropicModel {
     {
         let http_client = self.http_client.clone();
 
-        let anthropic_settings =
-            global_registry::get!(AnthropicSettings).expect("AnthropicSettings not found");
-        let api_key = anthropic_settings.api_key.clone();
-        let api_url = anthropic_settings.api_url.clone();
+        let settings = AnthropicSettings::resolve(&self.settings);
+        if let Some(bedrock) = &settings.bedrock {
+            return self
+                .stream_bedrock_completion(bedrock, &settings.extra_headers, request)
+                .await;
+        }
+        let api_key = resolve_api_key(
+            &settings.api_key,
+            self.credentials(&settings),
+            PROVIDER_NAME,
+        )
+        .await?;
 
-         anthropic::stream_completion(http_client.as_ref(), &api_url, &api_key, request).await
-            .map_err(Into::<LanguageModelCompletionError>::into)
+        let response = match &settings.vertex {
+            Some(vertex) => {
+                if vertex.project_id.is_empty() {
+                    return Err(anyhow!("the Vertex AI project_id is not configured").into());
+                }
+                let model_id = vertex.model_id(&self.model);
+                let endpoint = anthropic::VertexEndpoint {
+                    project_id: &vertex.project_id,
+                    location: vertex.location(),
+                    model_id: &model_id,
+                    url: vertex.endpoint_url.as_deref(),
+                };
+                anthropic::stream_vertex_completion(
+                    http_client.as_ref(),
+                    &endpoint,
+                    &api_key,
+                    &settings.extra_headers,
+                    request,
+                )
+                .await
+                .map(|(stream, rate_limits)| (stream, Some(rate_limits)))
+            }
+            None => {
+                anthropic::stream_completion_with_rate_limit_info(
+                    http_client.as_ref(),
+                    settings.api_url(),
+                    &api_key,
+                    &settings.extra_headers,
+                    request,
+                )
+                .await
+            }
+        };
+        let (stream, rate_limits) = response.map_err(|error| self.map_error(error))?;
+        if let Some(rate_limits) = rate_limits {
+            self.request_limiter.update(&(&rate_limits).into());
+        }
+        Ok(stream)
+    }
+
+    /// Requests the whole response at once, which only the Anthropic API
+    /// supports here.
+    async fn complete(
+        &self,
+        settings: &AnthropicSettings,
+        request: anthropic::Request,
+    ) -> Result<anthropic::Response, LanguageModelCompletionError> {
+        let api_key =
+            resolve_api_key(&settings.api_key, self.credentials(settings), PROVIDER_NAME).await?;
+        let (response, rate_limits) = anthropic::complete_with_rate_limit_info(
+            self.http_client.as_ref(),
+            settings.api_url(),
+            &api_key,
+            &settings.extra_headers,
+            request,
+        )
+        .await
+        .map_err(|error| self.map_error(error))?;
+        self.request_limiter.update(&(&rate_limits).into());
+        Ok(response)
+    }
+
+    fn map_error(&self, error: AnthropicError) -> LanguageModelCompletionError {
+        let error: LanguageModelCompletionError = error.into();
+        if error.is_authentication_error() {
+            // The key may have been rotated; load it again next time.
+            self.credentials.invalidate();
+            self.vertex_credentials.invalidate();
+        }
+        error
+    }
+
+    async fn stream_bedrock_completion(
+        &self,
+        bedrock: &BedrockSettings,
+        extra_headers: &BTreeMap<String, String>,
+        request: anthropic::Request,
+    ) -> Result<
+        BoxStream<'static, Result<anthropic::Event, AnthropicError>>,
+        LanguageModelCompletionError,
+    > {
+        let credentials = bedrock.load_credentials().await?;
+        let region = bedrock.load_region().await?;
+        let model_id = bedrock.model_id(&self.model);
+        let endpoint = anthropic::BedrockEndpoint {
+            region: &region,
+            model_id: &model_id,
+            url: bedrock.endpoint_url.as_deref(),
+        };
+        anthropic::stream_bedrock_completion(
+            self.http_client.as_ref(),
+            &endpoint,
+            &credentials,
+            extra_headers,
+            request,
+        )
+        .await
+        .map_err(Into::into)
     }
 }
 #[async_trait::async_trait]
@@ -305,11 +573,15 @@ impl LanguageModel for AnthropicModel {
 
     async fn stream_completion(
         &self,
-        request: LanguageModelRequest,
+        mut request: LanguageModelRequest,
     ) -> Result<
         BoxStream<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         LanguageModelCompletionError,
     > {
+        let estimated_tokens = estimate_request_tokens(&request);
+        if !self.model.supports_thinking() {
+            request.thinking = None;
+        }
         let request = into_anthropic(
             request,
             self.model.request_id().into(),
@@ -317,9 +589,23 @@ impl LanguageModel for AnthropicModel {
             self.model.max_output_tokens(),
             self.model.mode(),
         );
-        let response = self.stream_completion(request).await?;
-        let stream = AnthropicEventMapper::new().map_stream(response);
-        Ok(stream.boxed())
+        let settings = AnthropicSettings::resolve(&self.settings);
+        if settings.disable_streaming && settings.bedrock.is_none() && settings.vertex.is_none() {
+            return self
+                .request_limiter
+                .stream(&self.id, estimated_tokens, async move {
+                    let response = self.complete(&settings, request).await?;
+                    Ok(futures::stream::iter(map_response(response)).boxed())
+                })
+                .await;
+        }
+        let future = self.stream_completion(request);
+        self.request_limiter
+            .stream(&self.id, estimated_tokens, async move {
+                let response = future.await?;
+                Ok(AnthropicEventMapper::new().map_stream(response).boxed())
+            })
+            .await
     }
 
 
@@ -330,6 +616,13 @@ impl LanguageModel for AnthropicModel {
     fn supports_burn_mode(&self) -> bool {
         true
     }
+
+    fn thinking_support(&self) -> ThinkingSupport {
+        ThinkingSupport {
+            effort: false,
+            budget: self.model.supports_thinking(),
+        }
+    }
 }
 
 pub fn into_anthropic(
@@ -475,6 +768,51 @@ pub fn into_anthropic(
         }
     }
 
+    // Claude may use several tools at once unless told otherwise, which only
+    // the tool choice can carry.
+    let disable_parallel_tool_use =
+        (request.parallel_tool_calls == Some(false) && !request.tools.is_empty()).then_some(true);
+    let tool_choice = match (request.tool_choice, disable_parallel_tool_use) {
+        (Some(LanguageModelToolChoice::Auto), disable_parallel_tool_use)
+        | (None, disable_parallel_tool_use @ Some(_)) => Some(anthropic::ToolChoice::Auto {
+            disable_parallel_tool_use,
+        }),
+        (Some(LanguageModelToolChoice::Any), disable_parallel_tool_use) => {
+            Some(anthropic::ToolChoice::Any {
+                disable_parallel_tool_use,
+            })
+        }
+        (Some(LanguageModelToolChoice::None), _) => Some(anthropic::ToolChoice::None),
+        (None, None) => None,
+    };
+
+    let thinking = match request.thinking {
+        Some(ThinkingConfig::Off) => None,
+        Some(ThinkingConfig::Effort { effort }) => {
+            thinking_enabled(effort_budget_tokens(effort), max_output_tokens)
+        }
+        Some(ThinkingConfig::Budget { budget_tokens }) => {
+            thinking_enabled(budget_tokens, max_output_tokens)
+        }
+        None => match mode {
+            AnthropicModelMode::Thinking {
+                budget_tokens: Some(budget_tokens),
+            } if request.thinking_allowed => thinking_enabled(budget_tokens, max_output_tokens),
+            AnthropicModelMode::Thinking {
+                budget_tokens: None,
+            } if request.thinking_allowed => Some(anthropic::Thinking::Enabled {
+                budget_tokens: None,
+            }),
+            _ => None,
+        },
+    };
+    // The API rejects any temperature other than 1 while thinking.
+    let temperature = if thinking.is_some() {
+        None
+    } else {
+        request.temperature.or(Some(default_temperature))
+    };
+
     anthropic::Request {
         model,
         messages: new_messages,
@@ -484,13 +822,7 @@ pub fn into_anthropic(
         } else {
             Some(anthropic::StringOrContents::String(system_message))
         },
-        thinking: if request.thinking_allowed
-            && let AnthropicModelMode::Thinking { budget_tokens } = mode
-        {
-            Some(anthropic::Thinking::Enabled { budget_tokens })
-        } else {
-            None
-        },
+        thinking,
         tools: request
             .tools
             .into_iter()
@@ -500,19 +832,39 @@ pub fn into_anthropic(
                 input_schema: tool.input_schema,
             })
             .collect(),
-        tool_choice: request.tool_choice.map(|choice| match choice {
-            LanguageModelToolChoice::Auto => anthropic::ToolChoice::Auto,
-            LanguageModelToolChoice::Any => anthropic::ToolChoice::Any,
-            LanguageModelToolChoice::None => anthropic::ToolChoice::None,
-        }),
+        tool_choice,
         metadata: None,
         stop_sequences: Vec::new(),
-        temperature: request.temperature.or(Some(default_temperature)),
+        temperature,
         top_k: None,
         top_p: None,
     }
 }
 
+/// Enables thinking with `budget_tokens` moved into what the API accepts: at
+/// least [`anthropic::MIN_THINKING_BUDGET_TOKENS`], and below `max_tokens`.
+/// Thinking stays off when `max_tokens` leaves no room for that.
+fn thinking_enabled(budget_tokens: u32, max_tokens: u64) -> Option<anthropic::Thinking> {
+    let max_budget_tokens = u32::try_from(max_tokens.saturating_sub(1)).unwrap_or(u32::MAX);
+    if max_budget_tokens < anthropic::MIN_THINKING_BUDGET_TOKENS {
+        return None;
+    }
+    Some(anthropic::Thinking::Enabled {
+        budget_tokens: Some(
+            budget_tokens.clamp(anthropic::MIN_THINKING_BUDGET_TOKENS, max_budget_tokens),
+        ),
+    })
+}
+
+/// Anthropic only takes a budget, so efforts stand for these.
+fn effort_budget_tokens(effort: ReasoningEffort) -> u32 {
+    match effort {
+        ReasoningEffort::Low => 2_048,
+        ReasoningEffort::Medium => 8_192,
+        ReasoningEffort::High => 24_576,
+    }
+}
+
 pub struct AnthropicEventMapper {
     tool_uses_by_index: HashMap<usize, RawToolUse>,
     usage: Usage,
@@ -553,7 +905,7 @@ impl AnthropicEventMapper {
                 ResponseContent::Text { text } => {
                     vec![Ok(LanguageModelCompletionEvent::Text(text))]
                 }
-                ResponseContent::Thinking { thinking } => {
+                ResponseContent::Thinking { thinking, .. } => {
                     vec![Ok(LanguageModelCompletionEvent::Thinking {
                         text: thinking,
                         signature: None,
@@ -662,16 +1014,7 @@ impl AnthropicEventMapper {
             Event::MessageDelta { delta, usage } => {
                 update_usage(&mut self.usage, &usage);
                 if let Some(stop_reason) = delta.stop_reason.as_deref() {
-                    self.stop_reason = match stop_reason {
-                        "end_turn" => StopReason::EndTurn,
-                        "max_tokens" => StopReason::MaxTokens,
-                        "tool_use" => StopReason::ToolUse,
-                        "refusal" => StopReason::Refusal,
-                        _ => {
-                            log::error!("Unexpected anthropic stop_reason: {stop_reason}");
-                            StopReason::EndTurn
-                        }
-                    };
+                    self.stop_reason = convert_stop_reason(stop_reason);
                 }
                 vec![Ok(LanguageModelCompletionEvent::UsageUpdate(
                     convert_usage(&self.usage),
@@ -688,6 +1031,61 @@ impl AnthropicEventMapper {
     }
 }
 
+/// Maps a whole response to the events its stream would have produced.
+pub fn map_response(
+    response: anthropic::Response,
+) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+    let mut events = vec![Ok(LanguageModelCompletionEvent::StartMessage {
+        message_id: response.id,
+    })];
+    events.extend(response.content.into_iter().map(|content| {
+        Ok(match content {
+            ResponseContent::Text { text } => LanguageModelCompletionEvent::Text(text),
+            ResponseContent::Thinking {
+                thinking,
+                signature,
+            } => LanguageModelCompletionEvent::Thinking {
+                text: thinking,
+                signature,
+            },
+            ResponseContent::RedactedThinking { data } => {
+                LanguageModelCompletionEvent::RedactedThinking { data }
+            }
+            ResponseContent::ToolUse { id, name, input } => {
+                LanguageModelCompletionEvent::ToolUse(LanguageModelToolUse {
+                    id: id.into(),
+                    name: name.into(),
+                    is_input_complete: true,
+                    raw_input: input.to_string(),
+                    input,
+                })
+            }
+        })
+    }));
+    events.push(Ok(LanguageModelCompletionEvent::UsageUpdate(
+        convert_usage(&response.usage),
+    )));
+    let stop_reason = response
+        .stop_reason
+        .as_deref()
+        .map_or(StopReason::EndTurn, convert_stop_reason);
+    events.push(Ok(LanguageModelCompletionEvent::Stop(stop_reason)));
+    events
+}
+
+fn convert_stop_reason(stop_reason: &str) -> StopReason {
+    match stop_reason {
+        "end_turn" => StopReason::EndTurn,
+        "max_tokens" => StopReason::MaxTokens,
+        "tool_use" => StopReason::ToolUse,
+        "refusal" => StopReason::Refusal,
+        _ => {
+            log::error!("Unexpected anthropic stop_reason: {stop_reason}");
+            StopReason::EndTurn
+        }
+    }
+}
+
 struct RawToolUse {
     id: String,
     name: String,
@@ -716,5 +1114,395 @@ fn convert_usage(usage: &Usage) -> model::TokenUsage {
         output_tokens: usage.output_tokens.unwrap_or(0),
         cache_creation_input_tokens: usage.cache_creation_input_tokens.unwrap_or(0),
         cache_read_input_tokens: usage.cache_read_input_tokens.unwrap_or(0),
+        reasoning_tokens: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aws::encode_event_stream_message;
+    use crate::http_client::FakeHttpClient;
+    use crate::model::{
+        FakeCredentialProvider, GlobalRegistration, LanguageModelRequestMessage,
+        LanguageModelRequestTool,
+    };
+    use base64::Engine as _;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+
+    #[test]
+    fn test_thinking_config() {
+        let thinking = |thinking, max_output_tokens| {
+            into_anthropic(
+                LanguageModelRequest {
+                    thinking,
+                    thinking_allowed: true,
+                    ..Default::default()
+                },
+                "claude-sonnet-4-20250514".into(),
+                1.0,
+                max_output_tokens,
+                AnthropicModelMode::Thinking {
+                    budget_tokens: Some(4_096),
+                },
+            )
+            .thinking
+            .map(|anthropic::Thinking::Enabled { budget_tokens }| budget_tokens)
+        };
+
+        assert_eq!(thinking(None, 64_000), Some(Some(4_096)));
+        assert_eq!(thinking(Some(ThinkingConfig::Off), 64_000), None);
+        assert_eq!(
+            thinking(
+                Some(ThinkingConfig::Effort {
+                    effort: ReasoningEffort::High
+                }),
+                64_000
+            ),
+            Some(Some(24_576))
+        );
+        // Budgets are kept below `max_tokens`, and above the minimum.
+        assert_eq!(
+            thinking(
+                Some(ThinkingConfig::Budget {
+                    budget_tokens: 32_000
+                }),
+                8_192
+            ),
+            Some(Some(8_191))
+        );
+        assert_eq!(
+            thinking(Some(ThinkingConfig::Budget { budget_tokens: 100 }), 8_192),
+            Some(Some(1_024))
+        );
+        assert_eq!(
+            thinking(Some(ThinkingConfig::Budget { budget_tokens: 100 }), 1_000),
+            None
+        );
+    }
+
+    #[test]
+    fn test_thinking_temperature() {
+        let temperature = |thinking| {
+            into_anthropic(
+                LanguageModelRequest {
+                    thinking,
+                    thinking_allowed: true,
+                    temperature: Some(0.5),
+                    ..Default::default()
+                },
+                "claude-sonnet-4-20250514".into(),
+                1.0,
+                64_000,
+                AnthropicModelMode::Default,
+            )
+            .temperature
+        };
+
+        assert_eq!(temperature(None), Some(0.5));
+        assert_eq!(temperature(Some(ThinkingConfig::Off)), Some(0.5));
+        assert_eq!(
+            temperature(Some(ThinkingConfig::Budget {
+                budget_tokens: 4_096
+            })),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parallel_tool_calls() {
+        let tool_choice = |parallel_tool_calls| {
+            let request = into_anthropic(
+                LanguageModelRequest {
+                    tools: vec![LanguageModelRequestTool {
+                        name: "read_file".into(),
+                        description: "Reads a file".into(),
+                        input_schema: serde_json::json!({"type": "object"}),
+                    }],
+                    parallel_tool_calls,
+                    ..Default::default()
+                },
+                "claude-sonnet-4-20250514".into(),
+                1.0,
+                64_000,
+                AnthropicModelMode::Default,
+            );
+            serde_json::to_value(request.tool_choice).unwrap()
+        };
+        assert_eq!(tool_choice(None), serde_json::Value::Null);
+        assert_eq!(tool_choice(Some(true)), serde_json::Value::Null);
+        assert_eq!(
+            tool_choice(Some(false)),
+            serde_json::json!({"type": "auto", "disable_parallel_tool_use": true})
+        );
+
+        let events = [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_1","name":"read_file","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{\"path\": \"a.rs\"}"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_2","name":"read_file","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\": \"b.rs\"}"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+        ];
+        let mut mapper = AnthropicEventMapper::new();
+        let tool_uses = events
+            .into_iter()
+            .flat_map(|event| mapper.map_event(serde_json::from_str(event).unwrap()))
+            .filter_map(|event| match event.unwrap() {
+                LanguageModelCompletionEvent::ToolUse(tool_use) if tool_use.is_input_complete => {
+                    Some((tool_use.id.to_string(), tool_use.input))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tool_uses,
+            vec![
+                ("toolu_1".into(), serde_json::json!({"path": "a.rs"})),
+                ("toolu_2".into(), serde_json::json!({"path": "b.rs"})),
+            ]
+        );
+    }
+
+    /// Events of a response from `invoke-with-response-stream`.
+    const EVENTS: &[&str] = &[
+        r#"{"type":"message_start","message":{"id":"msg_bdrk_01","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":12,"output_tokens":1}}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello from"}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":" Bedrock"}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":5}}"#,
+        r#"{"type":"message_stop","amazon-bedrock-invocationMetrics":{"inputTokenCount":12,"outputTokenCount":5,"invocationLatency":412,"firstByteLatency":230}}"#,
+    ];
+
+    #[tokio::test]
+    async fn test_bedrock_completion() {
+        let body = EVENTS
+            .iter()
+            .flat_map(|event| {
+                let payload = format!(r#"{{"bytes":"{}","p":"abcd"}}"#, BASE64.encode(event));
+                encode_event_stream_message(
+                    &[
+                        (":event-type", "chunk"),
+                        (":content-type", "application/json"),
+                        (":message-type", "event"),
+                    ],
+                    payload.as_bytes(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let (http_client, sent_requests) = FakeHttpClient::replay(body);
+        let provider = AnthropicLanguageModelProvider::new(
+            http_client,
+            Some(AnthropicSettings {
+                bedrock: Some(BedrockSettings {
+                    region: Some("us-west-2".into()),
+                    credentials: Some(
+                        AwsCredentials::new("AKIDEXAMPLE", "secret").with_session_token("token"),
+                    ),
+                    inference_profile_prefix: Some("us".into()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        );
+        provider.authenticate().await.unwrap();
+
+        let model = provider.create_language_model(anthropic::Model::ClaudeSonnet4);
+        let response = model
+            .complete(LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Hi".into())],
+                    cache: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "Hello from Bedrock");
+        assert_eq!(response.stop_reason, StopReason::EndTurn);
+        assert_eq!(response.usage.input_tokens, 12);
+        assert_eq!(response.usage.output_tokens, 5);
+
+        let sent_request = sent_requests.lock().pop().unwrap();
+        let parts = &sent_request.parts;
+        assert_eq!(
+            parts.uri.to_string(),
+            "https://bedrock-runtime.us-west-2.amazonaws.com/model/\
+             us.anthropic.claude-sonnet-4-20250514-v1%3A0/invoke-with-response-stream"
+        );
+        let authorization = parts.headers["authorization"].to_str().unwrap();
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+        assert!(authorization.contains("/us-west-2/bedrock/aws4_request"));
+        assert!(
+            authorization
+                .contains("SignedHeaders=accept;content-type;host;x-amz-date;x-amz-security-token")
+        );
+        assert_eq!(parts.headers["x-amz-security-token"], "token");
+        assert!(parts.headers.get("x-api-key").is_none());
+
+        let body = sent_request.json();
+        assert_eq!(
+            body["anthropic_version"],
+            anthropic::BEDROCK_ANTHROPIC_VERSION
+        );
+        assert!(body.get("model").is_none());
+        assert!(body.get("stream").is_none());
+        assert_eq!(body["messages"][0]["content"][0]["text"], "Hi");
+    }
+
+    const VERTEX_STREAM: &str = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_vrtx_01","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[],"stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":9,"output_tokens":1}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello from Vertex"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":4}}
+
+event: message_stop
+data: {"type":"message_stop"}
+
+"#;
+
+    #[tokio::test]
+    async fn test_vertex_completion() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(VERTEX_STREAM);
+        let provider = AnthropicLanguageModelProvider::new(
+            http_client,
+            Some(AnthropicSettings {
+                api_key: "ya29.token".into(),
+                vertex: Some(VertexSettings {
+                    project_id: "omni-test".into(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        );
+
+        let model = provider.create_language_model(anthropic::Model::ClaudeSonnet4);
+        let response = model
+            .complete(LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Hi".into())],
+                    cache: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "Hello from Vertex");
+        assert_eq!(response.stop_reason, StopReason::EndTurn);
+
+        let sent_request = sent_requests.lock().pop().unwrap();
+        let parts = &sent_request.parts;
+        assert_eq!(
+            parts.uri.to_string(),
+            "https://us-east5-aiplatform.googleapis.com/v1/projects/omni-test/locations/us-east5/\
+             publishers/anthropic/models/claude-sonnet-4@20250514:streamRawPredict"
+        );
+        assert_eq!(parts.headers["authorization"], "Bearer ya29.token");
+        let body = sent_request.json();
+        assert_eq!(
+            body["anthropic_version"],
+            anthropic::VERTEX_ANTHROPIC_VERSION
+        );
+        assert_eq!(body["stream"], true);
+        assert!(body.get("model").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_global_vertex_settings_use_vertex_credentials() {
+        let _settings = GlobalRegistration::new(AnthropicSettings {
+            vertex: Some(VertexSettings {
+                project_id: "omni-global".into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let (http_client, sent_requests) = FakeHttpClient::replay(VERTEX_STREAM);
+        let provider = AnthropicLanguageModelProvider::new(http_client, None)
+            .with_credentials(Arc::new(FakeCredentialProvider("sk-ant-test".into())))
+            .with_vertex_credentials(Arc::new(FakeCredentialProvider("ya29.token".into())));
+        provider.authenticate().await.unwrap();
+
+        let model = provider.create_language_model(anthropic::Model::ClaudeSonnet4);
+        model
+            .complete(LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Hi".into())],
+                    cache: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let sent_request = sent_requests.lock().pop().unwrap();
+        assert!(
+            sent_request
+                .parts
+                .uri
+                .to_string()
+                .contains("/projects/omni-global/")
+        );
+        assert_eq!(
+            sent_request.parts.headers["authorization"],
+            "Bearer ya29.token"
+        );
+        assert!(sent_request.parts.headers.get("x-api-key").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_disable_streaming() {
+        const RESPONSE: &str = r#"{"id":"msg_01","type":"message","role":"assistant","model":"claude-sonnet-4-20250514","content":[{"type":"thinking","thinking":"Look it up.","signature":"c2ln"},{"type":"text","text":"Checking."},{"type":"tool_use","id":"toolu_01","name":"weather","input":{"city":"Paris"}}],"stop_reason":"tool_use","stop_sequence":null,"usage":{"input_tokens":20,"output_tokens":12}}"#;
+        let (http_client, sent_requests) = FakeHttpClient::replay(RESPONSE);
+        let provider = AnthropicLanguageModelProvider::new(
+            http_client,
+            Some(AnthropicSettings {
+                api_key: "sk-ant-test".into(),
+                disable_streaming: true,
+                ..Default::default()
+            }),
+        );
+
+        let model = provider.create_language_model(anthropic::Model::ClaudeSonnet4);
+        let response = model
+            .complete(LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Weather in Paris?".into())],
+                    cache: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.message_id.as_deref(), Some("msg_01"));
+        assert_eq!(
+            response.message.content[0],
+            MessageContent::Thinking {
+                text: "Look it up.".into(),
+                signature: Some("c2ln".into()),
+            }
+        );
+        assert_eq!(response.text(), "Checking.");
+        let tool_use = response.tool_uses().next().unwrap();
+        assert_eq!(tool_use.input, serde_json::json!({"city": "Paris"}));
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        assert_eq!(response.usage.input_tokens, 20);
+        assert_eq!(response.usage.output_tokens, 12);
+
+        let body = sent_requests.lock().pop().unwrap().json();
+        assert!(body.get("stream").is_none());
     }
 }
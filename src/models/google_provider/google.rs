@@ -0,0 +1,731 @@
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicU64};
+
+use futures::{Stream, StreamExt, stream::BoxStream};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::common::SecretString;
+use crate::google::{
+    self, Content, FunctionCallPart, FunctionCallingConfig, FunctionCallingMode,
+    FunctionDeclaration, FunctionResponse, FunctionResponsePart, GenerateContentResponse,
+    GenerationConfig, GenerativeContentBlob, GoogleModelMode, InlineDataPart, ModelName, Part,
+    SystemInstruction, TextPart, ThinkingConfig, ThoughtPart, Tool, ToolConfig, UsageMetadata,
+};
+use crate::http_client::HttpClient;
+use crate::model::{
+    self, CredentialProvider, EnvCredentialProvider, LanguageModel, LanguageModelCompletionError,
+    LanguageModelCompletionEvent, LanguageModelId, LanguageModelName, LanguageModelProvider,
+    LanguageModelProviderId, LanguageModelProviderName, LanguageModelRequest,
+    LanguageModelToolChoice, LanguageModelToolResultContent, LanguageModelToolSchemaFormat,
+    LanguageModelToolUse, LanguageModelToolUseId, MessageContent, RateLimiter, RateLimits, Role,
+    StopReason, estimate_request_tokens, resolve_api_key,
+};
+
+const PROVIDER_ID: LanguageModelProviderId = model::GOOGLE_PROVIDER_ID;
+const PROVIDER_NAME: LanguageModelProviderName = model::GOOGLE_PROVIDER_NAME;
+
+const GEMINI_API_KEY_VAR: &str = "GEMINI_API_KEY";
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct GoogleSettings {
+    /// Defaults to [`google::API_URL`] when empty.
+    pub api_url: String,
+    pub api_key: SecretString,
+    /// Models offered in addition to the built-in ones, or overriding them.
+    pub available_models: Vec<AvailableModel>,
+}
+
+impl GoogleSettings {
+    pub fn api_url(&self) -> &str {
+        if self.api_url.is_empty() {
+            google::API_URL
+        } else {
+            &self.api_url
+        }
+    }
+
+    /// Returns `settings`, or the globally registered settings without them.
+    fn resolve(settings: &Option<Arc<Self>>) -> Arc<Self> {
+        settings
+            .clone()
+            .or_else(|| global_registry::get!(GoogleSettings).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AvailableModel {
+    /// The model's name in the Gemini API, e.g. `gemini-2.5-flash-preview-05-20`.
+    pub name: String,
+    pub display_name: Option<String>,
+    /// The model's context window size.
+    pub max_tokens: u64,
+    pub max_output_tokens: Option<u64>,
+    /// Whether the model thinks, and with which budget.
+    pub mode: Option<GoogleModelMode>,
+}
+
+pub struct GoogleLanguageModelProvider {
+    http_client: Arc<dyn HttpClient>,
+    settings: Option<Arc<GoogleSettings>>,
+    credentials: Arc<dyn CredentialProvider>,
+    request_limiter: RateLimiter,
+}
+
+impl GoogleLanguageModelProvider {
+    /// Creates a provider for the Gemini API.
+    ///
+    /// Without settings, the [`GoogleSettings`] registered in `global_registry`
+    /// are looked up on every request. When no API key is configured, it is
+    /// read from `GEMINI_API_KEY`.
+    pub fn new(http_client: Arc<dyn HttpClient>, settings: Option<GoogleSettings>) -> Self {
+        Self {
+            http_client,
+            settings: settings.map(Arc::new),
+            credentials: Arc::new(EnvCredentialProvider::new(GEMINI_API_KEY_VAR)),
+            request_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Replaces where the API key comes from when the settings have none.
+    pub fn with_credentials(mut self, credentials: Arc<dyn CredentialProvider>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Replaces the limits shared by every model this provider creates.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.request_limiter = RateLimiter::new(limits);
+        self
+    }
+
+    pub fn create_language_model(&self, model: google::Model) -> Arc<dyn LanguageModel> {
+        Arc::new(GoogleLanguageModel {
+            id: LanguageModelId::from(model.id().to_string()),
+            model,
+            http_client: self.http_client.clone(),
+            settings: self.settings.clone(),
+            credentials: self.credentials.clone(),
+            request_limiter: self.request_limiter.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl LanguageModelProvider for GoogleLanguageModelProvider {
+    fn id(&self) -> LanguageModelProviderId {
+        PROVIDER_ID
+    }
+
+    fn name(&self) -> LanguageModelProviderName {
+        PROVIDER_NAME
+    }
+
+    fn default_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        Some(self.create_language_model(google::Model::default()))
+    }
+
+    fn default_fast_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        Some(self.create_language_model(google::Model::default_fast()))
+    }
+
+    fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>> {
+        let mut models = BTreeMap::default();
+
+        for model in google::Model::iter() {
+            if !matches!(model, google::Model::Custom { .. }) {
+                models.insert(model.id().to_string(), model);
+            }
+        }
+
+        for model in &GoogleSettings::resolve(&self.settings).available_models {
+            models.insert(
+                model.name.clone(),
+                google::Model::Custom {
+                    name: model.name.clone(),
+                    display_name: model.display_name.clone(),
+                    max_tokens: model.max_tokens,
+                    max_output_tokens: model.max_output_tokens,
+                    mode: model.mode.clone().unwrap_or_default(),
+                },
+            );
+        }
+
+        models
+            .into_values()
+            .map(|model| self.create_language_model(model))
+            .collect()
+    }
+
+    async fn authenticate(&self) -> anyhow::Result<()> {
+        let settings = GoogleSettings::resolve(&self.settings);
+        resolve_api_key(&settings.api_key, self.credentials.as_ref(), PROVIDER_NAME).await?;
+        Ok(())
+    }
+}
+
+pub struct GoogleLanguageModel {
+    id: LanguageModelId,
+    model: google::Model,
+    http_client: Arc<dyn HttpClient>,
+    settings: Option<Arc<GoogleSettings>>,
+    credentials: Arc<dyn CredentialProvider>,
+    request_limiter: RateLimiter,
+}
+
+impl GoogleLanguageModel {
+    async fn stream_completion(
+        &self,
+        request: google::GenerateContentRequest,
+    ) -> Result<
+        BoxStream<'static, anyhow::Result<GenerateContentResponse>>,
+        LanguageModelCompletionError,
+    > {
+        let settings = GoogleSettings::resolve(&self.settings);
+        let api_key =
+            resolve_api_key(&settings.api_key, self.credentials.as_ref(), PROVIDER_NAME).await?;
+
+        google::stream_generate_content(
+            self.http_client.as_ref(),
+            settings.api_url(),
+            &api_key,
+            request,
+        )
+        .await
+        .map_err(|error| {
+            let error = match error.downcast::<google::ApiError>() {
+                Ok(error) => LanguageModelCompletionError::from(error),
+                Err(error) => LanguageModelCompletionError::from(error),
+            };
+            if error.is_authentication_error() {
+                self.credentials.invalidate();
+            }
+            error
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl LanguageModel for GoogleLanguageModel {
+    fn id(&self) -> LanguageModelId {
+        self.id.clone()
+    }
+
+    fn name(&self) -> LanguageModelName {
+        LanguageModelName::from(self.model.display_name().to_string())
+    }
+
+    fn provider_id(&self) -> LanguageModelProviderId {
+        PROVIDER_ID
+    }
+
+    fn provider_name(&self) -> LanguageModelProviderName {
+        PROVIDER_NAME
+    }
+
+    fn max_token_count(&self) -> u64 {
+        self.model.max_token_count()
+    }
+
+    fn max_output_tokens(&self) -> Option<u64> {
+        self.model.max_output_tokens()
+    }
+
+    fn tool_input_format(&self) -> LanguageModelToolSchemaFormat {
+        LanguageModelToolSchemaFormat::JsonSchemaSubset
+    }
+
+    async fn stream_completion(
+        &self,
+        request: LanguageModelRequest,
+    ) -> Result<
+        BoxStream<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+        LanguageModelCompletionError,
+    > {
+        let estimated_tokens = estimate_request_tokens(&request);
+        let request = into_google(request, self.model.id().to_string(), self.model.mode());
+        let future = self.stream_completion(request);
+        self.request_limiter
+            .stream(&self.id, estimated_tokens, async move {
+                let response = future.await?;
+                Ok(GoogleEventMapper::new().map_stream(response).boxed())
+            })
+            .await
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    fn supports_burn_mode(&self) -> bool {
+        false
+    }
+}
+
+pub fn into_google(
+    request: LanguageModelRequest,
+    model_id: String,
+    mode: GoogleModelMode,
+) -> google::GenerateContentRequest {
+    fn map_content(content: Vec<MessageContent>) -> Vec<Part> {
+        content
+            .into_iter()
+            .filter_map(|content| match content {
+                MessageContent::Text(text) => {
+                    if !text.is_empty() {
+                        Some(Part::TextPart(TextPart {
+                            text,
+                            thought: false,
+                            thought_signature: None,
+                        }))
+                    } else {
+                        None
+                    }
+                }
+                // Gemini only needs the signature to restore its reasoning.
+                MessageContent::Thinking {
+                    signature: Some(signature),
+                    ..
+                } => Some(Part::ThoughtPart(ThoughtPart {
+                    thought: true,
+                    thought_signature: signature,
+                })),
+                MessageContent::Thinking { .. } | MessageContent::RedactedThinking(_) => None,
+                MessageContent::Image(image) => Some(Part::InlineDataPart(InlineDataPart {
+                    inline_data: GenerativeContentBlob {
+                        mime_type: "image/png".to_string(),
+                        data: image.source.to_string(),
+                    },
+                })),
+                MessageContent::ToolUse(tool_use) => {
+                    Some(Part::FunctionCallPart(FunctionCallPart {
+                        function_call: google::FunctionCall {
+                            name: tool_use.name.to_string(),
+                            args: tool_use.input,
+                        },
+                        thought_signature: None,
+                    }))
+                }
+                MessageContent::ToolResult(tool_result) => {
+                    let output = match tool_result.content {
+                        LanguageModelToolResultContent::Text(text) => text.to_string(),
+                    };
+                    Some(Part::FunctionResponsePart(FunctionResponsePart {
+                        function_response: FunctionResponse {
+                            name: tool_result.tool_name.to_string(),
+                            // The API expects an object, so wrap the output.
+                            response: serde_json::json!({ "output": output }),
+                        },
+                    }))
+                }
+            })
+            .collect()
+    }
+
+    let system_instructions = if let Some(message) = request
+        .messages
+        .first()
+        .filter(|message| message.role == Role::System)
+    {
+        let parts = map_content(message.content.clone());
+        Some(SystemInstruction { parts })
+    } else {
+        None
+    };
+
+    let thinking_config = match mode {
+        GoogleModelMode::Thinking { budget_tokens } if request.thinking_allowed => {
+            Some(ThinkingConfig {
+                thinking_budget: budget_tokens,
+                include_thoughts: Some(true),
+            })
+        }
+        _ => None,
+    };
+
+    google::GenerateContentRequest {
+        model: ModelName { model_id },
+        system_instruction: system_instructions,
+        contents: request
+            .messages
+            .into_iter()
+            .skip_while(|message| message.role == Role::System)
+            .filter_map(|message| {
+                let parts = map_content(message.content);
+                if parts.is_empty() {
+                    None
+                } else {
+                    Some(Content {
+                        parts,
+                        role: match message.role {
+                            Role::User => google::Role::User,
+                            Role::Assistant => google::Role::Model,
+                            Role::System => google::Role::User, // Google AI doesn't have a system role
+                        },
+                    })
+                }
+            })
+            .collect(),
+        generation_config: Some(GenerationConfig {
+            candidate_count: Some(1),
+            stop_sequences: Some(request.stop),
+            max_output_tokens: None,
+            temperature: request.temperature.map(|t| t as f64).or(Some(1.0)),
+            thinking_config,
+            top_p: None,
+            top_k: None,
+        }),
+        safety_settings: None,
+        tools: (!request.tools.is_empty()).then(|| {
+            vec![Tool {
+                function_declarations: request
+                    .tools
+                    .into_iter()
+                    .map(|tool| FunctionDeclaration {
+                        name: tool.name,
+                        description: tool.description,
+                        parameters: tool.input_schema,
+                    })
+                    .collect(),
+            }]
+        }),
+        tool_config: request.tool_choice.map(|choice| ToolConfig {
+            function_calling_config: FunctionCallingConfig {
+                mode: match choice {
+                    LanguageModelToolChoice::Auto => FunctionCallingMode::Auto,
+                    LanguageModelToolChoice::Any => FunctionCallingMode::Any,
+                    LanguageModelToolChoice::None => FunctionCallingMode::None,
+                },
+                allowed_function_names: None,
+            },
+        }),
+    }
+}
+
+pub struct GoogleEventMapper {
+    usage: UsageMetadata,
+    stop_reason: StopReason,
+    message_started: bool,
+}
+
+impl Default for GoogleEventMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GoogleEventMapper {
+    pub fn new() -> Self {
+        Self {
+            usage: UsageMetadata::default(),
+            stop_reason: StopReason::EndTurn,
+            message_started: false,
+        }
+    }
+
+    pub fn map_stream(
+        mut self,
+        events: Pin<Box<dyn Send + Stream<Item = anyhow::Result<GenerateContentResponse>>>>,
+    ) -> impl Stream<Item = Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>
+    {
+        events
+            .map(Some)
+            .chain(futures::stream::once(async { None }))
+            .flat_map(move |event| {
+                futures::stream::iter(match event {
+                    Some(Ok(event)) => self.map_event(event),
+                    Some(Err(error)) => vec![Err(LanguageModelCompletionError::from(error))],
+                    // Gemini has no dedicated "message stop" event, so stop once
+                    // the stream ends.
+                    None => vec![Ok(LanguageModelCompletionEvent::Stop(self.stop_reason))],
+                })
+            })
+    }
+
+    pub fn map_event(
+        &mut self,
+        event: GenerateContentResponse,
+    ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+        // Gemini doesn't assign ids to function calls, so make up unique ones.
+        static TOOL_CALL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let mut events: Vec<_> = Vec::new();
+        let mut wants_to_use_tool = false;
+
+        if let Some(response_id) = event.response_id
+            && !self.message_started
+        {
+            self.message_started = true;
+            events.push(Ok(LanguageModelCompletionEvent::StartMessage {
+                message_id: response_id,
+            }));
+        }
+
+        if let Some(usage_metadata) = event.usage_metadata {
+            // Every chunk reports the usage of the whole response so far.
+            self.usage = usage_metadata;
+            events.push(Ok(LanguageModelCompletionEvent::UsageUpdate(
+                convert_usage(&self.usage),
+            )));
+        }
+
+        if let Some(block_reason) = event
+            .prompt_feedback
+            .and_then(|feedback| feedback.block_reason)
+        {
+            log::warn!("Google AI blocked the prompt: {block_reason}");
+            self.stop_reason = StopReason::Refusal;
+        }
+
+        for candidate in event.candidates.into_iter().flatten() {
+            if let Some(finish_reason) = candidate.finish_reason.as_deref() {
+                self.stop_reason = match finish_reason {
+                    "STOP" | "FINISH_REASON_UNSPECIFIED" => StopReason::EndTurn,
+                    "MAX_TOKENS" => StopReason::MaxTokens,
+                    "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII"
+                    | "IMAGE_SAFETY" => StopReason::Refusal,
+                    _ => {
+                        log::error!("Unexpected google finish_reason: {finish_reason}");
+                        StopReason::EndTurn
+                    }
+                };
+            }
+
+            for part in candidate.content.parts {
+                match part {
+                    Part::TextPart(part) if part.thought => {
+                        events.push(Ok(LanguageModelCompletionEvent::Thinking {
+                            text: part.text,
+                            signature: part.thought_signature,
+                        }));
+                    }
+                    Part::TextPart(part) => {
+                        if let Some(signature) = part.thought_signature {
+                            events.push(Ok(LanguageModelCompletionEvent::Thinking {
+                                text: String::new(),
+                                signature: Some(signature),
+                            }));
+                        }
+                        if !part.text.is_empty() {
+                            events.push(Ok(LanguageModelCompletionEvent::Text(part.text)));
+                        }
+                    }
+                    Part::ThoughtPart(part) => {
+                        events.push(Ok(LanguageModelCompletionEvent::Thinking {
+                            text: String::new(),
+                            signature: Some(part.thought_signature),
+                        }));
+                    }
+                    Part::FunctionCallPart(part) => {
+                        wants_to_use_tool = true;
+                        if let Some(signature) = part.thought_signature {
+                            events.push(Ok(LanguageModelCompletionEvent::Thinking {
+                                text: String::new(),
+                                signature: Some(signature),
+                            }));
+                        }
+                        let name: Arc<str> = part.function_call.name.into();
+                        let next_tool_id = TOOL_CALL_COUNTER.fetch_add(1, atomic::Ordering::SeqCst);
+                        let id: LanguageModelToolUseId = format!("{name}-{next_tool_id}").into();
+                        events.push(Ok(LanguageModelCompletionEvent::ToolUse(
+                            LanguageModelToolUse {
+                                id,
+                                name,
+                                is_input_complete: true,
+                                raw_input: part.function_call.args.to_string(),
+                                input: part.function_call.args,
+                            },
+                        )));
+                    }
+                    Part::InlineDataPart(_) | Part::FunctionResponsePart(_) => {}
+                }
+            }
+        }
+
+        // Gemini reports `STOP` even when it wants to call a function.
+        if wants_to_use_tool {
+            self.stop_reason = StopReason::ToolUse;
+        }
+
+        events
+    }
+}
+
+fn convert_usage(usage: &UsageMetadata) -> model::TokenUsage {
+    let prompt_tokens = usage.prompt_token_count.unwrap_or(0);
+    let cached_tokens = usage.cached_content_token_count.unwrap_or(0);
+    let thoughts_tokens = usage.thoughts_token_count.unwrap_or(0);
+    model::TokenUsage {
+        input_tokens: prompt_tokens.saturating_sub(cached_tokens),
+        output_tokens: usage.candidates_token_count.unwrap_or(0) + thoughts_tokens,
+        cache_creation_input_tokens: 0,
+        cache_read_input_tokens: cached_tokens,
+        reasoning_tokens: thoughts_tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::FakeHttpClient;
+    use crate::model::{
+        LanguageModelRequestMessage, LanguageModelRequestTool, LanguageModelToolResult, TokenUsage,
+    };
+
+    const RESPONSE: &str = r#"data: {"candidates": [{"content": {"parts": [{"text": "The user wants the weather.", "thought": true}], "role": "model"}, "index": 0}], "usageMetadata": {"promptTokenCount": 20, "totalTokenCount": 20}, "responseId": "resp_1"}
+
+data: {"candidates": [{"content": {"parts": [{"text": "Let me check."}], "role": "model"}, "index": 0}], "usageMetadata": {"promptTokenCount": 20, "candidatesTokenCount": 3, "thoughtsTokenCount": 6, "totalTokenCount": 29}, "responseId": "resp_1"}
+
+data: {"candidates": [{"content": {"parts": [{"functionCall": {"name": "weather", "args": {"city": "Paris"}}, "thoughtSignature": "c2ln"}], "role": "model"}, "finishReason": "STOP", "index": 0}], "usageMetadata": {"promptTokenCount": 20, "cachedContentTokenCount": 8, "candidatesTokenCount": 10, "thoughtsTokenCount": 6, "totalTokenCount": 36}, "responseId": "resp_1"}
+
+"#;
+
+    #[tokio::test]
+    async fn test_stream_completion() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(RESPONSE);
+        let provider = GoogleLanguageModelProvider::new(
+            http_client,
+            Some(GoogleSettings {
+                api_url: "https://gemini.test".into(),
+                api_key: "test-key".into(),
+                ..Default::default()
+            }),
+        );
+        let model = provider.create_language_model(google::Model::Gemini25Flash);
+
+        let request = LanguageModelRequest {
+            messages: vec![
+                LanguageModelRequestMessage {
+                    role: Role::System,
+                    content: vec![MessageContent::Text("Be brief.".into())],
+                    cache: false,
+                },
+                LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Weather in Paris?".into())],
+                    cache: false,
+                },
+            ],
+            tools: vec![LanguageModelRequestTool {
+                name: "weather".into(),
+                description: "Looks up the weather".into(),
+                input_schema: serde_json::json!({
+                    "type": "object",
+                    "properties": { "city": { "type": "string" } },
+                }),
+            }],
+            thinking_allowed: true,
+            ..Default::default()
+        };
+        let response = model.complete(request).await.unwrap();
+
+        let sent_request = sent_requests.lock().pop().unwrap();
+        assert_eq!(
+            sent_request.parts.uri.to_string(),
+            "https://gemini.test/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse"
+        );
+        assert_eq!(sent_request.parts.headers["x-goog-api-key"], "test-key");
+        let body = sent_request.json();
+        assert_eq!(
+            body["systemInstruction"],
+            serde_json::json!({ "parts": [{ "text": "Be brief." }] })
+        );
+        assert_eq!(body["contents"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"],
+            serde_json::json!({ "includeThoughts": true })
+        );
+        assert_eq!(
+            body["tools"][0]["functionDeclarations"][0]["name"],
+            "weather"
+        );
+
+        assert_eq!(response.message_id.as_deref(), Some("resp_1"));
+        assert_eq!(
+            response.message.content[0],
+            MessageContent::Thinking {
+                text: "The user wants the weather.".into(),
+                signature: None,
+            }
+        );
+        assert_eq!(response.text(), "Let me check.");
+        let tool_use = response.tool_uses().next().unwrap();
+        assert_eq!(tool_use.name.as_ref(), "weather");
+        assert_eq!(tool_use.input, serde_json::json!({ "city": "Paris" }));
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        assert_eq!(
+            response.usage,
+            TokenUsage {
+                input_tokens: 12,
+                output_tokens: 16,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 8,
+                reasoning_tokens: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn test_into_google_tool_round_trip() {
+        let tool_use = LanguageModelToolUse {
+            id: "weather-0".into(),
+            name: "weather".into(),
+            raw_input: r#"{"city":"Paris"}"#.into(),
+            input: serde_json::json!({ "city": "Paris" }),
+            is_input_complete: true,
+        };
+        let request = LanguageModelRequest {
+            messages: vec![
+                LanguageModelRequestMessage {
+                    role: Role::Assistant,
+                    content: vec![
+                        MessageContent::Thinking {
+                            text: String::new(),
+                            signature: Some("c2ln".into()),
+                        },
+                        MessageContent::ToolUse(tool_use),
+                    ],
+                    cache: false,
+                },
+                LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::ToolResult(LanguageModelToolResult {
+                        tool_use_id: "weather-0".into(),
+                        tool_name: "weather".into(),
+                        is_error: false,
+                        content: "Sunny".into(),
+                        output: None,
+                    })],
+                    cache: false,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let request = into_google(request, "gemini-2.5-pro".into(), GoogleModelMode::Default);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["contents"],
+            serde_json::json!([
+                {
+                    "role": "model",
+                    "parts": [
+                        { "thought": true, "thoughtSignature": "c2ln" },
+                        { "functionCall": { "name": "weather", "args": { "city": "Paris" } } },
+                    ],
+                },
+                {
+                    "role": "user",
+                    "parts": [
+                        { "functionResponse": { "name": "weather", "response": { "output": "Sunny" } } },
+                    ],
+                },
+            ])
+        );
+        assert!(json["generationConfig"].get("thinkingConfig").is_none());
+    }
+}
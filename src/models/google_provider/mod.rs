@@ -0,0 +1,2 @@
+mod google;
+pub use google::*;
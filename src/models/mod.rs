@@ -1,6 +1,10 @@
 mod anthropic_provider;
 mod openai_provider;
+mod google_provider;
+mod ollama_provider;
 
 
 pub use openai_provider::*;
-pub use anthropic_provider::*;
\ No newline at end of file
+pub use anthropic_provider::*;
+pub use google_provider::*;
+pub use ollama_provider::*;
\ No newline at end of file
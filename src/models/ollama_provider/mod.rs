@@ -0,0 +1,2 @@
+mod ollama;
+pub use ollama::*;
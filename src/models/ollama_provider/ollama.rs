@@ -0,0 +1,588 @@
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicU64};
+
+use futures::{Stream, StreamExt, future, stream, stream::BoxStream};
+use parking_lot::RwLock;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::common::SecretString;
+use crate::http_client::HttpClient;
+use crate::model::{
+    self, LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent,
+    LanguageModelId, LanguageModelName, LanguageModelProvider, LanguageModelProviderId,
+    LanguageModelProviderName, LanguageModelRequest, LanguageModelRequestTool,
+    LanguageModelToolResultContent, LanguageModelToolUse, LanguageModelToolUseId, MessageContent,
+    RateLimiter, RateLimits, Role, StopReason, TokenUsage, estimate_request_tokens,
+};
+use crate::ollama::{
+    self, ChatMessage, ChatOptions, ChatRequest, ChatResponseDelta, KeepAlive, OllamaFunctionCall,
+    OllamaFunctionTool, OllamaTool, OllamaToolCall,
+};
+
+const PROVIDER_ID: LanguageModelProviderId = model::OLLAMA_PROVIDER_ID;
+const PROVIDER_NAME: LanguageModelProviderName = model::OLLAMA_PROVIDER_NAME;
+
+/// How many `/api/show` requests [`OllamaLanguageModelProvider::refresh_models`]
+/// sends at once, to spare the local server.
+const MAX_CONCURRENT_SHOW_REQUESTS: usize = 4;
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct OllamaSettings {
+    /// Defaults to [`ollama::OLLAMA_API_URL`] when empty.
+    pub api_url: String,
+    /// Only needed for hosted Ollama servers; local ones accept any request.
+    pub api_key: SecretString,
+    /// Models offered in addition to the discovered ones, or overriding them.
+    pub available_models: Vec<AvailableModel>,
+}
+
+impl OllamaSettings {
+    pub fn api_url(&self) -> &str {
+        if self.api_url.is_empty() {
+            ollama::OLLAMA_API_URL
+        } else {
+            &self.api_url
+        }
+    }
+
+    /// Returns `settings`, or the globally registered settings without them.
+    fn resolve(settings: &Option<Arc<Self>>) -> Arc<Self> {
+        settings
+            .clone()
+            .or_else(|| global_registry::get!(OllamaSettings).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AvailableModel {
+    /// The model name in the Ollama library, e.g. `llama3.2:latest`.
+    pub name: String,
+    pub display_name: Option<String>,
+    /// The context window to request, sent as `num_ctx`.
+    pub max_tokens: u64,
+    /// How long the model stays loaded after a request: seconds, or a duration
+    /// like "5m". Negative values keep it loaded indefinitely.
+    #[schemars(with = "Option<serde_json::Value>")]
+    pub keep_alive: Option<KeepAlive>,
+    pub supports_tools: Option<bool>,
+    pub supports_images: Option<bool>,
+    pub supports_thinking: Option<bool>,
+}
+
+pub struct OllamaLanguageModelProvider {
+    http_client: Arc<dyn HttpClient>,
+    settings: Option<Arc<OllamaSettings>>,
+    /// The models found on the server by the last [`Self::refresh_models`].
+    fetched_models: RwLock<Vec<ollama::Model>>,
+    request_limiter: RateLimiter,
+}
+
+impl OllamaLanguageModelProvider {
+    /// Creates a provider for an Ollama server.
+    ///
+    /// Without settings, the [`OllamaSettings`] registered in `global_registry`
+    /// are looked up on every request. Models pulled on the server are only
+    /// listed after [`LanguageModelProvider::authenticate`] or
+    /// [`Self::refresh_models`] has run.
+    pub fn new(http_client: Arc<dyn HttpClient>, settings: Option<OllamaSettings>) -> Self {
+        Self {
+            http_client,
+            settings: settings.map(Arc::new),
+            fetched_models: RwLock::default(),
+            request_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Replaces the limits shared by every model this provider creates.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.request_limiter = RateLimiter::new(limits);
+        self
+    }
+
+    /// Lists the models pulled on the server (`/api/tags`) and looks up their
+    /// context length and capabilities (`/api/show`).
+    pub async fn refresh_models(&self) -> anyhow::Result<()> {
+        let settings = OllamaSettings::resolve(&self.settings);
+        let api_url = settings.api_url();
+        let api_key = Some(&settings.api_key);
+        let http_client = self.http_client.as_ref();
+
+        let listings = ollama::get_models(http_client, api_url, api_key).await?;
+        let models = stream::iter(listings)
+            .map(|listing| async move {
+                let show = ollama::show_model(http_client, api_url, api_key, &listing.name).await;
+                (listing, show)
+            })
+            .buffered(MAX_CONCURRENT_SHOW_REQUESTS)
+            .filter_map(|(listing, show)| {
+                future::ready(match show {
+                    Ok(show) => Some((listing, show)),
+                    Err(error) => {
+                        log::warn!("skipping Ollama model {}: {error:#}", listing.name);
+                        None
+                    }
+                })
+            })
+            // Embedding models can't chat.
+            .filter(|(_, show)| {
+                future::ready(
+                    show.capabilities.is_empty()
+                        || show.capabilities.iter().any(|c| c == "completion"),
+                )
+            })
+            .map(|(listing, show)| {
+                ollama::Model::new(
+                    &listing.name,
+                    None,
+                    Some(ollama::clamp_context_length(show.context_length())),
+                    Some(show.supports_tools()),
+                    Some(show.supports_vision()),
+                    Some(show.supports_thinking()),
+                )
+            })
+            .collect()
+            .await;
+
+        *self.fetched_models.write() = models;
+        Ok(())
+    }
+
+    pub fn create_language_model(&self, model: ollama::Model) -> Arc<dyn LanguageModel> {
+        Arc::new(OllamaLanguageModel {
+            id: LanguageModelId::from(model.id().to_string()),
+            model,
+            http_client: self.http_client.clone(),
+            settings: self.settings.clone(),
+            request_limiter: self.request_limiter.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl LanguageModelProvider for OllamaLanguageModelProvider {
+    fn id(&self) -> LanguageModelProviderId {
+        PROVIDER_ID
+    }
+
+    fn name(&self) -> LanguageModelProviderName {
+        PROVIDER_NAME
+    }
+
+    fn default_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        self.provided_models().into_iter().next()
+    }
+
+    fn default_fast_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        None
+    }
+
+    fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>> {
+        let mut models = BTreeMap::default();
+
+        for model in self.fetched_models.read().iter() {
+            models.insert(model.name.clone(), model.clone());
+        }
+
+        for model in &OllamaSettings::resolve(&self.settings).available_models {
+            models.insert(
+                model.name.clone(),
+                ollama::Model {
+                    name: model.name.clone(),
+                    display_name: model.display_name.clone(),
+                    max_tokens: model.max_tokens,
+                    keep_alive: model.keep_alive.clone(),
+                    supports_tools: model.supports_tools,
+                    supports_vision: model.supports_images,
+                    supports_thinking: model.supports_thinking,
+                },
+            );
+        }
+
+        models
+            .into_values()
+            .map(|model| self.create_language_model(model))
+            .collect()
+    }
+
+    /// Ollama needs no credentials, so this checks that the server is
+    /// reachable and discovers its models.
+    async fn authenticate(&self) -> anyhow::Result<()> {
+        self.refresh_models().await
+    }
+}
+
+pub struct OllamaLanguageModel {
+    id: LanguageModelId,
+    model: ollama::Model,
+    http_client: Arc<dyn HttpClient>,
+    settings: Option<Arc<OllamaSettings>>,
+    request_limiter: RateLimiter,
+}
+
+impl OllamaLanguageModel {
+    fn to_ollama_request(&self, request: LanguageModelRequest) -> ChatRequest {
+        let supports_vision = self.model.supports_vision.unwrap_or(false);
+
+        let mut messages = Vec::with_capacity(request.messages.len());
+        for message in request.messages {
+            let mut text = String::new();
+            let mut thinking = String::new();
+            let mut images = Vec::new();
+            let mut tool_calls = Vec::new();
+            for content in message.content {
+                match content {
+                    MessageContent::Text(content) => text.push_str(&content),
+                    MessageContent::Thinking { text, .. } => thinking.push_str(&text),
+                    MessageContent::RedactedThinking(_) => {}
+                    MessageContent::Image(image) if supports_vision => {
+                        images.push(image.source.to_string());
+                    }
+                    MessageContent::Image(_) => {}
+                    MessageContent::ToolUse(tool_use) => tool_calls.push(OllamaToolCall {
+                        function: OllamaFunctionCall {
+                            name: tool_use.name.to_string(),
+                            arguments: tool_use.input,
+                        },
+                    }),
+                    MessageContent::ToolResult(tool_result) => {
+                        let content = match tool_result.content {
+                            LanguageModelToolResultContent::Text(text) => text.to_string(),
+                        };
+                        messages.push(ChatMessage::Tool {
+                            tool_name: tool_result.tool_name.to_string(),
+                            content,
+                        });
+                    }
+                }
+            }
+
+            let images = (!images.is_empty()).then_some(images);
+            match message.role {
+                Role::User if text.is_empty() && images.is_none() => {}
+                Role::User => messages.push(ChatMessage::User {
+                    content: text,
+                    images,
+                }),
+                Role::Assistant => messages.push(ChatMessage::Assistant {
+                    content: text,
+                    tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                    images,
+                    thinking: (!thinking.is_empty()).then_some(thinking),
+                }),
+                Role::System => messages.push(ChatMessage::System { content: text }),
+            }
+        }
+
+        let tools = if self.supports_tools() {
+            request.tools.into_iter().map(tool_into_ollama).collect()
+        } else {
+            Vec::new()
+        };
+
+        ChatRequest {
+            model: self.model.name.clone(),
+            messages,
+            stream: true,
+            keep_alive: self.model.keep_alive.clone(),
+            options: Some(ChatOptions {
+                num_ctx: Some(self.model.max_tokens),
+                stop: Some(request.stop),
+                temperature: request.temperature.or(Some(1.0)),
+                ..Default::default()
+            }),
+            tools,
+            // Only thinking models accept the flag.
+            think: self
+                .model
+                .supports_thinking
+                .filter(|supported| *supported)
+                .map(|_| request.thinking_allowed),
+        }
+    }
+}
+
+fn tool_into_ollama(tool: LanguageModelRequestTool) -> OllamaTool {
+    OllamaTool::Function {
+        function: OllamaFunctionTool {
+            name: tool.name,
+            description: Some(tool.description),
+            parameters: Some(tool.input_schema),
+        },
+    }
+}
+
+#[async_trait::async_trait]
+impl LanguageModel for OllamaLanguageModel {
+    fn id(&self) -> LanguageModelId {
+        self.id.clone()
+    }
+
+    fn name(&self) -> LanguageModelName {
+        LanguageModelName::from(self.model.display_name().to_string())
+    }
+
+    fn provider_id(&self) -> LanguageModelProviderId {
+        PROVIDER_ID
+    }
+
+    fn provider_name(&self) -> LanguageModelProviderName {
+        PROVIDER_NAME
+    }
+
+    fn max_token_count(&self) -> u64 {
+        self.model.max_token_count()
+    }
+
+    async fn stream_completion(
+        &self,
+        request: LanguageModelRequest,
+    ) -> Result<
+        BoxStream<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+        LanguageModelCompletionError,
+    > {
+        let estimated_tokens = estimate_request_tokens(&request);
+        let request = self.to_ollama_request(request);
+        let settings = OllamaSettings::resolve(&self.settings);
+        let http_client = self.http_client.clone();
+        self.request_limiter
+            .stream(&self.id, estimated_tokens, async move {
+                let response = ollama::stream_chat_completion(
+                    http_client.as_ref(),
+                    settings.api_url(),
+                    Some(&settings.api_key),
+                    request,
+                )
+                .await
+                .map_err(|error| match error.downcast::<ollama::ApiError>() {
+                    Ok(error) => LanguageModelCompletionError::from(error),
+                    Err(error) => LanguageModelCompletionError::from(error),
+                })?;
+                Ok(OllamaEventMapper::new().map_stream(response).boxed())
+            })
+            .await
+    }
+
+    fn supports_tools(&self) -> bool {
+        self.model.supports_tools.unwrap_or(false)
+    }
+
+    fn supports_burn_mode(&self) -> bool {
+        false
+    }
+}
+
+pub struct OllamaEventMapper {
+    used_tools: bool,
+}
+
+impl Default for OllamaEventMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OllamaEventMapper {
+    pub fn new() -> Self {
+        Self { used_tools: false }
+    }
+
+    pub fn map_stream(
+        mut self,
+        events: Pin<Box<dyn Send + Stream<Item = anyhow::Result<ChatResponseDelta>>>>,
+    ) -> impl Stream<Item = Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>
+    {
+        events.flat_map(move |event| {
+            futures::stream::iter(match event {
+                Ok(event) => self.map_event(event),
+                Err(error) => vec![Err(LanguageModelCompletionError::from(error))],
+            })
+        })
+    }
+
+    pub fn map_event(
+        &mut self,
+        delta: ChatResponseDelta,
+    ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+        // Ollama doesn't assign ids to tool calls, so make up unique ones.
+        static TOOL_CALL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let mut events = Vec::new();
+        match delta.message {
+            ChatMessage::Assistant {
+                content,
+                tool_calls,
+                thinking,
+                ..
+            } => {
+                if let Some(thinking) = thinking.filter(|thinking| !thinking.is_empty()) {
+                    events.push(Ok(LanguageModelCompletionEvent::Thinking {
+                        text: thinking,
+                        signature: None,
+                    }));
+                }
+                if !content.is_empty() {
+                    events.push(Ok(LanguageModelCompletionEvent::Text(content)));
+                }
+                for tool_call in tool_calls.into_iter().flatten() {
+                    self.used_tools = true;
+                    let name: Arc<str> = tool_call.function.name.into();
+                    let next_tool_id = TOOL_CALL_COUNTER.fetch_add(1, atomic::Ordering::SeqCst);
+                    let id: LanguageModelToolUseId = format!("{name}-{next_tool_id}").into();
+                    events.push(Ok(LanguageModelCompletionEvent::ToolUse(
+                        LanguageModelToolUse {
+                            id,
+                            name,
+                            is_input_complete: true,
+                            raw_input: tool_call.function.arguments.to_string(),
+                            input: tool_call.function.arguments,
+                        },
+                    )));
+                }
+            }
+            message => log::error!("Unexpected Ollama message: {message:?}"),
+        }
+
+        if delta.done {
+            events.push(Ok(LanguageModelCompletionEvent::UsageUpdate(TokenUsage {
+                input_tokens: delta.prompt_eval_count.unwrap_or(0),
+                output_tokens: delta.eval_count.unwrap_or(0),
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 0,
+                reasoning_tokens: 0,
+            })));
+            let stop_reason = if self.used_tools {
+                StopReason::ToolUse
+            } else if delta.done_reason.as_deref() == Some("length") {
+                StopReason::MaxTokens
+            } else {
+                StopReason::EndTurn
+            };
+            events.push(Ok(LanguageModelCompletionEvent::Stop(stop_reason)));
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::FakeHttpClient;
+    use crate::model::{LanguageModelRequestMessage, LanguageModelToolResult};
+
+    const TAGS: &str = r#"{"models":[{"name":"qwen3:latest","modified_at":"2025-07-01T12:00:00Z","size":5225387923,"digest":"e4b5","details":{"format":"gguf","family":"qwen3","families":["qwen3"],"parameter_size":"8.2B","quantization_level":"Q4_K_M"}},{"name":"nomic-embed-text:latest","modified_at":"2025-07-01T12:00:00Z","size":274302450,"digest":"0a10","details":{"format":"gguf","family":"nomic-bert","families":null,"parameter_size":"137M","quantization_level":"F16"}},{"name":"broken:latest","modified_at":"2025-07-01T12:00:00Z","size":1024,"digest":"dead","details":{"format":"gguf","family":"llama","families":null,"parameter_size":"1B","quantization_level":"Q4_0"}}]}"#;
+
+    const CHAT: &str = r#"{"model":"qwen3:latest","created_at":"2025-07-01T12:00:00Z","message":{"role":"assistant","content":"","thinking":"The user wants the weather."},"done":false}
+{"model":"qwen3:latest","created_at":"2025-07-01T12:00:01Z","message":{"role":"assistant","content":"Let me check."},"done":false}
+{"model":"qwen3:latest","created_at":"2025-07-01T12:00:02Z","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"weather","arguments":{"city":"Paris"}}}]},"done":false}
+{"model":"qwen3:latest","created_at":"2025-07-01T12:00:03Z","message":{"role":"assistant","content":""},"done_reason":"stop","done":true,"prompt_eval_count":42,"eval_count":17}
+"#;
+
+    #[tokio::test]
+    async fn test_discover_models_and_stream_completion() {
+        // `/api/show` is requested for each model in the order of `/api/tags`.
+        let (http_client, sent_requests) = FakeHttpClient::replay_responses([
+            (200, TAGS),
+            (
+                200,
+                r#"{"capabilities":["completion","tools","thinking"],"model_info":{"general.architecture":"qwen3","qwen3.context_length":40960}}"#,
+            ),
+            (200, r#"{"capabilities":["embedding"],"model_info":{}}"#),
+            (500, r#"{"error":"model file is corrupt"}"#),
+            (200, CHAT),
+        ]);
+        let provider = OllamaLanguageModelProvider::new(
+            http_client,
+            Some(OllamaSettings {
+                api_url: "http://ollama.test".into(),
+                ..Default::default()
+            }),
+        );
+        assert!(provider.provided_models().is_empty());
+
+        provider.authenticate().await.unwrap();
+        let models = provider.provided_models();
+        assert_eq!(models.len(), 1);
+        let model = models[0].clone();
+        assert_eq!(model.id().0.as_ref(), "qwen3:latest");
+        assert_eq!(model.name().0.as_ref(), "qwen3");
+        assert_eq!(model.max_token_count(), 16384);
+        assert!(model.supports_tools());
+
+        let request = LanguageModelRequest {
+            messages: vec![
+                LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Weather in Paris?".into())],
+                    cache: false,
+                },
+                LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::ToolResult(LanguageModelToolResult {
+                        tool_use_id: "weather-0".into(),
+                        tool_name: "weather".into(),
+                        is_error: false,
+                        content: "Sunny".into(),
+                        output: None,
+                    })],
+                    cache: false,
+                },
+            ],
+            tools: vec![LanguageModelRequestTool {
+                name: "weather".into(),
+                description: "Looks up the weather".into(),
+                input_schema: serde_json::json!({ "type": "object" }),
+            }],
+            thinking_allowed: true,
+            ..Default::default()
+        };
+        let response = model.complete(request).await.unwrap();
+
+        let sent_requests = sent_requests.lock();
+        let paths = sent_requests
+            .iter()
+            .map(|request| request.parts.uri.path())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "/api/tags",
+                "/api/show",
+                "/api/show",
+                "/api/show",
+                "/api/chat"
+            ]
+        );
+        assert_eq!(sent_requests[3].json()["model"], "broken:latest");
+        let body = sent_requests[4].json();
+        assert_eq!(body["model"], "qwen3:latest");
+        assert_eq!(body["think"], true);
+        assert_eq!(body["options"]["num_ctx"], 16384);
+        assert_eq!(
+            body["messages"],
+            serde_json::json!([
+                { "role": "user", "content": "Weather in Paris?" },
+                { "role": "tool", "tool_name": "weather", "content": "Sunny" },
+            ])
+        );
+        assert_eq!(body["tools"][0]["function"]["name"], "weather");
+
+        assert_eq!(
+            response.message.content[0],
+            MessageContent::Thinking {
+                text: "The user wants the weather.".into(),
+                signature: None,
+            }
+        );
+        assert_eq!(response.text(), "Let me check.");
+        let tool_use = response.tool_uses().next().unwrap();
+        assert_eq!(tool_use.input, serde_json::json!({ "city": "Paris" }));
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        assert_eq!(response.usage.input_tokens, 42);
+        assert_eq!(response.usage.output_tokens, 17);
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::common::SecretString;
+use crate::http_client::HttpClient;
+use crate::model::{
+    CredentialProvider, EnvCredentialProvider, LanguageModel, LanguageModelProvider,
+    LanguageModelProviderId, LanguageModelProviderName, RateLimiter, RateLimits, resolve_api_key,
+};
+use crate::models::openai_provider::openai_model::{AvailableModel, OpenAiLanguageModel};
+use crate::models::openai_provider::profile::OpenAiCompatibleProfile;
+use crate::models::openai_provider::provider::OpenAiSettings;
+use crate::openai;
+
+/// A provider for a vendor serving the OpenAI Chat Completions API, such as
+/// DeepSeek or a local vLLM server, reported under the vendor's own id.
+pub struct OpenAiCompatibleProvider {
+    http_client: Arc<dyn HttpClient>,
+    profile: Arc<OpenAiCompatibleProfile>,
+    settings: Arc<OpenAiSettings>,
+    credentials: Arc<dyn CredentialProvider>,
+    request_limiter: RateLimiter,
+}
+
+/// Used for vendors that don't need an API key.
+struct NoCredentials;
+
+#[async_trait::async_trait]
+impl CredentialProvider for NoCredentials {
+    async fn api_key(&self) -> anyhow::Result<Option<SecretString>> {
+        Ok(None)
+    }
+}
+
+impl OpenAiCompatibleProvider {
+    /// Creates a provider for the vendor described by `profile`.
+    ///
+    /// `settings` can override the profile's URL, add headers and models, and
+    /// hold the API key. Unlike [`super::OpenAiLanguageModelProvider`], the
+    /// global `OpenAiSettings` are never used, as they belong to OpenAI. When
+    /// no API key is configured, it is read from the profile's `api_key_var`.
+    pub fn new(
+        http_client: Arc<dyn HttpClient>,
+        profile: OpenAiCompatibleProfile,
+        settings: Option<OpenAiSettings>,
+    ) -> Self {
+        let credentials: Arc<dyn CredentialProvider> = match &profile.api_key_var {
+            Some(var) => Arc::new(EnvCredentialProvider::new(var.to_string())),
+            None => Arc::new(NoCredentials),
+        };
+        Self {
+            http_client,
+            profile: Arc::new(profile),
+            settings: Arc::new(settings.unwrap_or_default()),
+            credentials,
+            request_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Replaces where the API key comes from when the settings have none.
+    pub fn with_credentials(mut self, credentials: Arc<dyn CredentialProvider>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Replaces the limits shared by every model this provider creates.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.request_limiter = RateLimiter::new(limits);
+        self
+    }
+
+    pub fn profile(&self) -> &OpenAiCompatibleProfile {
+        &self.profile
+    }
+
+    pub fn create_language_model(&self, model: openai::Model) -> Arc<dyn LanguageModel> {
+        Arc::new(OpenAiLanguageModel {
+            id: model.id().to_string().into(),
+            model,
+            http_client: self.http_client.clone(),
+            settings: Some(self.settings.clone()),
+            profile: self.profile.clone(),
+            credentials: self.credentials.clone(),
+            azure_credentials: self.credentials.clone(),
+            request_limiter: self.request_limiter.clone(),
+        })
+    }
+
+    /// The profile's models, replaced or extended by the configured ones.
+    fn models(&self) -> BTreeMap<String, openai::Model> {
+        self.profile
+            .models
+            .iter()
+            .chain(&self.settings.available_models)
+            .map(|model| (model.name.clone(), custom_model(model)))
+            .collect()
+    }
+
+    fn create_model_named(&self, name: &str) -> Option<Arc<dyn LanguageModel>> {
+        self.models()
+            .remove(name)
+            .map(|model| self.create_language_model(model))
+    }
+}
+
+fn custom_model(model: &AvailableModel) -> openai::Model {
+    openai::Model::Custom {
+        name: model.name.clone(),
+        display_name: model.display_name.clone(),
+        max_tokens: model.max_tokens,
+        max_output_tokens: model.max_output_tokens,
+        max_completion_tokens: model.max_completion_tokens,
+    }
+}
+
+#[async_trait::async_trait]
+impl LanguageModelProvider for OpenAiCompatibleProvider {
+    fn id(&self) -> LanguageModelProviderId {
+        self.profile.id.clone()
+    }
+
+    fn name(&self) -> LanguageModelProviderName {
+        self.profile.name.clone()
+    }
+
+    fn default_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        let model = self
+            .profile
+            .models
+            .first()
+            .or_else(|| self.settings.available_models.first())?;
+        self.create_model_named(&model.name)
+    }
+
+    fn default_fast_model(&self) -> Option<Arc<dyn LanguageModel>> {
+        self.create_model_named(self.profile.default_fast_model.as_ref()?)
+    }
+
+    fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>> {
+        self.models()
+            .into_values()
+            .map(|model| self.create_language_model(model))
+            .collect()
+    }
+
+    async fn authenticate(&self) -> anyhow::Result<()> {
+        if self.profile.api_key_var.is_some() {
+            resolve_api_key(
+                &self.settings.api_key,
+                self.credentials.as_ref(),
+                self.profile.name.clone(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::FakeHttpClient;
+    use crate::models::openai_provider::openai_model::into_open_ai;
+    use crate::model::{
+        LanguageModelId, LanguageModelRequest, LanguageModelRequestMessage, MessageContent, Role,
+        StopReason,
+    };
+
+    const RESPONSE: &str = r#"data: {"id":"1","model":"kimi-k2-0711-preview","choices":[{"index":0,"delta":{"role":"assistant","content":"Hello"},"finish_reason":null}]}
+
+data: {"id":"1","model":"kimi-k2-0711-preview","choices":[{"index":0,"delta":{},"finish_reason":"stop","usage":{"prompt_tokens":8,"completion_tokens":1,"total_tokens":9}}]}
+
+data: [DONE]
+
+"#;
+
+    #[tokio::test]
+    async fn test_vendor_profile() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(RESPONSE);
+        let provider = OpenAiCompatibleProvider::new(
+            http_client,
+            OpenAiCompatibleProfile::moonshot().with_header("X-Title", "omni"),
+            Some(OpenAiSettings {
+                api_key: "sk-moonshot".into(),
+                ..Default::default()
+            }),
+        );
+        assert_eq!(provider.id().0.as_ref(), "moonshot");
+
+        let model = provider.default_model().unwrap();
+        assert_eq!(model.provider_id().0.as_ref(), "moonshot");
+        assert_eq!(
+            model.id(),
+            LanguageModelId::from("kimi-k2-0711-preview".to_string())
+        );
+
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("Hi".into())],
+                cache: false,
+            }],
+            ..Default::default()
+        };
+        let response = model.complete(request).await.unwrap();
+        assert_eq!(response.text(), "Hello");
+        assert_eq!(response.stop_reason, StopReason::EndTurn);
+        assert_eq!(response.usage.input_tokens, 8);
+
+        let sent_request = sent_requests.lock().pop().unwrap();
+        assert_eq!(
+            sent_request.parts.uri.to_string(),
+            "https://api.moonshot.cn/v1/chat/completions"
+        );
+        assert_eq!(
+            sent_request.parts.headers["authorization"],
+            "Bearer sk-moonshot"
+        );
+        assert_eq!(sent_request.parts.headers["x-title"], "omni");
+        let body = sent_request.json();
+        assert_eq!(body["max_tokens"], 16_384);
+        assert!(body.get("max_completion_tokens").is_none());
+        assert!(body.get("stream_options").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_server_without_api_key() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(RESPONSE);
+        let provider = OpenAiCompatibleProvider::new(
+            http_client,
+            OpenAiCompatibleProfile::vllm(),
+            Some(OpenAiSettings {
+                api_url: "http://gpu-box:8000/v1".into(),
+                available_models: vec![AvailableModel {
+                    name: "Qwen/Qwen3-8B".into(),
+                    display_name: None,
+                    max_tokens: 32_768,
+                    max_output_tokens: Some(4_096),
+                    max_completion_tokens: None,
+                }],
+                ..Default::default()
+            }),
+        );
+        provider.authenticate().await.unwrap();
+        assert!(provider.default_fast_model().is_none());
+
+        let model = provider.default_model().unwrap();
+        model
+            .complete(LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Hi".into())],
+                    cache: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let sent_request = sent_requests.lock().pop().unwrap();
+        assert_eq!(
+            sent_request.parts.uri.to_string(),
+            "http://gpu-box:8000/v1/chat/completions"
+        );
+        assert!(sent_request.parts.headers.get("authorization").is_none());
+        let body = sent_request.json();
+        assert_eq!(body["max_completion_tokens"], 4_096);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    const REASONING_RESPONSE: &str = r#"data: {"id":"1","model":"deepseek-reasoner","choices":[{"index":0,"delta":{"role":"assistant","content":null,"reasoning_content":"The user "},"finish_reason":null}]}
+
+data: {"id":"1","model":"deepseek-reasoner","choices":[{"index":0,"delta":{"content":null,"reasoning_content":"greets me."},"finish_reason":null}]}
+
+data: {"id":"1","model":"deepseek-reasoner","choices":[{"index":0,"delta":{"content":"Hello","reasoning_content":null},"finish_reason":"stop"}]}
+
+data: [DONE]
+
+"#;
+
+    #[tokio::test]
+    async fn test_reasoning_content() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(REASONING_RESPONSE);
+        let provider = OpenAiCompatibleProvider::new(
+            http_client,
+            OpenAiCompatibleProfile::deepseek(),
+            Some(OpenAiSettings {
+                api_key: "sk-deepseek".into(),
+                ..Default::default()
+            }),
+        );
+        let model = provider.default_model().unwrap();
+        let mut request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("Hi".into())],
+                cache: false,
+            }],
+            ..Default::default()
+        };
+        let response = model.complete(request.clone()).await.unwrap();
+        assert_eq!(
+            response.message.content,
+            vec![
+                MessageContent::Thinking {
+                    text: "The user greets me.".into(),
+                    signature: None,
+                },
+                MessageContent::Text("Hello".into()),
+            ]
+        );
+
+        // DeepSeek rejects its reasoning as input, so it is left out.
+        request.messages.push(response.message);
+        request.messages.push(LanguageModelRequestMessage {
+            role: Role::User,
+            content: vec![MessageContent::Text("How are you?".into())],
+            cache: false,
+        });
+        model.complete(request.clone()).await.unwrap();
+        let body = sent_requests.lock().pop().unwrap().json();
+        assert_eq!(
+            body["messages"][1],
+            serde_json::json!({"role": "assistant", "content": "Hello"})
+        );
+
+        // Kimi gets it back.
+        let mut open_ai_request = into_open_ai(request, "kimi-thinking-preview", false, None);
+        OpenAiCompatibleProfile::moonshot().adapt_request(&mut open_ai_request);
+        assert_eq!(
+            open_ai_request.messages[1],
+            openai::RequestMessage::Assistant {
+                content: Some(openai::MessageContent::Plain("Hello".into())),
+                tool_calls: Vec::new(),
+                reasoning_content: Some("The user greets me.".into()),
+            }
+        );
+    }
+}
@@ -1,48 +1,55 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::pin::Pin;
 use std::str::FromStr;
-use anyhow::anyhow;
 use futures_core::Stream;
 use futures::{FutureExt, StreamExt, future::BoxFuture};
-use crate::model::{LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelToolUse, StopReason, TokenUsage};
+use crate::model::{LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelProviderName, LanguageModelToolUse, StopReason, TokenUsage};
 use crate::models::openai_provider::types::RawToolCall;
-use crate::openai::ResponseStreamEvent;
+use crate::openai::{OpenAiError, ResponseStreamEvent};
 
 pub struct OpenAiEventMapper {
-    tool_calls_by_index: HashMap<usize, RawToolCall>,
+    provider: LanguageModelProviderName,
+    tool_calls_by_index: BTreeMap<usize, RawToolCall>,
 }
 
 impl OpenAiEventMapper {
-    pub fn new() -> Self {
+    pub fn new(provider: LanguageModelProviderName) -> Self {
         Self {
-            tool_calls_by_index: HashMap::default(),
+            provider,
+            tool_calls_by_index: BTreeMap::default(),
         }
     }
 
     pub fn map_stream(
         mut self,
-        events: Pin<Box<dyn Send + Stream<Item = anyhow::Result<ResponseStreamEvent>>>>,
+        events: Pin<Box<dyn Send + Stream<Item = Result<ResponseStreamEvent, OpenAiError>>>>,
     ) -> impl Stream<Item = Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>
     {
         events.flat_map(move |event| {
             futures::stream::iter(match event {
                 Ok(event) => self.map_event(event),
-                Err(error) => vec![Err(LanguageModelCompletionError::from(anyhow!(error)))],
+                Err(error) => vec![Err(error.into_completion_error(self.provider.clone()))],
             })
         })
     }
 
     pub fn map_event(
         &mut self,
-        event: ResponseStreamEvent,
+        mut event: ResponseStreamEvent,
     ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
         let mut events = Vec::new();
-        if let Some(usage) = event.usage {
+        if let Some(usage) = event.take_usage() {
+            let cached_tokens = usage
+                .prompt_tokens_details
+                .map_or(0, |details| details.cached_tokens);
             events.push(Ok(LanguageModelCompletionEvent::UsageUpdate(TokenUsage {
-                input_tokens: usage.prompt_tokens,
+                input_tokens: usage.prompt_tokens.saturating_sub(cached_tokens),
                 output_tokens: usage.completion_tokens,
                 cache_creation_input_tokens: 0,
-                cache_read_input_tokens: 0,
+                cache_read_input_tokens: cached_tokens,
+                reasoning_tokens: usage
+                    .completion_tokens_details
+                    .map_or(0, |details| details.reasoning_tokens),
             })));
         }
 
@@ -50,59 +57,81 @@ impl OpenAiEventMapper {
             return events;
         };
 
+        if let Some(reasoning) = choice
+            .delta
+            .reasoning_content
+            .clone()
+            .or_else(|| choice.delta.reasoning.clone())
+            .filter(|reasoning| !reasoning.is_empty())
+        {
+            events.push(Ok(LanguageModelCompletionEvent::Thinking {
+                text: reasoning,
+                signature: None,
+            }));
+        }
+
         if let Some(content) = choice.delta.content.clone() {
             events.push(Ok(LanguageModelCompletionEvent::Text(content)));
         }
 
+        let mut updated_indices = Vec::new();
         if let Some(tool_calls) = choice.delta.tool_calls.as_ref() {
             for tool_call in tool_calls {
                 let entry = self.tool_calls_by_index.entry(tool_call.index).or_default();
 
-                if let Some(tool_id) = tool_call.id.clone() {
+                // Some servers repeat the id in every chunk, or send a new one;
+                // the first one is kept so that partial tool uses match.
+                if let Some(tool_id) = tool_call.id.clone()
+                    && entry.id.is_empty()
+                {
                     entry.id = tool_id;
                 }
 
                 if let Some(function) = tool_call.function.as_ref() {
-                    if let Some(name) = function.name.clone() {
-                        entry.name = name;
-                    }
-
-                    if let Some(arguments) = function.arguments.clone() {
-                        entry.arguments.push_str(&arguments);
-                    }
+                    entry.push_function_chunk(function);
+                    updated_indices.push(tool_call.index);
                 }
             }
         }
 
+        if let Some(function) = choice.delta.function_call.as_ref() {
+            self.tool_calls_by_index
+                .entry(0)
+                .or_default()
+                .push_function_chunk(function);
+            updated_indices.push(0);
+        }
+
+        updated_indices.dedup();
+        for index in updated_indices {
+            if let Some(tool_call) = self.tool_calls_by_index.get_mut(&index)
+                && let Some(tool_use) = partial_tool_use(tool_call)
+            {
+                events.push(Ok(LanguageModelCompletionEvent::ToolUse(tool_use)));
+            }
+        }
+
+        if choice.finish_reason.is_some() {
+            self.flush_tool_calls(&mut events);
+        }
+
         match choice.finish_reason.as_deref() {
             Some("stop") => {
                 events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::EndTurn)));
             }
-            Some("tool_calls") => {
-                events.extend(self.tool_calls_by_index.drain().map(|(_, tool_call)| {
-                    match serde_json::Value::from_str(&tool_call.arguments) {
-                        Ok(input) => Ok(LanguageModelCompletionEvent::ToolUse(
-                            LanguageModelToolUse {
-                                id: tool_call.id.clone().into(),
-                                name: tool_call.name.as_str().into(),
-                                is_input_complete: true,
-                                input,
-                                raw_input: tool_call.arguments.clone(),
-                            },
-                        )),
-                        Err(error) => Ok(LanguageModelCompletionEvent::ToolUseJsonParseError {
-                            id: tool_call.id.into(),
-                            tool_name: tool_call.name.into(),
-                            raw_input: tool_call.arguments.clone().into(),
-                            json_parse_error: error.to_string(),
-                        }),
-                    }
-                }));
-
+            Some("tool_calls" | "function_call") => {
                 events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::ToolUse)));
             }
+            Some("length") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(
+                    StopReason::MaxTokens,
+                )));
+            }
+            Some("content_filter") => {
+                events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::Refusal)));
+            }
             Some(stop_reason) => {
-                // log::error!("Unexpected OpenAI stop_reason: {stop_reason:?}",);
+                log::warn!("unexpected OpenAI finish_reason: {stop_reason}");
                 events.push(Ok(LanguageModelCompletionEvent::Stop(StopReason::EndTurn)));
             }
             None => {}
@@ -110,4 +139,128 @@ impl OpenAiEventMapper {
 
         events
     }
+
+    /// Emits every buffered tool call as a complete tool use. Servers don't
+    /// always finish with `tool_calls`, so this runs on any finish reason.
+    fn flush_tool_calls(
+        &mut self,
+        events: &mut Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
+    ) {
+        let tool_calls = std::mem::take(&mut self.tool_calls_by_index);
+        events.extend(tool_calls.into_values().map(|mut tool_call| {
+            let id = tool_call.id().to_string();
+            let arguments = tool_call.arguments.trim();
+            let input = if arguments.is_empty() {
+                Ok(serde_json::Value::Object(serde_json::Map::default()))
+            } else {
+                serde_json::Value::from_str(arguments)
+            };
+            match input {
+                Ok(input) => Ok(LanguageModelCompletionEvent::ToolUse(
+                    LanguageModelToolUse {
+                        id: id.into(),
+                        name: tool_call.name.as_str().into(),
+                        is_input_complete: true,
+                        input,
+                        raw_input: tool_call.arguments.clone(),
+                    },
+                )),
+                Err(error) => Ok(LanguageModelCompletionEvent::ToolUseJsonParseError {
+                    id: id.into(),
+                    tool_name: tool_call.name.into(),
+                    raw_input: tool_call.arguments.clone().into(),
+                    json_parse_error: error.to_string(),
+                }),
+            }
+        }));
+    }
+}
+
+/// Returns the tool use streamed so far, if its arguments can be completed to
+/// valid JSON, e.g. by closing unclosed delimiters. This way, the UI can be
+/// updated with whatever has been streamed back so far.
+fn partial_tool_use(tool_call: &mut RawToolCall) -> Option<LanguageModelToolUse> {
+    if tool_call.name.is_empty() || tool_call.arguments.is_empty() {
+        return None;
+    }
+    let input =
+        serde_json::Value::from_str(&partial_json_fixer::fix_json(&tool_call.arguments)).ok()?;
+    Some(LanguageModelToolUse {
+        id: tool_call.id().to_string().into(),
+        name: tool_call.name.as_str().into(),
+        is_input_complete: false,
+        input,
+        raw_input: tool_call.arguments.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_tool_calls() {
+        let chunks = [
+            r#"{"model":"gpt-4.1","choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","type":"function","function":{"name":"read_file","arguments":""}}]},"finish_reason":null}]}"#,
+            r#"{"model":"gpt-4.1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"search","arguments":"{\"query\": \"pa"}}]},"finish_reason":null}]}"#,
+            r#"{"model":"gpt-4.1","choices":[{"index":0,"delta":{"tool_calls":[{"index":1,"function":{"arguments":"{\"path\": \"a.rs\"}"}}]},"finish_reason":null}]}"#,
+            r#"{"model":"gpt-4.1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"ris\"}"}}]},"finish_reason":null}]}"#,
+            r#"{"model":"gpt-4.1","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#,
+        ];
+        let mut mapper = OpenAiEventMapper::new(LanguageModelProviderName::new("OpenAI"));
+        let tool_uses = chunks
+            .into_iter()
+            .flat_map(|chunk| mapper.map_event(serde_json::from_str(chunk).unwrap()))
+            .filter_map(|event| match event.unwrap() {
+                LanguageModelCompletionEvent::ToolUse(tool_use) => Some(tool_use),
+                _ => None,
+            })
+            .map(|tool_use| {
+                (
+                    tool_use.id.to_string(),
+                    tool_use.input,
+                    tool_use.is_input_complete,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tool_uses,
+            vec![
+                ("call_a".into(), serde_json::json!({"query": "pa"}), false),
+                ("call_b".into(), serde_json::json!({"path": "a.rs"}), false),
+                (
+                    "call_a".into(),
+                    serde_json::json!({"query": "paris"}),
+                    false
+                ),
+                ("call_a".into(), serde_json::json!({"query": "paris"}), true),
+                ("call_b".into(), serde_json::json!({"path": "a.rs"}), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tool_calls_flushed_on_stop() {
+        let chunks = [
+            r#"{"model":"gpt-4.1","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"search","arguments":"{\"query\": \"paris\"}"}}]},"finish_reason":null}]}"#,
+            r#"{"model":"gpt-4.1","choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#,
+        ];
+        let mut mapper = OpenAiEventMapper::new(LanguageModelProviderName::new("OpenAI"));
+        let events = chunks
+            .into_iter()
+            .flat_map(|chunk| mapper.map_event(serde_json::from_str(chunk).unwrap()))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let [.., LanguageModelCompletionEvent::ToolUse(tool_use), stop] = events.as_slice() else {
+            panic!("expected a tool use before the stop event, got {events:?}");
+        };
+        assert!(tool_use.is_input_complete);
+        assert_eq!(tool_use.id.to_string(), "call_a");
+        assert_eq!(tool_use.input, serde_json::json!({"query": "paris"}));
+        assert!(matches!(
+            stop,
+            LanguageModelCompletionEvent::Stop(StopReason::EndTurn)
+        ));
+        assert!(mapper.tool_calls_by_index.is_empty());
+    }
 }
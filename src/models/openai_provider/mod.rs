@@ -1,6 +1,11 @@
 mod provider;
 mod openai_model;
 mod event_mapper;
+mod responses_event_mapper;
 mod types;
+mod profile;
+mod compatible;
 pub use provider::*;
-pub use openai_model::*;
\ No newline at end of file
+pub use openai_model::*;
+pub use profile::*;
+pub use compatible::*;
\ No newline at end of file
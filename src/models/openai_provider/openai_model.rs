@@ -8,16 +8,21 @@ use std::pin::Pin;
 use std::sync::Arc;
 // use futures_core::{future::BoxFuture, stream::{BoxStream};
 
-use crate::OpenAiSettings;
+use crate::{OpenAiApi, OpenAiSettings};
+use crate::common::SecretString;
 use crate::http_client::HttpClient;
 use crate::model::{
     LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelId,
     LanguageModelName, LanguageModelProviderId, LanguageModelProviderName, LanguageModelRequest,
-    LanguageModelToolChoice, LanguageModelToolResultContent, MessageContent, Role,
+    CredentialProvider, LanguageModelToolChoice, LanguageModelToolResultContent, MessageContent,
+    RateLimiter, ReasoningEffort, Role, ThinkingConfig, ThinkingSupport, estimate_request_tokens,
+    resolve_api_key,
 };
 use crate::models::openai_provider::event_mapper::OpenAiEventMapper;
-use crate::openai::{self, ImageUrl, ResponseStreamEvent};
-use futures_util::{FutureExt, StreamExt};
+use crate::models::openai_provider::profile::OpenAiCompatibleProfile;
+use crate::models::openai_provider::responses_event_mapper::OpenAiResponsesEventMapper;
+use crate::openai::{self, ImageUrl, OpenAiError, ResponseStreamEvent, responses};
+use futures_util::{FutureExt, StreamExt, future, stream};
 use log::info;
 use serde::{Deserialize, Serialize};
 use strum::EnumIter;
@@ -31,22 +36,162 @@ pub struct OpenAiLanguageModel {
     pub(crate) model: openai::Model,
     // pub(crate) state: State,
     pub(crate) http_client: Arc<dyn HttpClient>,
+    pub(crate) settings: Option<Arc<OpenAiSettings>>,
+    pub(crate) profile: Arc<OpenAiCompatibleProfile>,
+    pub(crate) credentials: Arc<dyn CredentialProvider>,
+    pub(crate) azure_credentials: Arc<dyn CredentialProvider>,
+    pub(crate) request_limiter: RateLimiter,
 }
 
 impl OpenAiLanguageModel {
+    /// Returns where the key comes from for the endpoint `settings` target,
+    /// since global settings may switch to Azure after the model is created.
+    fn credentials(&self, settings: &OpenAiSettings) -> &dyn CredentialProvider {
+        if settings.azure.is_some() {
+            self.azure_credentials.as_ref()
+        } else {
+            self.credentials.as_ref()
+        }
+    }
+
     async fn stream_completion(
         &self,
+        settings: Arc<OpenAiSettings>,
+        api_key: SecretString,
         request: openai::Request,
-    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<ResponseStreamEvent>>> {
-        let http_client = self.http_client.clone();
-        let openai_settings =
-            global_registry::get!(OpenAiSettings).expect("OpenAiSettings not found");
-        let api_key = openai_settings.api_key.clone();
-        let base_url = openai_settings.api_url.clone();
+    ) -> Result<
+        BoxStream<'static, Result<ResponseStreamEvent, OpenAiError>>,
+        LanguageModelCompletionError,
+    > {
+        let http_client = self.http_client.as_ref();
+
+        let api_url = if settings.api_url.is_empty() {
+            self.profile.api_url.as_ref()
+        } else {
+            settings.api_url.as_str()
+        };
+        let mut headers = self.profile.headers.clone();
+        headers.extend(settings.headers());
+
+        let deployment = match &settings.azure {
+            Some(_) if settings.api_url.is_empty() => {
+                return Err(LanguageModelCompletionError::Other(anyhow!(
+                    "the api_url of the Azure OpenAI resource is not configured"
+                )));
+            }
+            Some(azure) => Some(openai::AzureDeployment {
+                deployment: azure.deployment(self.model.id()),
+                api_version: azure.api_version(),
+                auth: azure.auth,
+            }),
+            None => None,
+        };
+        let response = if request.stream {
+            match &deployment {
+                Some(deployment) => {
+                    openai::stream_azure_completion_with_rate_limit_info(
+                        http_client,
+                        api_url,
+                        deployment,
+                        &api_key,
+                        &headers,
+                        request,
+                    )
+                    .await
+                }
+                None => {
+                    openai::stream_completion_with_rate_limit_info(
+                        http_client,
+                        api_url,
+                        &api_key,
+                        &headers,
+                        request,
+                    )
+                    .await
+                }
+            }
+        } else {
+            let response = match &deployment {
+                Some(deployment) => {
+                    openai::complete_azure_with_rate_limit_info(
+                        http_client,
+                        api_url,
+                        deployment,
+                        &api_key,
+                        &headers,
+                        request,
+                    )
+                    .await
+                }
+                None => {
+                    openai::complete_with_rate_limit_info(
+                        http_client,
+                        api_url,
+                        &api_key,
+                        &headers,
+                        request,
+                    )
+                    .await
+                }
+            };
+            // The whole completion is mapped like a stream of one event.
+            response.map(|(completion, rate_limits)| {
+                let event = ResponseStreamEvent::from(completion);
+                (stream::once(future::ready(Ok(event))).boxed(), rate_limits)
+            })
+        };
+        let (response, rate_limits) = response.map_err(|error| self.map_error(error))?;
+        if let Some(rate_limits) = rate_limits {
+            self.request_limiter.update(&(&rate_limits).into());
+        }
+        Ok(response)
+    }
 
-        let response =
-            openai::stream_completion(http_client.as_ref(), &base_url, &api_key, request).await?;
-        Ok(response.boxed())
+    async fn stream_response(
+        &self,
+        settings: Arc<OpenAiSettings>,
+        api_key: SecretString,
+        request: responses::Request,
+    ) -> Result<
+        BoxStream<'static, Result<responses::StreamEvent, OpenAiError>>,
+        LanguageModelCompletionError,
+    > {
+        if settings.azure.is_some() {
+            return Err(LanguageModelCompletionError::Other(anyhow!(
+                "the Responses API is not supported for Azure OpenAI deployments"
+            )));
+        }
+        let api_url = if settings.api_url.is_empty() {
+            self.profile.api_url.as_ref()
+        } else {
+            settings.api_url.as_str()
+        };
+        let mut headers = self.profile.headers.clone();
+        headers.extend(settings.headers());
+
+        let (response, rate_limits) = responses::stream_response(
+            self.http_client.as_ref(),
+            api_url,
+            &api_key,
+            &headers,
+            request,
+        )
+        .await
+        .map_err(|error| self.map_error(error))?;
+        if let Some(rate_limits) = rate_limits {
+            self.request_limiter.update(&(&rate_limits).into());
+        }
+        Ok(response)
+    }
+
+    fn map_error(&self, error: OpenAiError) -> LanguageModelCompletionError {
+        let error = error.into_completion_error(self.profile.name.clone());
+        if error.is_authentication_error() {
+            // The key may have been rotated; load it again next time.
+            self.credentials.invalidate();
+            self.azure_credentials.invalidate();
+        }
+        error
     }
 }
 #[async_trait::async_trait]
@@ -60,11 +205,11 @@ impl LanguageModel for OpenAiLanguageModel {
     }
 
     fn provider_id(&self) -> LanguageModelProviderId {
-        OPEN_AI_PROVIDER_ID
+        self.profile.id.clone()
     }
 
     fn provider_name(&self) -> LanguageModelProviderName {
-        OPEN_AI_PROVIDER_NAME
+        self.profile.name.clone()
     }
 
     fn max_token_count(&self) -> u64 {
@@ -74,11 +219,17 @@ impl LanguageModel for OpenAiLanguageModel {
         self.model.max_output_tokens()
     }
     fn supports_tools(&self) -> bool {
-        return true;
+        self.profile.capabilities.tools
     }
     fn supports_burn_mode(&self) -> bool {
         return false;
     }
+    fn thinking_support(&self) -> ThinkingSupport {
+        ThinkingSupport {
+            effort: self.model.is_reasoning_model(),
+            budget: false,
+        }
+    }
     async fn stream_completion(
         &self,
         request: LanguageModelRequest,
@@ -86,17 +237,68 @@ impl LanguageModel for OpenAiLanguageModel {
         BoxStream<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>,
         LanguageModelCompletionError,
     > {
-        let request = into_open_ai(
+        let settings = OpenAiSettings::resolve(&self.settings);
+        let api_key = match resolve_api_key(
+            &settings.api_key,
+            self.credentials(&settings),
+            self.profile.name.clone(),
+        )
+        .await
+        {
+            // Vendors without an API key variable, like local servers, run
+            // without a key unless one is configured.
+            Err(LanguageModelCompletionError::NoApiKey { .. })
+                if self.profile.api_key_var.is_none() =>
+            {
+                SecretString::default()
+            }
+            api_key => api_key?,
+        };
+        let estimated_tokens = estimate_request_tokens(&request);
+        let provider = self.profile.name.clone();
+        if settings.api(self.model.id()) == OpenAiApi::Responses {
+            let mut request =
+                into_open_ai_responses(request, &self.model, self.max_output_tokens());
+            if !self.profile.capabilities.tools {
+                request.tools.clear();
+                request.tool_choice = None;
+            }
+            let future = self.stream_response(settings, api_key, request);
+            return self
+                .request_limiter
+                .stream(&self.id, estimated_tokens, async move {
+                    let response = future.await?;
+                    let mapper = OpenAiResponsesEventMapper::new(provider);
+                    Ok(mapper.map_stream(response).boxed())
+                })
+                .await;
+        }
+        let supports_parallel_tool_calls = match &self.model {
+            openai::Model::Custom { .. } => self.profile.capabilities.parallel_tool_calls,
+            model => model.supports_parallel_tool_calls(),
+        };
+        let mut request = into_open_ai(
             request,
             self.model.id(),
-            self.model.supports_parallel_tool_calls(),
+            supports_parallel_tool_calls,
             self.max_output_tokens(),
         );
-        let completion = self.stream_completion(request).await?.boxed();
-        let mapper = OpenAiEventMapper::new();
-        Ok(mapper.map_stream(completion).boxed())
+        if !self.model.is_reasoning_model() {
+            request.reasoning_effort = None;
+        }
+        request.stream = !settings.disable_streaming;
+        self.profile.adapt_request(&mut request);
+        let future = self.stream_completion(settings, api_key, request);
+        self.request_limiter
+            .stream(&self.id, estimated_tokens, async move {
+                let completion = future.await?;
+                let mapper = OpenAiEventMapper::new(provider);
+                Ok(mapper.map_stream(completion).boxed())
+            })
+            .await
     }
 }
+
 fn add_message_content_part(
     new_part: openai::MessagePart,
     role: Role,
@@ -114,6 +316,17 @@ fn add_message_content_part(
         | (Role::System, Some(openai::RequestMessage::System { content, .. })) => {
             content.push_part(new_part);
         }
+        // The text following thinking.
+        (
+            Role::Assistant,
+            Some(openai::RequestMessage::Assistant {
+                content: content @ None,
+                tool_calls,
+                ..
+            }),
+        ) if tool_calls.is_empty() => {
+            *content = Some(openai::MessageContent::from(vec![new_part]));
+        }
         _ => {
             messages.push(match role {
                 Role::User => openai::RequestMessage::User {
@@ -122,6 +335,7 @@ fn add_message_content_part(
                 Role::Assistant => openai::RequestMessage::Assistant {
                     content: Some(openai::MessageContent::from(vec![new_part])),
                     tool_calls: Vec::new(),
+                    reasoning_content: None,
                 },
                 Role::System => openai::RequestMessage::System {
                     content: openai::MessageContent::from(vec![new_part]),
@@ -136,19 +350,32 @@ pub fn into_open_ai(
     supports_parallel_tool_calls: bool,
     max_output_tokens: Option<u64>,
 ) -> openai::Request {
-    let stream = !model_id.starts_with("o1-");
-
     let mut messages = Vec::new();
     for message in request.messages {
         for content in message.content {
             match content {
-                MessageContent::Text(text) | MessageContent::Thinking { text, .. } => {
-                    add_message_content_part(
-                        openai::MessagePart::Text { text: text },
-                        message.role,
-                        &mut messages,
-                    )
+                MessageContent::Text(text) => add_message_content_part(
+                    openai::MessagePart::Text { text: text },
+                    message.role,
+                    &mut messages,
+                ),
+                // The profile decides whether the server gets the thinking back.
+                MessageContent::Thinking { text, .. } if message.role == Role::Assistant => {
+                    if let Some(openai::RequestMessage::Assistant {
+                        reasoning_content: Some(reasoning_content),
+                        ..
+                    }) = messages.last_mut()
+                    {
+                        reasoning_content.push_str(&text);
+                    } else {
+                        messages.push(openai::RequestMessage::Assistant {
+                            content: None,
+                            tool_calls: Vec::new(),
+                            reasoning_content: Some(text),
+                        });
+                    }
                 }
+                MessageContent::Thinking { .. } => {}
                 MessageContent::RedactedThinking(_) => {}
                 MessageContent::Image(image) => {
                     add_message_content_part(
@@ -182,6 +409,7 @@ pub fn into_open_ai(
                         messages.push(openai::RequestMessage::Assistant {
                             content: None,
                             tool_calls: vec![tool_call],
+                            reasoning_content: None,
                         });
                     }
                 }
@@ -213,13 +441,13 @@ pub fn into_open_ai(
     openai::Request {
         model: model_id.into(),
         messages,
-        stream,
+        stream: true,
         stop: request.stop,
         temperature: request.temperature.unwrap_or(1.0),
         max_completion_tokens: max_output_tokens,
+        max_tokens: None,
         parallel_tool_calls: if supports_parallel_tool_calls && !request.tools.is_empty() {
-            // Disable parallel tool calls, as the Agent currently expects a maximum of one per turn.
-            Some(false)
+            request.parallel_tool_calls
         } else {
             None
         },
@@ -239,6 +467,142 @@ pub fn into_open_ai(
             LanguageModelToolChoice::Any => openai::ToolChoice::Required,
             LanguageModelToolChoice::None => openai::ToolChoice::None,
         }),
+        stream_options: None,
+        reasoning_effort: request.thinking.map(reasoning_effort),
+    }
+}
+
+/// OpenAI only takes an effort, so budgets are rounded to the closest one.
+/// Reasoning can't be turned off, so `Off` asks for as little as possible.
+fn reasoning_effort(thinking: ThinkingConfig) -> openai::ReasoningEffort {
+    match thinking {
+        ThinkingConfig::Off
+        | ThinkingConfig::Effort {
+            effort: ReasoningEffort::Low,
+        } => openai::ReasoningEffort::Low,
+        ThinkingConfig::Effort {
+            effort: ReasoningEffort::Medium,
+        } => openai::ReasoningEffort::Medium,
+        ThinkingConfig::Effort {
+            effort: ReasoningEffort::High,
+        } => openai::ReasoningEffort::High,
+        ThinkingConfig::Budget { budget_tokens } => match budget_tokens {
+            0..4_096 => openai::ReasoningEffort::Low,
+            4_096..16_384 => openai::ReasoningEffort::Medium,
+            _ => openai::ReasoningEffort::High,
+        },
+    }
+}
+
+/// Converts a request for the Responses API. Reasoning is requested with a
+/// summary and encrypted content, so the response doesn't need to be stored
+/// for the conversation to continue.
+pub fn into_open_ai_responses(
+    request: LanguageModelRequest,
+    model: &openai::Model,
+    max_output_tokens: Option<u64>,
+) -> responses::Request {
+    let mut input = Vec::new();
+    for message in request.messages {
+        for content in message.content {
+            let part = match content {
+                MessageContent::Text(text) if message.role == Role::Assistant => {
+                    responses::InputContent::OutputText { text }
+                }
+                MessageContent::Text(text) => responses::InputContent::InputText { text },
+                MessageContent::Image(image) => responses::InputContent::InputImage {
+                    image_url: image.to_base64_url(),
+                },
+                // Summaries are not passed back; the encrypted reasoning is.
+                MessageContent::Thinking { .. } => continue,
+                MessageContent::RedactedThinking(data) => {
+                    // Redacted thinking from other providers can't be used.
+                    if let Ok(item @ responses::InputItem::Reasoning { .. }) =
+                        serde_json::from_str(&data)
+                    {
+                        input.push(item);
+                    }
+                    continue;
+                }
+                MessageContent::ToolUse(tool_use) => {
+                    input.push(responses::InputItem::FunctionCall {
+                        call_id: tool_use.id.to_string(),
+                        name: tool_use.name.to_string(),
+                        arguments: serde_json::to_string(&tool_use.input).unwrap_or_default(),
+                    });
+                    continue;
+                }
+                MessageContent::ToolResult(tool_result) => {
+                    let output = match &tool_result.content {
+                        LanguageModelToolResultContent::Text(text) => text.to_string(),
+                    };
+                    input.push(responses::InputItem::FunctionCallOutput {
+                        call_id: tool_result.tool_use_id.to_string(),
+                        output,
+                    });
+                    continue;
+                }
+            };
+
+            let role = match message.role {
+                Role::User => openai::Role::User,
+                Role::Assistant => openai::Role::Assistant,
+                Role::System => openai::Role::System,
+            };
+            match input.last_mut() {
+                Some(responses::InputItem::Message {
+                    role: last_role,
+                    content,
+                }) if *last_role == role => content.push(part),
+                _ => input.push(responses::InputItem::Message {
+                    role,
+                    content: vec![part],
+                }),
+            }
+        }
+    }
+
+    let reasoning = model.is_reasoning_model();
+    let parallel_tool_calls = request
+        .parallel_tool_calls
+        .filter(|_| !request.tools.is_empty());
+    responses::Request {
+        model: model.id().into(),
+        input,
+        instructions: None,
+        stream: true,
+        max_output_tokens,
+        temperature: if reasoning { None } else { request.temperature },
+        tools: request
+            .tools
+            .into_iter()
+            .map(|tool| responses::Tool::Function {
+                name: tool.name,
+                description: Some(tool.description),
+                parameters: Some(tool.input_schema),
+                strict: None,
+            })
+            .collect(),
+        tool_choice: request.tool_choice.map(|choice| match choice {
+            LanguageModelToolChoice::Auto => responses::ToolChoice::Auto,
+            LanguageModelToolChoice::Any => responses::ToolChoice::Required,
+            LanguageModelToolChoice::None => responses::ToolChoice::None,
+        }),
+        parallel_tool_calls,
+        reasoning: Some(responses::ReasoningConfig {
+            effort: request.thinking.map(reasoning_effort),
+            summary: request
+                .thinking_allowed
+                .then_some(responses::ReasoningSummary::Auto),
+        })
+        .filter(|config| reasoning && *config != responses::ReasoningConfig::default()),
+        include: if reasoning {
+            vec![responses::INCLUDE_REASONING_ENCRYPTED_CONTENT.to_string()]
+        } else {
+            Vec::new()
+        },
+        previous_response_id: None,
+        store: Some(false),
     }
 }
 
@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+
+use crate::common::SharedString;
+use crate::model::{LanguageModelProviderId, LanguageModelProviderName};
+use crate::models::openai_provider::openai_model::{
+    AvailableModel, OPEN_AI_PROVIDER_ID, OPEN_AI_PROVIDER_NAME,
+};
+use crate::openai::{self, StreamOptions};
+
+/// Describes a vendor serving the OpenAI Chat Completions API: where it lives,
+/// what it supports and how it deviates from OpenAI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenAiCompatibleProfile {
+    pub id: LanguageModelProviderId,
+    pub name: LanguageModelProviderName,
+    /// The base URL, without the `/chat/completions` suffix.
+    pub api_url: SharedString,
+    /// The environment variable holding the API key, if the vendor needs one.
+    pub api_key_var: Option<SharedString>,
+    /// Headers sent with every request, e.g. OpenRouter's `HTTP-Referer`.
+    pub headers: BTreeMap<String, String>,
+    pub capabilities: OpenAiCompatibleCapabilities,
+    pub quirks: OpenAiCompatibleQuirks,
+    /// The models offered by default. The first one is the default model.
+    pub models: Vec<AvailableModel>,
+    pub default_fast_model: Option<SharedString>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpenAiCompatibleCapabilities {
+    pub tools: bool,
+    pub images: bool,
+    /// Whether custom models accept the `parallel_tool_calls` parameter.
+    pub parallel_tool_calls: bool,
+    /// Whether `stream_options.include_usage` is accepted.
+    pub stream_usage: bool,
+}
+
+impl Default for OpenAiCompatibleCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            images: false,
+            parallel_tool_calls: false,
+            stream_usage: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpenAiCompatibleQuirks {
+    /// Send the output limit as `max_tokens` instead of `max_completion_tokens`.
+    pub legacy_max_tokens: bool,
+    /// Send thinking back as the `reasoning_content` of assistant messages.
+    /// Otherwise it is dropped, as most servers reject or ignore it.
+    pub reasoning_content_input: bool,
+}
+
+impl OpenAiCompatibleProfile {
+    pub fn new(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        api_url: impl Into<SharedString>,
+    ) -> Self {
+        Self {
+            id: LanguageModelProviderId::from(id.into()),
+            name: LanguageModelProviderName(SharedString::from(name.into())),
+            api_url: api_url.into(),
+            api_key_var: None,
+            headers: BTreeMap::new(),
+            capabilities: OpenAiCompatibleCapabilities::default(),
+            quirks: OpenAiCompatibleQuirks::default(),
+            models: Vec::new(),
+            default_fast_model: None,
+        }
+    }
+
+    pub fn with_api_key_var(mut self, var: impl Into<SharedString>) -> Self {
+        self.api_key_var = Some(var.into());
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: OpenAiCompatibleCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn with_quirks(mut self, quirks: OpenAiCompatibleQuirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn with_models(mut self, models: impl IntoIterator<Item = AvailableModel>) -> Self {
+        self.models = models.into_iter().collect();
+        self
+    }
+
+    pub fn with_default_fast_model(mut self, name: impl Into<SharedString>) -> Self {
+        self.default_fast_model = Some(name.into());
+        self
+    }
+
+    /// OpenAI itself. Its built-in models know their own capabilities.
+    pub fn openai() -> Self {
+        Self {
+            id: OPEN_AI_PROVIDER_ID,
+            name: OPEN_AI_PROVIDER_NAME,
+            api_url: SharedString::new_static(openai::OPEN_AI_API_URL),
+            api_key_var: Some(SharedString::new_static("OPENAI_API_KEY")),
+            headers: BTreeMap::new(),
+            capabilities: OpenAiCompatibleCapabilities {
+                tools: true,
+                images: true,
+                parallel_tool_calls: false,
+                stream_usage: true,
+            },
+            quirks: OpenAiCompatibleQuirks::default(),
+            models: Vec::new(),
+            default_fast_model: None,
+        }
+    }
+
+    pub fn deepseek() -> Self {
+        Self::new("deepseek", "DeepSeek", "https://api.deepseek.com/v1")
+            .with_api_key_var("DEEPSEEK_API_KEY")
+            .with_quirks(OpenAiCompatibleQuirks {
+                legacy_max_tokens: true,
+                ..Default::default()
+            })
+            .with_models([
+                model("deepseek-chat", "DeepSeek Chat", 128_000, 8_192),
+                model("deepseek-reasoner", "DeepSeek Reasoner", 128_000, 64_000),
+            ])
+            .with_default_fast_model("deepseek-chat")
+    }
+
+    pub fn mistral() -> Self {
+        Self::new("mistral", "Mistral", "https://api.mistral.ai/v1")
+            .with_api_key_var("MISTRAL_API_KEY")
+            .with_capabilities(OpenAiCompatibleCapabilities {
+                images: true,
+                parallel_tool_calls: true,
+                ..Default::default()
+            })
+            .with_quirks(OpenAiCompatibleQuirks {
+                legacy_max_tokens: true,
+                ..Default::default()
+            })
+            .with_models([
+                model("mistral-large-latest", "Mistral Large", 131_072, 32_768),
+                model("mistral-medium-latest", "Mistral Medium", 131_072, 32_768),
+                model("mistral-small-latest", "Mistral Small", 131_072, 32_768),
+                model("codestral-latest", "Codestral", 256_000, 32_768),
+            ])
+            .with_default_fast_model("mistral-small-latest")
+    }
+
+    /// OpenRouter ranks apps by the `HTTP-Referer` and `X-Title` headers; set
+    /// them with [`Self::with_header`].
+    pub fn open_router() -> Self {
+        Self::new("openrouter", "OpenRouter", "https://openrouter.ai/api/v1")
+            .with_api_key_var("OPENROUTER_API_KEY")
+            .with_capabilities(OpenAiCompatibleCapabilities {
+                images: true,
+                parallel_tool_calls: true,
+                ..Default::default()
+            })
+    }
+
+    pub fn groq() -> Self {
+        Self::new("groq", "Groq", "https://api.groq.com/openai/v1")
+            .with_api_key_var("GROQ_API_KEY")
+            .with_capabilities(OpenAiCompatibleCapabilities {
+                parallel_tool_calls: true,
+                // Groq reports usage in `x_groq` and rejects `stream_options`.
+                stream_usage: false,
+                ..Default::default()
+            })
+            .with_models([
+                model("llama-3.3-70b-versatile", "Llama 3.3 70B", 131_072, 32_768),
+                model("llama-3.1-8b-instant", "Llama 3.1 8B", 131_072, 8_192),
+            ])
+            .with_default_fast_model("llama-3.1-8b-instant")
+    }
+
+    /// A local vLLM server. Models are served under the name they were
+    /// started with, so they have to be configured.
+    pub fn vllm() -> Self {
+        Self::new("vllm", "vLLM", "http://localhost:8000/v1")
+    }
+
+    /// The local server of LM Studio. Models have to be configured.
+    pub fn lm_studio() -> Self {
+        Self::new("lmstudio", "LM Studio", "http://localhost:1234/v1").with_quirks(
+            OpenAiCompatibleQuirks {
+                legacy_max_tokens: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn moonshot() -> Self {
+        Self::new("moonshot", "Moonshot", "https://api.moonshot.cn/v1")
+            .with_api_key_var("MOONSHOT_API_KEY")
+            .with_capabilities(OpenAiCompatibleCapabilities {
+                // Moonshot reports usage on the last choice instead.
+                stream_usage: false,
+                ..Default::default()
+            })
+            .with_quirks(OpenAiCompatibleQuirks {
+                legacy_max_tokens: true,
+                // Kimi thinking models expect their reasoning back between
+                // tool calls.
+                reasoning_content_input: true,
+            })
+            .with_models([
+                model("kimi-k2-0711-preview", "Kimi K2", 131_072, 16_384),
+                model("kimi-k2-turbo-preview", "Kimi K2 Turbo", 131_072, 16_384),
+                model("kimi-thinking-preview", "Kimi Thinking", 131_072, 16_384),
+            ])
+            .with_default_fast_model("kimi-k2-turbo-preview")
+    }
+
+    /// Rewrites a request built for OpenAI to what this vendor accepts.
+    pub(crate) fn adapt_request(&self, request: &mut openai::Request) {
+        if !self.capabilities.tools {
+            request.tools.clear();
+            request.tool_choice = None;
+        }
+        if !self.capabilities.images {
+            for message in &mut request.messages {
+                strip_images(message);
+            }
+        }
+        if self.capabilities.stream_usage && request.stream {
+            request.stream_options = Some(StreamOptions {
+                include_usage: true,
+            });
+        }
+        if self.quirks.legacy_max_tokens {
+            request.max_tokens = request.max_completion_tokens.take();
+        }
+        if !self.quirks.reasoning_content_input {
+            request.messages.retain_mut(|message| match message {
+                openai::RequestMessage::Assistant {
+                    content,
+                    tool_calls,
+                    reasoning_content,
+                } => {
+                    *reasoning_content = None;
+                    content.is_some() || !tool_calls.is_empty()
+                }
+                _ => true,
+            });
+        }
+    }
+}
+
+fn model(
+    name: &str,
+    display_name: &str,
+    max_tokens: u64,
+    max_output_tokens: u64,
+) -> AvailableModel {
+    AvailableModel {
+        name: name.into(),
+        display_name: Some(display_name.into()),
+        max_tokens,
+        max_output_tokens: Some(max_output_tokens),
+        max_completion_tokens: None,
+    }
+}
+
+fn strip_images(message: &mut openai::RequestMessage) {
+    let content = match message {
+        openai::RequestMessage::User { content }
+        | openai::RequestMessage::System { content }
+        | openai::RequestMessage::Tool { content, .. } => content,
+        openai::RequestMessage::Assistant {
+            content: Some(content),
+            ..
+        } => content,
+        openai::RequestMessage::Assistant { content: None, .. } => return,
+    };
+    if let openai::MessageContent::Multipart(parts) = content {
+        parts.retain(|part| !matches!(part, openai::MessagePart::Image { .. }));
+        *content = std::mem::take(parts).into();
+    }
+}
@@ -1,11 +1,16 @@
+use crate::common::SecretString;
 use crate::http_client::HttpClient;
 use crate::model::{
-    LanguageModel, LanguageModelProvider, LanguageModelProviderId, LanguageModelProviderName,
+    CredentialProvider, EnvCredentialProvider, LanguageModel, LanguageModelProvider,
+    LanguageModelProviderId, LanguageModelProviderName, RateLimiter, RateLimits, resolve_api_key,
 };
 use crate::models::openai_provider::openai_model::{
-     OPEN_AI_PROVIDER_ID, OPEN_AI_PROVIDER_NAME, OpenAiLanguageModel,
+    AvailableModel, OPEN_AI_PROVIDER_ID, OPEN_AI_PROVIDER_NAME, OpenAiLanguageModel,
 };
-use std::sync::{Arc, Mutex};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use strum::IntoEnumIterator;
+use crate::models::openai_provider::profile::OpenAiCompatibleProfile;
 use crate::openai;
 
 const PROVIDER_ID: LanguageModelProviderId = OPEN_AI_PROVIDER_ID;
@@ -13,25 +18,150 @@ const PROVIDER_NAME: LanguageModelProviderName = OPEN_AI_PROVIDER_NAME;
 
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct OpenAiSettings {
+    /// Defaults to [`openai::OPEN_AI_API_URL`] when empty.
     pub api_url: String,
-    pub api_key: String,
+    pub api_key: SecretString,
+    /// Sent as the `OpenAI-Organization` header.
+    pub organization_id: Option<String>,
+    /// Headers sent with every request, e.g. for a proxy or gateway.
+    pub extra_headers: BTreeMap<String, String>,
+    /// Models offered in addition to the built-in ones, or overriding them.
+    pub available_models: Vec<AvailableModel>,
+    /// Talks to an Azure OpenAI resource at `api_url` instead of OpenAI.
+    pub azure: Option<AzureSettings>,
+    /// The API used by model id. Models without an entry use Chat Completions.
+    pub model_apis: BTreeMap<String, OpenAiApi>,
+    /// Requests whole Chat Completions instead of streaming them, e.g. for
+    /// models or proxies that don't support server-sent events.
+    pub disable_streaming: bool,
 }
+
+/// The API a model is served through.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OpenAiApi {
+    #[default]
+    ChatCompletions,
+    /// The Responses API, which streams reasoning summaries and returns
+    /// encrypted reasoning.
+    Responses,
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct AzureSettings {
+    /// Defaults to [`openai::AZURE_API_VERSION`] when empty.
+    pub api_version: String,
+    pub auth: openai::AzureAuth,
+    /// Deployment names by model id. Models without an entry are expected to
+    /// be deployed under their id.
+    pub deployments: BTreeMap<String, String>,
+}
+
+impl AzureSettings {
+    pub fn api_version(&self) -> &str {
+        if self.api_version.is_empty() {
+            openai::AZURE_API_VERSION
+        } else {
+            &self.api_version
+        }
+    }
+
+    pub fn deployment<'a>(&'a self, model_id: &'a str) -> &'a str {
+        self.deployments
+            .get(model_id)
+            .map_or(model_id, String::as_str)
+    }
+}
+
+impl OpenAiSettings {
+    pub fn api_url(&self) -> &str {
+        if self.api_url.is_empty() {
+            openai::OPEN_AI_API_URL
+        } else {
+            &self.api_url
+        }
+    }
+
+    pub fn api(&self, model_id: &str) -> OpenAiApi {
+        self.model_apis.get(model_id).copied().unwrap_or_default()
+    }
+
+    /// Returns `extra_headers` along with the organization header, if any.
+    pub fn headers(&self) -> BTreeMap<String, String> {
+        let mut headers = self.extra_headers.clone();
+        if let Some(organization_id) = &self.organization_id {
+            headers.insert("OpenAI-Organization".into(), organization_id.clone());
+        }
+        headers
+    }
+
+    /// Returns `settings`, or the globally registered settings without them.
+    pub(crate) fn resolve(settings: &Option<Arc<Self>>) -> Arc<Self> {
+        settings
+            .clone()
+            .or_else(|| global_registry::get!(OpenAiSettings).ok())
+            .unwrap_or_default()
+    }
+}
+
+const OPENAI_API_KEY_VAR: &str = "OPENAI_API_KEY";
+const AZURE_OPENAI_API_KEY_VAR: &str = "AZURE_OPENAI_API_KEY";
+
 pub struct OpenAiLanguageModelProvider {
     http_client: Arc<dyn HttpClient>,
+    settings: Option<Arc<OpenAiSettings>>,
+    profile: Arc<OpenAiCompatibleProfile>,
+    credentials: Arc<dyn CredentialProvider>,
+    azure_credentials: Arc<dyn CredentialProvider>,
+    request_limiter: RateLimiter,
 }
 
 impl OpenAiLanguageModelProvider {
-    pub fn new(client: Arc<dyn HttpClient>) -> Self {
+    /// Creates a provider for the endpoint described by `settings`, so several
+    /// OpenAI-compatible endpoints can be used side by side.
+    ///
+    /// Without settings, the [`OpenAiSettings`] registered in `global_registry`
+    /// are looked up on every request. When no API key is configured, it is
+    /// read from `OPENAI_API_KEY`, or `AZURE_OPENAI_API_KEY` for Azure.
+    pub fn new(client: Arc<dyn HttpClient>, settings: Option<OpenAiSettings>) -> Self {
         Self {
             http_client: client,
+            settings: settings.map(Arc::new),
+            profile: Arc::new(OpenAiCompatibleProfile::openai()),
+            credentials: Arc::new(EnvCredentialProvider::new(OPENAI_API_KEY_VAR)),
+            azure_credentials: Arc::new(EnvCredentialProvider::new(AZURE_OPENAI_API_KEY_VAR)),
+            request_limiter: RateLimiter::default(),
             // state: State::new(),
         }
     }
+
+    /// Replaces where the API key comes from when the settings have none.
+    pub fn with_credentials(mut self, credentials: Arc<dyn CredentialProvider>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Replaces where the API key comes from when the settings have none and
+    /// target Azure.
+    pub fn with_azure_credentials(mut self, credentials: Arc<dyn CredentialProvider>) -> Self {
+        self.azure_credentials = credentials;
+        self
+    }
+
+    /// Replaces the limits shared by every model this provider creates.
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.request_limiter = RateLimiter::new(limits);
+        self
+    }
     pub fn create_language_model(&self, model: openai::Model) -> Arc<dyn LanguageModel> {
         Arc::new(OpenAiLanguageModel {
             id: model.id().to_string().into(),
             model,
             http_client: self.http_client.clone(),
+            settings: self.settings.clone(),
+            profile: self.profile.clone(),
+            credentials: self.credentials.clone(),
+            azure_credentials: self.azure_credentials.clone(),
+            request_limiter: self.request_limiter.clone(),
             // state: self.state.clone(),
         })
     }
@@ -47,18 +177,513 @@ impl LanguageModelProvider for OpenAiLanguageModelProvider {
     }
 
     fn default_model(&self) -> Option<Arc<dyn LanguageModel>> {
-        todo!()
+        Some(self.create_language_model(openai::Model::default()))
     }
 
     fn provided_models(&self) -> Vec<Arc<dyn LanguageModel>> {
-        todo!()
+        let mut models = BTreeMap::default();
+
+        for model in openai::Model::iter() {
+            if !matches!(model, openai::Model::Custom { .. }) {
+                models.insert(model.id().to_string(), model);
+            }
+        }
+
+        // Models from the settings replace built-in models with the same name.
+        for model in &OpenAiSettings::resolve(&self.settings).available_models {
+            models.insert(
+                model.name.clone(),
+                openai::Model::Custom {
+                    name: model.name.clone(),
+                    display_name: model.display_name.clone(),
+                    max_tokens: model.max_tokens,
+                    max_output_tokens: model.max_output_tokens,
+                    max_completion_tokens: model.max_completion_tokens,
+                },
+            );
+        }
+
+        models
+            .into_values()
+            .map(|model| self.create_language_model(model))
+            .collect()
     }
+
     fn default_fast_model(&self) -> Option<Arc<dyn LanguageModel>> {
-        todo!()
+        Some(self.create_language_model(openai::Model::default_fast()))
+    }
+
+    async fn authenticate(&self) -> anyhow::Result<()> {
+        let settings = OpenAiSettings::resolve(&self.settings);
+        let credentials = if settings.azure.is_some() {
+            &self.azure_credentials
+        } else {
+            &self.credentials
+        };
+        resolve_api_key(&settings.api_key, credentials.as_ref(), PROVIDER_NAME).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::{AsyncBody, BlockedHttpClient, FakeHttpClient, Response};
+    use crate::model::{
+        FakeCredentialProvider, GlobalRegistration, LanguageModelCompletionError, LanguageModelId,
+        LanguageModelRequest, LanguageModelRequestMessage, LanguageModelToolResult,
+        LanguageModelToolResultContent, MessageContent, Role, StopReason, ThinkingConfig,
+        ThinkingSupport, TokenUsage,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_provided_models_include_available_models() {
+        let settings = OpenAiSettings {
+            available_models: vec![
+                AvailableModel {
+                    name: "gpt-4o".into(),
+                    display_name: Some("GPT-4o (proxy)".into()),
+                    max_tokens: 64_000,
+                    max_output_tokens: None,
+                    max_completion_tokens: None,
+                },
+                AvailableModel {
+                    name: "qwen3-32b".into(),
+                    display_name: None,
+                    max_tokens: 32_768,
+                    max_output_tokens: Some(8_192),
+                    max_completion_tokens: None,
+                },
+            ],
+            ..Default::default()
+        };
+        let provider =
+            OpenAiLanguageModelProvider::new(Arc::new(BlockedHttpClient::new()), Some(settings));
+
+        let models = provider.provided_models();
+        let builtin_count = openai::Model::iter()
+            .filter(|model| !matches!(model, openai::Model::Custom { .. }))
+            .count();
+        assert_eq!(models.len(), builtin_count + 1);
+
+        let find = |id: &str| {
+            models
+                .iter()
+                .find(|model| model.id() == LanguageModelId::from(id.to_string()))
+                .unwrap()
+        };
+        assert_eq!(find("gpt-4o").max_token_count(), 64_000);
+        assert_eq!(find("qwen3-32b").max_output_tokens(), Some(8_192));
+        assert_eq!(
+            provider.default_fast_model().unwrap().id(),
+            LanguageModelId::from("gpt-4.1-mini".to_string())
+        );
+    }
+
+    const AZURE_RESPONSE: &str = r#"data: {"choices":[],"created":0,"id":"","model":"","object":"","prompt_filter_results":[{"prompt_index":0,"content_filter_results":{}}]}
+
+data: {"choices":[{"delta":{"content":"Once upon"},"finish_reason":null,"index":0}],"created":1,"id":"chatcmpl-1","model":"gpt-4o-2024-11-20","object":"chat.completion.chunk"}
+
+data: {"choices":[{"content_filter_results":{"violence":{"filtered":true,"severity":"high"}},"finish_reason":"content_filter","index":0}],"created":1,"id":"chatcmpl-1","model":"gpt-4o-2024-11-20","object":"chat.completion.chunk"}
+
+data: [DONE]
+
+"#;
+
+    const AZURE_FILTERED_PROMPT: &str = r#"{"error":{"message":"The response was filtered due to the prompt triggering Azure OpenAI's content management policy.","type":null,"param":"prompt","code":"content_filter","status":400,"innererror":{"code":"ResponsibleAIPolicyViolation"}}}"#;
+
+    #[tokio::test]
+    async fn test_azure_deployment() {
+        let (http_client, sent_requests) =
+            FakeHttpClient::replay_responses([(200, AZURE_RESPONSE), (400, AZURE_FILTERED_PROMPT)]);
+        let provider = OpenAiLanguageModelProvider::new(
+            http_client,
+            Some(OpenAiSettings {
+                api_url: "https://example.openai.azure.com".into(),
+                api_key: "azure-key".into(),
+                azure: Some(AzureSettings {
+                    deployments: BTreeMap::from_iter([("gpt-4o".into(), "prod-4o".into())]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        );
+        let model = provider.create_language_model(openai::Model::FourOmni);
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("Tell me a story".into())],
+                cache: false,
+            }],
+            ..Default::default()
+        };
+
+        let response = model.complete(request.clone()).await.unwrap();
+        assert_eq!(response.text(), "Once upon");
+        assert_eq!(response.stop_reason, StopReason::Refusal);
+
+        let error = model.complete(request).await.unwrap_err();
+        assert!(matches!(
+            error,
+            LanguageModelCompletionError::ContentFiltered { .. }
+        ));
+
+        let sent_request = &sent_requests.lock()[0];
+        assert_eq!(
+            sent_request.parts.uri.to_string(),
+            "https://example.openai.azure.com/openai/deployments/prod-4o/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(sent_request.parts.headers["api-key"], "azure-key");
+        assert!(sent_request.parts.headers.get("authorization").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_azure_url_is_encoded() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(AZURE_RESPONSE);
+        let provider = OpenAiLanguageModelProvider::new(
+            http_client,
+            Some(OpenAiSettings {
+                api_url: "https://example.openai.azure.com".into(),
+                api_key: "azure-key".into(),
+                azure: Some(AzureSettings {
+                    api_version: "2024-10-21&x=1".into(),
+                    deployments: BTreeMap::from_iter([("gpt-4o".into(), "prod/4o eu".into())]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        );
+        let model = provider.create_language_model(openai::Model::FourOmni);
+        model
+            .complete(LanguageModelRequest::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sent_requests.lock()[0].parts.uri.to_string(),
+            "https://example.openai.azure.com/openai/deployments/prod%2F4o%20eu/chat/completions?api-version=2024-10-21%26x%3D1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_global_azure_settings_use_azure_credentials() {
+        let _settings = GlobalRegistration::new(OpenAiSettings {
+            api_url: "https://global.openai.azure.com".into(),
+            azure: Some(AzureSettings::default()),
+            ..Default::default()
+        });
+        let (http_client, sent_requests) = FakeHttpClient::replay(AZURE_RESPONSE);
+        let provider = OpenAiLanguageModelProvider::new(http_client, None)
+            .with_credentials(Arc::new(FakeCredentialProvider("sk-openai".into())))
+            .with_azure_credentials(Arc::new(FakeCredentialProvider("azure-key".into())));
+        provider.authenticate().await.unwrap();
+
+        let model = provider.create_language_model(openai::Model::FourOmni);
+        model
+            .complete(LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Tell me a story".into())],
+                    cache: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let sent_request = sent_requests.lock().pop().unwrap();
+        assert!(
+            sent_request
+                .parts
+                .uri
+                .to_string()
+                .starts_with("https://global.openai.azure.com/openai/deployments/gpt-4o/")
+        );
+        assert_eq!(sent_request.parts.headers["api-key"], "azure-key");
+        assert!(sent_request.parts.headers.get("authorization").is_none());
+    }
+
+    const RESPONSES_STREAM: &str = r#"event: response.created
+data: {"type":"response.created","sequence_number":0,"response":{"id":"resp_1","status":"in_progress","output":[]}}
+
+event: response.reasoning_summary_text.delta
+data: {"type":"response.reasoning_summary_text.delta","sequence_number":1,"item_id":"rs_1","output_index":0,"summary_index":0,"delta":"Checking the weather."}
+
+event: response.reasoning_summary_text.delta
+data: {"type":"response.reasoning_summary_text.delta","sequence_number":2,"item_id":"rs_1","output_index":0,"summary_index":1,"delta":"Calling the tool."}
+
+event: response.output_item.done
+data: {"type":"response.output_item.done","sequence_number":3,"output_index":0,"item":{"id":"rs_1","type":"reasoning","summary":[],"encrypted_content":"gAAAAB-secret"}}
+
+event: response.output_item.done
+data: {"type":"response.output_item.done","sequence_number":4,"output_index":1,"item":{"id":"fc_1","type":"function_call","status":"completed","call_id":"call_1","name":"weather","arguments":"{\"city\":\"Paris\"}"}}
+
+event: response.completed
+data: {"type":"response.completed","sequence_number":5,"response":{"id":"resp_1","status":"completed","output":[],"usage":{"input_tokens":120,"input_tokens_details":{"cached_tokens":100},"output_tokens":40,"output_tokens_details":{"reasoning_tokens":32},"total_tokens":160}}}
+
+"#;
+
+    #[tokio::test]
+    async fn test_responses_api() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(RESPONSES_STREAM);
+        let provider = OpenAiLanguageModelProvider::new(
+            http_client,
+            Some(OpenAiSettings {
+                api_key: "sk-test".into(),
+                model_apis: BTreeMap::from_iter([("o3".into(), OpenAiApi::Responses)]),
+                ..Default::default()
+            }),
+        );
+        let model = provider.create_language_model(openai::Model::O3);
+        let mut request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("Weather in Paris?".into())],
+                cache: false,
+            }],
+            thinking_allowed: true,
+            thinking: Some(ThinkingConfig::Budget {
+                budget_tokens: 32_000,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            model.thinking_support(),
+            ThinkingSupport {
+                effort: true,
+                budget: false,
+            }
+        );
+
+        let response = model.complete(request.clone()).await.unwrap();
+        assert_eq!(response.message_id.as_deref(), Some("resp_1"));
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        assert_eq!(response.usage.input_tokens, 20);
+        assert_eq!(response.usage.cache_read_input_tokens, 100);
+        assert_eq!(response.usage.output_tokens, 40);
+        assert_eq!(
+            response.message.content[0],
+            MessageContent::Thinking {
+                text: "Checking the weather.\n\nCalling the tool.".into(),
+                signature: None,
+            }
+        );
+        let tool_use = response.tool_uses().next().unwrap();
+        assert_eq!(tool_use.id.to_string(), "call_1");
+        assert_eq!(tool_use.input, serde_json::json!({"city": "Paris"}));
+
+        // The encrypted reasoning is passed back with the tool result.
+        request.messages.push(response.message.clone());
+        request.messages.push(LanguageModelRequestMessage {
+            role: Role::User,
+            content: vec![MessageContent::ToolResult(LanguageModelToolResult {
+                tool_use_id: tool_use.id.clone(),
+                tool_name: "weather".into(),
+                is_error: false,
+                content: LanguageModelToolResultContent::Text("Sunny".into()),
+                output: None,
+            })],
+            cache: false,
+        });
+        model.complete(request).await.unwrap();
+
+        let sent_requests = sent_requests.lock();
+        assert_eq!(
+            sent_requests[0].parts.uri.to_string(),
+            "https://api.openai.com/v1/responses"
+        );
+        let body = sent_requests[0].json();
+        assert_eq!(body["store"], false);
+        assert_eq!(body["include"][0], "reasoning.encrypted_content");
+        assert_eq!(body["reasoning"]["summary"], "auto");
+        assert_eq!(body["reasoning"]["effort"], "high");
+        assert!(body.get("temperature").is_none());
+
+        let input = &sent_requests[1].json()["input"];
+        assert_eq!(input[1]["type"], "reasoning");
+        assert_eq!(input[1]["id"], "rs_1");
+        assert_eq!(input[1]["encrypted_content"], "gAAAAB-secret");
+        assert_eq!(input[2]["type"], "function_call");
+        assert_eq!(input[2]["call_id"], "call_1");
+        assert_eq!(input[3]["type"], "function_call_output");
+        assert_eq!(input[3]["output"], "Sunny");
+    }
+
+    #[tokio::test]
+    async fn test_error_responses() {
+        let http_client = FakeHttpClient::create(|request| async move {
+            if request
+                .uri()
+                .path()
+                .ends_with("/rate-limited/chat/completions")
+            {
+                Ok(Response::builder()
+                    .status(429)
+                    .header("x-ratelimit-limit-requests", "500")
+                    .header("x-ratelimit-remaining-requests", "0")
+                    .header("x-ratelimit-reset-requests", "1.5s")
+                    .body(AsyncBody::from(
+                        r#"{"error":{"message":"Rate limit reached","type":"requests","code":"rate_limit_exceeded"}}"#,
+                    ))?)
+            } else {
+                Ok(Response::builder().status(401).body(AsyncBody::from(
+                    r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error","code":"invalid_api_key"}}"#,
+                ))?)
+            }
+        });
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("Hello".into())],
+                cache: false,
+            }],
+            ..Default::default()
+        };
+        let complete = |api_url: &str| {
+            let provider = OpenAiLanguageModelProvider::new(
+                http_client.clone(),
+                Some(OpenAiSettings {
+                    api_url: api_url.into(),
+                    api_key: "sk-test".into(),
+                    ..Default::default()
+                }),
+            );
+            let model = provider.create_language_model(openai::Model::FourOmni);
+            let request = request.clone();
+            async move { model.complete(request).await.unwrap_err() }
+        };
+
+        let error = complete("https://example.com/rate-limited").await;
+        assert!(matches!(
+            error,
+            LanguageModelCompletionError::RateLimitExceeded {
+                retry_after: Some(retry_after),
+                ..
+            } if retry_after == Duration::from_millis(1_500)
+        ));
+
+        let error = complete("https://example.com/v1").await;
+        assert!(matches!(
+            error,
+            LanguageModelCompletionError::AuthenticationError { .. }
+        ));
+    }
+
+    const TRUNCATED_RESPONSE: &str = r#"data: {"id":"chatcmpl-1","model":"o4-mini","choices":[{"index":0,"delta":{"role":"assistant","content":"Once upon"},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-1","model":"o4-mini","choices":[{"index":0,"delta":{},"finish_reason":"length"}]}
+
+data: {"id":"chatcmpl-1","model":"o4-mini","choices":[],"usage":{"prompt_tokens":1200,"completion_tokens":300,"total_tokens":1500,"prompt_tokens_details":{"cached_tokens":1024},"completion_tokens_details":{"reasoning_tokens":256}}}
+
+data: [DONE]
+
+"#;
+
+    const FUNCTION_CALL_RESPONSE: &str = r#"data: {"id":"chatcmpl-2","model":"o4-mini","choices":[{"index":0,"delta":{"role":"assistant","content":null,"function_call":{"name":"weather","arguments":""}},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-2","model":"o4-mini","choices":[{"index":0,"delta":{"function_call":{"arguments":"{\"city\":\"Paris\"}"}},"finish_reason":null}]}
+
+data: {"id":"chatcmpl-2","model":"o4-mini","choices":[{"index":0,"delta":{},"finish_reason":"function_call"}]}
+
+data: [DONE]
+
+"#;
+
+    #[tokio::test]
+    async fn test_finish_reasons_and_usage() {
+        let (http_client, sent_requests) = FakeHttpClient::replay_responses([
+            (200, TRUNCATED_RESPONSE),
+            (200, FUNCTION_CALL_RESPONSE),
+        ]);
+        let provider = OpenAiLanguageModelProvider::new(
+            http_client,
+            Some(OpenAiSettings {
+                api_key: "sk-test".into(),
+                ..Default::default()
+            }),
+        );
+        let model = provider.create_language_model(openai::Model::O4Mini);
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text("Tell me a story".into())],
+                cache: false,
+            }],
+            ..Default::default()
+        };
+
+        let response = model.complete(request.clone()).await.unwrap();
+        assert_eq!(response.stop_reason, StopReason::MaxTokens);
+        assert_eq!(
+            response.usage,
+            TokenUsage {
+                input_tokens: 176,
+                output_tokens: 300,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 1024,
+                reasoning_tokens: 256,
+            }
+        );
+        assert_eq!(
+            sent_requests.lock()[0].json()["stream_options"]["include_usage"],
+            true
+        );
+
+        let response = model.complete(request).await.unwrap();
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        let tool_use = response.tool_uses().next().unwrap();
+        assert!(!tool_use.id.to_string().is_empty());
+        assert_eq!(tool_use.name.as_ref(), "weather");
+        assert_eq!(tool_use.input, serde_json::json!({"city": "Paris"}));
     }
 
-    // async fn authenticate(&self) -> anyhow::Result<()> {
-    //     self.state.authenticate().await?;
-    //     Ok(())
-    // }
+    const CHAT_COMPLETION: &str = r#"{"id":"chatcmpl-3","object":"chat.completion","created":1,"model":"o1-preview-2024-09-12","choices":[{"index":0,"message":{"role":"assistant","content":null,"tool_calls":[{"id":"call_1","type":"function","function":{"name":"weather","arguments":"{\"city\":\"Paris\"}"}},{"id":"call_2","type":"function","function":{"name":"weather","arguments":"{\"city\":\"Rome\"}"}}]},"finish_reason":"tool_calls"}],"usage":{"prompt_tokens":40,"completion_tokens":90,"total_tokens":130,"completion_tokens_details":{"reasoning_tokens":64}}}"#;
+
+    #[tokio::test]
+    async fn test_non_streaming_completion() {
+        let (http_client, sent_requests) = FakeHttpClient::replay(CHAT_COMPLETION);
+        let provider = OpenAiLanguageModelProvider::new(
+            http_client,
+            Some(OpenAiSettings {
+                api_key: "sk-test".into(),
+                disable_streaming: true,
+                ..Default::default()
+            }),
+        );
+        let model = provider.create_language_model(openai::Model::Custom {
+            name: "o1-preview".into(),
+            display_name: None,
+            max_tokens: 128_000,
+            max_output_tokens: None,
+            max_completion_tokens: None,
+        });
+        let response = model
+            .complete(LanguageModelRequest {
+                messages: vec![LanguageModelRequestMessage {
+                    role: Role::User,
+                    content: vec![MessageContent::Text("Weather in Paris and Rome?".into())],
+                    cache: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(sent_requests.lock()[0].json()["stream"], false);
+        assert_eq!(response.stop_reason, StopReason::ToolUse);
+        assert_eq!(response.usage.output_tokens, 90);
+        assert_eq!(response.usage.reasoning_tokens, 64);
+        let tool_uses = response
+            .tool_uses()
+            .map(|tool_use| (tool_use.id.to_string(), tool_use.input.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tool_uses,
+            vec![
+                ("call_1".into(), serde_json::json!({"city": "Paris"})),
+                ("call_2".into(), serde_json::json!({"city": "Rome"})),
+            ]
+        );
+    }
 }
@@ -0,0 +1,205 @@
+use std::pin::Pin;
+
+use anyhow::anyhow;
+use futures::StreamExt;
+use futures_core::Stream;
+
+use crate::model::{
+    LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelProviderName,
+    LanguageModelToolUse, StopReason, TokenUsage,
+};
+use crate::openai::responses::{self, InputItem, OutputItem, StreamEvent};
+use crate::openai::{ApiError, OpenAiError};
+
+/// Maps the events of the Responses API.
+///
+/// Reasoning summaries become [`LanguageModelCompletionEvent::Thinking`], and
+/// encrypted reasoning becomes [`LanguageModelCompletionEvent::RedactedThinking`]
+/// holding the whole reasoning item, so it can be passed back as input.
+pub struct OpenAiResponsesEventMapper {
+    provider: LanguageModelProviderName,
+    last_summary: Option<(String, usize)>,
+    has_tool_calls: bool,
+    refused: bool,
+}
+
+impl OpenAiResponsesEventMapper {
+    pub fn new(provider: LanguageModelProviderName) -> Self {
+        Self {
+            provider,
+            last_summary: None,
+            has_tool_calls: false,
+            refused: false,
+        }
+    }
+
+    pub fn map_stream(
+        mut self,
+        events: Pin<Box<dyn Send + Stream<Item = Result<StreamEvent, OpenAiError>>>>,
+    ) -> impl Stream<Item = Result<LanguageModelCompletionEvent, LanguageModelCompletionError>>
+    {
+        events.flat_map(move |event| {
+            futures::stream::iter(match event {
+                Ok(event) => self.map_event(event),
+                Err(error) => vec![Err(error.into_completion_error(self.provider.clone()))],
+            })
+        })
+    }
+
+    pub fn map_event(
+        &mut self,
+        event: StreamEvent,
+    ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+        match event {
+            StreamEvent::Created { response } => {
+                vec![Ok(LanguageModelCompletionEvent::StartMessage {
+                    message_id: response.id,
+                })]
+            }
+            StreamEvent::OutputTextDelta { delta, .. } => {
+                vec![Ok(LanguageModelCompletionEvent::Text(delta))]
+            }
+            StreamEvent::RefusalDelta { delta, .. } => {
+                self.refused = true;
+                vec![Ok(LanguageModelCompletionEvent::Text(delta))]
+            }
+            StreamEvent::ReasoningSummaryTextDelta {
+                item_id,
+                summary_index,
+                mut delta,
+                ..
+            } => {
+                // Separate the parts of a summary like paragraphs.
+                let summary = (item_id, summary_index);
+                if self
+                    .last_summary
+                    .as_ref()
+                    .is_some_and(|last_summary| *last_summary != summary)
+                {
+                    delta.insert_str(0, "\n\n");
+                }
+                self.last_summary = Some(summary);
+                vec![Ok(LanguageModelCompletionEvent::Thinking {
+                    text: delta,
+                    signature: None,
+                })]
+            }
+            StreamEvent::OutputItemDone { item, .. } => self.map_output_item(item),
+            StreamEvent::Completed { response } => {
+                let stop_reason = if self.has_tool_calls {
+                    StopReason::ToolUse
+                } else if self.refused {
+                    StopReason::Refusal
+                } else {
+                    StopReason::EndTurn
+                };
+                Self::finish(response, stop_reason)
+            }
+            StreamEvent::Incomplete { response } => {
+                let stop_reason = match response
+                    .incomplete_details
+                    .as_ref()
+                    .map(|details| details.reason.as_str())
+                {
+                    Some("max_output_tokens") => StopReason::MaxTokens,
+                    Some("content_filter") => StopReason::Refusal,
+                    _ => StopReason::EndTurn,
+                };
+                Self::finish(response, stop_reason)
+            }
+            StreamEvent::Failed { response } => {
+                let error = response.error.unwrap_or_else(|| ApiError {
+                    message: "the response failed".to_string(),
+                    ..Default::default()
+                });
+                vec![Err(error.into_completion_error(None, self.provider.clone()))]
+            }
+            StreamEvent::Error { code, message } => {
+                let error = ApiError {
+                    message,
+                    code,
+                    ..Default::default()
+                };
+                vec![Err(error.into_completion_error(None, self.provider.clone()))]
+            }
+            StreamEvent::OutputItemAdded { .. }
+            | StreamEvent::FunctionCallArgumentsDelta { .. }
+            | StreamEvent::FunctionCallArgumentsDone { .. }
+            | StreamEvent::Unknown => Vec::new(),
+        }
+    }
+
+    fn map_output_item(
+        &mut self,
+        item: OutputItem,
+    ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+        match item {
+            OutputItem::FunctionCall {
+                call_id,
+                name,
+                arguments,
+                ..
+            } => {
+                self.has_tool_calls = true;
+                let event = match serde_json::from_str(&arguments) {
+                    Ok(input) => LanguageModelCompletionEvent::ToolUse(LanguageModelToolUse {
+                        id: call_id.into(),
+                        name: name.into(),
+                        is_input_complete: true,
+                        input,
+                        raw_input: arguments,
+                    }),
+                    Err(error) => LanguageModelCompletionEvent::ToolUseJsonParseError {
+                        id: call_id.into(),
+                        tool_name: name.into(),
+                        raw_input: arguments.into(),
+                        json_parse_error: error.to_string(),
+                    },
+                };
+                vec![Ok(event)]
+            }
+            OutputItem::Reasoning {
+                id,
+                encrypted_content: Some(encrypted_content),
+                ..
+            } => {
+                // The summary was already streamed, and isn't needed as input.
+                let item = InputItem::Reasoning {
+                    id,
+                    summary: Vec::new(),
+                    encrypted_content: Some(encrypted_content),
+                };
+                match serde_json::to_string(&item) {
+                    Ok(data) => vec![Ok(LanguageModelCompletionEvent::RedactedThinking { data })],
+                    Err(error) => vec![Err(LanguageModelCompletionError::from(anyhow!(error)))],
+                }
+            }
+            OutputItem::Message { .. } | OutputItem::Reasoning { .. } | OutputItem::Other => {
+                Vec::new()
+            }
+        }
+    }
+
+    fn finish(
+        response: responses::Response,
+        stop_reason: StopReason,
+    ) -> Vec<Result<LanguageModelCompletionEvent, LanguageModelCompletionError>> {
+        let mut events = Vec::new();
+        if let Some(usage) = response.usage {
+            let cached_tokens = usage
+                .input_tokens_details
+                .map_or(0, |details| details.cached_tokens);
+            events.push(Ok(LanguageModelCompletionEvent::UsageUpdate(TokenUsage {
+                input_tokens: usage.input_tokens.saturating_sub(cached_tokens),
+                output_tokens: usage.output_tokens,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: cached_tokens,
+                reasoning_tokens: usage
+                    .output_tokens_details
+                    .map_or(0, |details| details.reasoning_tokens),
+            })));
+        }
+        events.push(Ok(LanguageModelCompletionEvent::Stop(stop_reason)));
+        events
+    }
+}
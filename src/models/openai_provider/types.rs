@@ -1,6 +1,30 @@
+use uuid::Uuid;
+
+use crate::openai::FunctionChunk;
+
 #[derive(Default)]
 pub struct RawToolCall {
     pub id: String,
     pub name: String,
     pub arguments: String,
-}
\ No newline at end of file
+}
+
+impl RawToolCall {
+    /// Returns the id of the call, making one up if the server didn't send
+    /// one, as for the deprecated `function_call`.
+    pub fn id(&mut self) -> &str {
+        if self.id.is_empty() {
+            self.id = format!("call_{}", Uuid::new_v4().simple());
+        }
+        &self.id
+    }
+
+    pub fn push_function_chunk(&mut self, function: &FunctionChunk) {
+        if let Some(name) = function.name.clone() {
+            self.name = name;
+        }
+        if let Some(arguments) = function.arguments.as_deref() {
+            self.arguments.push_str(arguments);
+        }
+    }
+}
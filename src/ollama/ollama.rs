@@ -0,0 +1,447 @@
+use anyhow::{Context as _, Result, anyhow};
+use futures::{AsyncBufReadExt, AsyncReadExt, StreamExt, io::BufReader, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::common::SecretString;
+use crate::http_client::{
+    AsyncBody, HttpClient, Method, Request as HttpRequest, StatusCode, sensitive_header_value,
+};
+use crate::model::{LanguageModelCompletionError, OLLAMA_PROVIDER_NAME};
+
+pub const OLLAMA_API_URL: &str = "http://localhost:11434";
+
+/// The context window assumed when a model doesn't report one.
+const DEFAULT_CONTEXT_LENGTH: u64 = 4096;
+
+/// Ollama allocates memory for the whole context window, so large windows are
+/// capped unless configured explicitly.
+const MAXIMUM_CONTEXT_LENGTH: u64 = 16384;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Model {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub max_tokens: u64,
+    pub keep_alive: Option<KeepAlive>,
+    pub supports_tools: Option<bool>,
+    pub supports_vision: Option<bool>,
+    pub supports_thinking: Option<bool>,
+}
+
+impl Model {
+    pub fn new(
+        name: &str,
+        display_name: Option<&str>,
+        max_tokens: Option<u64>,
+        supports_tools: Option<bool>,
+        supports_vision: Option<bool>,
+        supports_thinking: Option<bool>,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            display_name: display_name
+                .map(ToString::to_string)
+                .or_else(|| name.strip_suffix(":latest").map(ToString::to_string)),
+            max_tokens: max_tokens.unwrap_or(DEFAULT_CONTEXT_LENGTH),
+            keep_alive: Some(KeepAlive::indefinite()),
+            supports_tools,
+            supports_vision,
+            supports_thinking,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.name
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_ref().unwrap_or(&self.name)
+    }
+
+    pub fn max_token_count(&self) -> u64 {
+        self.max_tokens
+    }
+}
+
+/// Picks the context window to request for a model that reports
+/// `context_length` tokens.
+pub fn clamp_context_length(context_length: Option<u64>) -> u64 {
+    context_length
+        .unwrap_or(DEFAULT_CONTEXT_LENGTH)
+        .clamp(1, MAXIMUM_CONTEXT_LENGTH)
+}
+
+/// How long Ollama keeps a model loaded after a request.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    /// Keep the model alive for N seconds, or forever when negative.
+    Seconds(isize),
+    /// A duration like "5m" or "1h".
+    Duration(String),
+}
+
+impl KeepAlive {
+    pub fn indefinite() -> Self {
+        Self::Seconds(-1)
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self::indefinite()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum ChatMessage {
+    Assistant {
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tool_calls: Option<Vec<OllamaToolCall>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        images: Option<Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thinking: Option<String>,
+    },
+    User {
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        images: Option<Vec<String>>,
+    },
+    System {
+        content: String,
+    },
+    Tool {
+        tool_name: String,
+        content: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OllamaToolCall {
+    pub function: OllamaFunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OllamaFunctionCall {
+    pub name: String,
+    pub arguments: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OllamaTool {
+    Function { function: OllamaFunctionTool },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OllamaFunctionTool {
+    pub name: String,
+    pub description: Option<String>,
+    pub parameters: Option<Value>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<KeepAlive>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<ChatOptions>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<OllamaTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub think: Option<bool>,
+}
+
+// https://github.com/ollama/ollama/blob/main/docs/modelfile.md#valid-parameters-and-values
+#[derive(Serialize, Default, Debug)]
+pub struct ChatOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<isize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+}
+
+/// One line of the NDJSON stream returned by `/api/chat`.
+#[derive(Deserialize, Debug)]
+pub struct ChatResponseDelta {
+    pub model: String,
+    pub created_at: String,
+    pub message: ChatMessage,
+    pub done_reason: Option<String>,
+    pub done: bool,
+    pub prompt_eval_count: Option<u64>,
+    pub eval_count: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LocalModelsResponse {
+    pub models: Vec<LocalModelListing>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LocalModelListing {
+    pub name: String,
+    pub modified_at: String,
+    pub size: u64,
+    pub digest: String,
+    pub details: ModelDetails,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModelDetails {
+    pub format: String,
+    pub family: String,
+    pub families: Option<Vec<String>>,
+    pub parameter_size: String,
+    pub quantization_level: String,
+}
+
+/// The response of `/api/show`.
+#[derive(Deserialize, Debug, Default)]
+pub struct ModelShow {
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    #[serde(default)]
+    pub model_info: serde_json::Map<String, Value>,
+}
+
+impl ModelShow {
+    pub fn supports_tools(&self) -> bool {
+        // .contains expects &String, which would require an additional allocation
+        self.capabilities.iter().any(|v| v == "tools")
+    }
+
+    pub fn supports_vision(&self) -> bool {
+        self.capabilities.iter().any(|v| v == "vision")
+    }
+
+    pub fn supports_thinking(&self) -> bool {
+        self.capabilities.iter().any(|v| v == "thinking")
+    }
+
+    /// The context window the model was trained with, reported under
+    /// `<architecture>.context_length`.
+    pub fn context_length(&self) -> Option<u64> {
+        let architecture = self.model_info.get("general.architecture")?.as_str()?;
+        self.model_info
+            .get(&format!("{architecture}.context_length"))?
+            .as_u64()
+    }
+}
+
+/// An unsuccessful response from the Ollama API.
+#[derive(Debug, Error)]
+#[error("Ollama API error ({status_code}): {message}")]
+pub struct ApiError {
+    pub status_code: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    fn from_response(status_code: StatusCode, body: String) -> Self {
+        #[derive(Deserialize)]
+        struct ErrorResponse {
+            error: String,
+        }
+
+        let message = serde_json::from_str::<ErrorResponse>(&body)
+            .map(|response| response.error)
+            .unwrap_or(body);
+        Self {
+            status_code,
+            message,
+        }
+    }
+}
+
+impl From<ApiError> for LanguageModelCompletionError {
+    fn from(error: ApiError) -> Self {
+        let provider = OLLAMA_PROVIDER_NAME;
+        match error.status_code.as_u16() {
+            400 => Self::BadRequestFormat {
+                provider,
+                message: error.message,
+            },
+            401 => Self::AuthenticationError {
+                provider,
+                message: error.message,
+            },
+            // Ollama answers 404 when the model hasn't been pulled.
+            404 => Self::BadRequestFormat {
+                provider,
+                message: error.message,
+            },
+            429 => Self::RateLimitExceeded {
+                provider,
+                retry_after: None,
+            },
+            500 => Self::ApiInternalServerError {
+                provider,
+                message: error.message,
+            },
+            _ => Self::HttpResponseError {
+                provider,
+                status_code: error.status_code,
+                message: error.message,
+            },
+        }
+    }
+}
+
+/// Builds a request to the Ollama API. Local servers need no key; hosted ones
+/// expect it as a bearer token.
+fn build_request(
+    method: Method,
+    uri: String,
+    api_key: Option<&SecretString>,
+    body: AsyncBody,
+) -> Result<HttpRequest<AsyncBody>> {
+    let mut request = HttpRequest::builder()
+        .method(method)
+        .uri(uri)
+        .header("Content-Type", "application/json");
+    if let Some(api_key) = api_key.filter(|api_key| !api_key.is_empty()) {
+        request = request.header(
+            "Authorization",
+            sensitive_header_value(&format!("Bearer {}", api_key.expose()))?,
+        );
+    }
+    Ok(request.body(body)?)
+}
+
+async fn read_error(mut response: crate::http_client::Response<AsyncBody>) -> anyhow::Error {
+    let mut body = String::new();
+    match response.body_mut().read_to_string(&mut body).await {
+        Ok(_) => ApiError::from_response(response.status(), body).into(),
+        Err(error) => error.into(),
+    }
+}
+
+pub async fn stream_chat_completion(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: Option<&SecretString>,
+    request: ChatRequest,
+) -> Result<BoxStream<'static, Result<ChatResponseDelta>>> {
+    let uri = format!("{api_url}/api/chat");
+    let request = build_request(
+        Method::POST,
+        uri,
+        api_key,
+        AsyncBody::from(serde_json::to_string(&request)?),
+    )?;
+
+    let response = client.send(request).await?;
+    if response.status().is_success() {
+        let reader = BufReader::new(response.into_body());
+        Ok(reader
+            .lines()
+            .filter_map(|line| async move {
+                match line {
+                    Ok(line) if line.trim().is_empty() => None,
+                    Ok(line) => Some(
+                        serde_json::from_str(&line)
+                            .with_context(|| format!("Error parsing JSON: {line:?}")),
+                    ),
+                    Err(error) => Some(Err(anyhow!(error))),
+                }
+            })
+            .boxed())
+    } else {
+        Err(read_error(response).await)
+    }
+}
+
+/// Lists the models pulled into the local Ollama library (`/api/tags`).
+pub async fn get_models(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: Option<&SecretString>,
+) -> Result<Vec<LocalModelListing>> {
+    let uri = format!("{api_url}/api/tags");
+    let request = build_request(Method::GET, uri, api_key, AsyncBody::default())?;
+
+    let mut response = client.send(request).await?;
+    if !response.status().is_success() {
+        return Err(read_error(response).await);
+    }
+    let mut body = String::new();
+    response.body_mut().read_to_string(&mut body).await?;
+    let response: LocalModelsResponse =
+        serde_json::from_str(&body).context("Unable to parse Ollama tag listing")?;
+    Ok(response.models)
+}
+
+/// Fetches the details of a model (`/api/show`), including its capabilities
+/// and context length.
+pub async fn show_model(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: Option<&SecretString>,
+    model: &str,
+) -> Result<ModelShow> {
+    let uri = format!("{api_url}/api/show");
+    let request = build_request(
+        Method::POST,
+        uri,
+        api_key,
+        AsyncBody::from(serde_json::json!({ "model": model }).to_string()),
+    )?;
+
+    let mut response = client.send(request).await?;
+    if !response.status().is_success() {
+        return Err(read_error(response).await);
+    }
+    let mut body = String::new();
+    response.body_mut().read_to_string(&mut body).await?;
+    serde_json::from_str(&body).context("Unable to parse Ollama model details")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chat_response_deltas() {
+        let delta: ChatResponseDelta = serde_json::from_str(
+            r#"{"model":"qwen3","created_at":"2025-07-01T12:00:00Z","message":{"role":"assistant","content":"","thinking":"Hmm","tool_calls":[{"function":{"name":"weather","arguments":{"city":"Paris"}}}]},"done":false}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            delta.message,
+            ChatMessage::Assistant {
+                content: String::new(),
+                tool_calls: Some(vec![OllamaToolCall {
+                    function: OllamaFunctionCall {
+                        name: "weather".into(),
+                        arguments: serde_json::json!({ "city": "Paris" }),
+                    },
+                }]),
+                images: None,
+                thinking: Some("Hmm".into()),
+            }
+        );
+
+        let show: ModelShow = serde_json::from_str(
+            r#"{"capabilities":["completion","tools"],"model_info":{"general.architecture":"qwen3","qwen3.context_length":40960}}"#,
+        )
+        .unwrap();
+        assert!(show.supports_tools());
+        assert!(!show.supports_thinking());
+        assert_eq!(show.context_length(), Some(40960));
+    }
+}
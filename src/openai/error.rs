@@ -0,0 +1,283 @@
+use std::io;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+use crate::http_client::StatusCode;
+use crate::http_client::http;
+use crate::model::{LanguageModelCompletionError, LanguageModelProviderName};
+
+#[derive(Debug, Error)]
+pub enum OpenAiError {
+    /// Failed to serialize the HTTP request body to JSON
+    #[error("failed to serialize the request: {0}")]
+    SerializeRequest(serde_json::Error),
+
+    /// Failed to construct the HTTP request body
+    #[error("failed to build the request: {0}")]
+    BuildRequestBody(http::Error),
+
+    /// Failed to send the HTTP request
+    #[error("failed to send the request: {0}")]
+    HttpSend(anyhow::Error),
+
+    /// Failed to deserialize the response from JSON
+    #[error("failed to deserialize the response: {0}")]
+    DeserializeResponse(serde_json::Error),
+
+    /// Failed to read from response stream
+    #[error("failed to read the response: {0}")]
+    ReadResponse(io::Error),
+
+    /// HTTP error response without an `error` object
+    #[error("the request failed with status {status_code}: {message}")]
+    HttpResponseError {
+        status_code: StatusCode,
+        message: String,
+    },
+
+    /// Rate limit exceeded
+    #[error("the rate limit was exceeded")]
+    RateLimit { retry_after: Option<Duration> },
+
+    /// Server overloaded
+    #[error("the server is overloaded")]
+    ServerOverloaded { retry_after: Option<Duration> },
+
+    /// API returned an error response, or streamed an error within a response,
+    /// in which case there is no status code.
+    #[error("{error}")]
+    ApiError {
+        status_code: Option<StatusCode>,
+        error: ApiError,
+    },
+}
+
+impl OpenAiError {
+    /// Converts the error, attributing it to `provider` since many vendors
+    /// serve the same API.
+    pub fn into_completion_error(
+        self,
+        provider: LanguageModelProviderName,
+    ) -> LanguageModelCompletionError {
+        match self {
+            OpenAiError::SerializeRequest(error) => {
+                LanguageModelCompletionError::SerializeRequest { provider, error }
+            }
+            OpenAiError::BuildRequestBody(error) => {
+                LanguageModelCompletionError::BuildRequestBody { provider, error }
+            }
+            OpenAiError::HttpSend(error) => {
+                LanguageModelCompletionError::HttpSend { provider, error }
+            }
+            OpenAiError::DeserializeResponse(error) => {
+                LanguageModelCompletionError::DeserializeResponse { provider, error }
+            }
+            OpenAiError::ReadResponse(error) => {
+                LanguageModelCompletionError::ApiReadResponseError { provider, error }
+            }
+            OpenAiError::HttpResponseError {
+                status_code,
+                message,
+            } => LanguageModelCompletionError::HttpResponseError {
+                provider,
+                status_code,
+                message,
+            },
+            OpenAiError::RateLimit { retry_after } => {
+                LanguageModelCompletionError::RateLimitExceeded {
+                    provider,
+                    retry_after,
+                }
+            }
+            OpenAiError::ServerOverloaded { retry_after } => {
+                LanguageModelCompletionError::ServerOverloaded {
+                    provider,
+                    retry_after,
+                }
+            }
+            OpenAiError::ApiError { status_code, error } => {
+                error.into_completion_error(status_code, provider)
+            }
+        }
+    }
+}
+
+/// The `error` object of an error response.
+/// <https://platform.openai.com/docs/guides/error-codes>
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Error)]
+#[serde(from = "ApiErrorPayload")]
+#[error("{message}")]
+pub struct ApiError {
+    pub message: String,
+    /// e.g. `invalid_request_error` or `server_error`.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub error_type: Option<String>,
+    /// e.g. `context_length_exceeded` or `invalid_api_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param: Option<String>,
+}
+
+/// Servers other than OpenAI also stream the message alone, or use numeric
+/// codes.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ApiErrorPayload {
+    Object {
+        #[serde(default)]
+        message: String,
+        #[serde(rename = "type", default)]
+        error_type: Option<String>,
+        #[serde(default, deserialize_with = "deserialize_code")]
+        code: Option<String>,
+        #[serde(default)]
+        param: Option<String>,
+    },
+    Message(String),
+}
+
+impl From<ApiErrorPayload> for ApiError {
+    fn from(payload: ApiErrorPayload) -> Self {
+        match payload {
+            ApiErrorPayload::Object {
+                message,
+                error_type,
+                code,
+                param,
+            } => Self {
+                message,
+                error_type,
+                code,
+                param,
+            },
+            ApiErrorPayload::Message(message) => Self {
+                message,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn deserialize_code<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(code)) => Some(code),
+            Some(serde_json::Value::Number(code)) => Some(code.to_string()),
+            _ => None,
+        },
+    )
+}
+
+impl ApiError {
+    /// Converts the error by its code, then its type, then `status_code`. A
+    /// numeric code stands for the status of an error streamed mid-response.
+    pub fn into_completion_error(
+        self,
+        status_code: Option<StatusCode>,
+        provider: LanguageModelProviderName,
+    ) -> LanguageModelCompletionError {
+        let message = self.message.clone();
+        let status = status_code
+            .map(|status_code| status_code.as_u16())
+            .or_else(|| self.code.as_deref()?.parse().ok());
+        match (self.code.as_deref(), self.error_type.as_deref(), status) {
+            (Some("content_filter"), _, _) => {
+                LanguageModelCompletionError::ContentFiltered { provider, message }
+            }
+            (Some("context_length_exceeded"), _, _) => {
+                LanguageModelCompletionError::PromptTooLarge {
+                    tokens: parse_context_length_exceeded(&message),
+                }
+            }
+            // Retrying doesn't help until the account is topped up.
+            (Some("insufficient_quota"), _, _) => {
+                LanguageModelCompletionError::PermissionError { provider, message }
+            }
+            (Some("rate_limit_exceeded"), _, _) | (_, _, Some(429)) => {
+                LanguageModelCompletionError::RateLimitExceeded {
+                    provider,
+                    retry_after: None,
+                }
+            }
+            (Some("invalid_api_key"), _, _)
+            | (_, Some("authentication_error"), _)
+            | (_, _, Some(401)) => {
+                LanguageModelCompletionError::AuthenticationError { provider, message }
+            }
+            (_, Some("permission_error"), _) | (_, _, Some(403)) => {
+                LanguageModelCompletionError::PermissionError { provider, message }
+            }
+            (Some("model_not_found"), _, _) | (_, _, Some(404)) => {
+                LanguageModelCompletionError::ApiEndpointNotFound { provider }
+            }
+            (_, Some("invalid_request_error"), _) | (_, _, Some(400 | 422)) => {
+                LanguageModelCompletionError::BadRequestFormat { provider, message }
+            }
+            (_, _, Some(503)) => LanguageModelCompletionError::ServerOverloaded {
+                provider,
+                retry_after: None,
+            },
+            (_, Some("server_error"), _) | (_, _, Some(500..=599)) => {
+                LanguageModelCompletionError::ApiInternalServerError { provider, message }
+            }
+            _ => LanguageModelCompletionError::Other(self.into()),
+        }
+    }
+}
+
+/// Parses the prompt size from a message like "This model's maximum context
+/// length is 128000 tokens. However, your messages resulted in 130000 tokens."
+pub fn parse_context_length_exceeded(message: &str) -> Option<u64> {
+    message
+        .split_once("resulted in ")?
+        .1
+        .split_once(" tokens")?
+        .0
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error() {
+        let error: ApiError = serde_json::from_str(
+            r#"{"message":"This model's maximum context length is 128000 tokens. However, your messages resulted in 130412 tokens. Please reduce the length of the messages.","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            error.into_completion_error(
+                Some(StatusCode::BAD_REQUEST),
+                LanguageModelProviderName::new("OpenAI")
+            ),
+            LanguageModelCompletionError::PromptTooLarge {
+                tokens: Some(130_412)
+            }
+        ));
+
+        let error: ApiError =
+            serde_json::from_str(r#"{"message":"Provider returned error","code":502}"#).unwrap();
+        assert_eq!(error.code.as_deref(), Some("502"));
+        assert!(matches!(
+            error.into_completion_error(None, LanguageModelProviderName::new("OpenRouter")),
+            LanguageModelCompletionError::ApiInternalServerError { .. }
+        ));
+
+        let error: ApiError = serde_json::from_str(r#""Model overloaded""#).unwrap();
+        assert_eq!(error.message, "Model overloaded");
+        assert!(matches!(
+            error.into_completion_error(
+                Some(StatusCode::SERVICE_UNAVAILABLE),
+                LanguageModelProviderName::new("vLLM")
+            ),
+            LanguageModelCompletionError::ServerOverloaded { .. }
+        ));
+    }
+}
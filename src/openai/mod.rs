@@ -1,3 +1,6 @@
 mod openai;
+mod error;
+pub mod responses;
 pub (crate) use openai::*;
+pub (crate) use error::*;
 pub use openai::Model;
\ No newline at end of file
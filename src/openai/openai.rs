@@ -1,9 +1,17 @@
-use crate::http_client::{AsyncBody, HttpClient, Method, Request as HttpRequest};
-use anyhow::{Context as _, Result, anyhow};
+use crate::http_client::http::{HeaderMap, HeaderValue, request::Builder as RequestBuilder};
+use crate::aws::uri_encode;
+use crate::common::SecretString;
+use crate::http_client::{
+    AsyncBody, HttpClient, Method, Request as HttpRequest, Response as HttpResponse, StatusCode,
+    sensitive_header_value,
+};
+use crate::openai::{ApiError, OpenAiError};
+use crate::model::{ReportedRateLimit, ReportedRateLimits};
+use anyhow::{Context as _, Result};
 use futures::{AsyncBufReadExt, AsyncReadExt, StreamExt, io::BufReader, stream::BoxStream};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{convert::TryFrom, future::Future};
+use std::{collections::BTreeMap, convert::TryFrom, future::Future, time::Duration};
 use strum::EnumIter;
 
 pub const OPEN_AI_API_URL: &str = "https://api.openai.com/v1";
@@ -201,6 +209,12 @@ impl Model {
             Self::O1 | Self::O3 | Self::O3Mini | Self::O4Mini | Model::Custom { .. } => false,
         }
     }
+
+    /// Returns whether the model reasons before answering. Reasoning models
+    /// reject `temperature`, and can summarize their reasoning.
+    pub fn is_reasoning_model(&self) -> bool {
+        matches!(self, Self::O1 | Self::O3 | Self::O3Mini | Self::O4Mini)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -210,6 +224,10 @@ pub struct Request {
     pub stream: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_completion_tokens: Option<u64>,
+    /// The older name of `max_completion_tokens`, which some OpenAI-compatible
+    /// servers still require.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub stop: Vec<String>,
     pub temperature: f32,
@@ -220,14 +238,35 @@ pub struct Request {
     pub parallel_tool_calls: Option<bool>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<ToolDefinition>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    /// Only accepted by reasoning models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StreamOptions {
+    /// Asks for a final chunk with the usage of the whole request.
+    pub include_usage: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
+#[serde(rename_all = "lowercase")]
 pub enum ToolChoice {
     Auto,
     Required,
     None,
+    #[serde(untagged)]
     Other(ToolDefinition),
 }
 
@@ -252,6 +291,9 @@ pub enum RequestMessage {
         content: Option<MessageContent>,
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         tool_calls: Vec<ToolCall>,
+        /// The reasoning of an earlier response, for servers that expect it back.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reasoning_content: Option<String>,
     },
     User {
         content: MessageContent,
@@ -337,12 +379,21 @@ pub struct FunctionContent {
     pub arguments: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
 pub struct ResponseMessageDelta {
     pub role: Option<Role>,
     pub content: Option<String>,
+    /// Reasoning, as streamed by DeepSeek, Kimi, Qwen and vLLM.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
+    /// Reasoning, as streamed by OpenRouter and Ollama.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
     #[serde(default, skip_serializing_if = "is_none_or_empty")]
     pub tool_calls: Option<Vec<ToolCallChunk>>,
+    /// The deprecated single function call, which has no id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<FunctionChunk>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
@@ -364,23 +415,46 @@ pub struct FunctionChunk {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Usage {
+    /// Includes the cached tokens.
     pub prompt_tokens: u64,
+    /// Includes the reasoning tokens.
     pub completion_tokens: u64,
     pub total_tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ChoiceDelta {
     pub index: u32,
+    /// Missing from the content filter annotations Azure streams.
+    #[serde(default)]
     pub delta: ResponseMessageDelta,
     pub finish_reason: Option<String>,
+    /// Moonshot reports usage on the last choice instead of the event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum ResponseStreamResult {
     Ok(ResponseStreamEvent),
-    Err { error: String },
+    Err { error: ApiError },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -388,27 +462,428 @@ pub struct ResponseStreamEvent {
     pub model: String,
     pub choices: Vec<ChoiceDelta>,
     pub usage: Option<Usage>,
+    /// Groq reports usage in its own extension object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x_groq: Option<GroqExtension>,
+}
+
+impl ResponseStreamEvent {
+    /// Returns the usage of the request, wherever the server reported it.
+    pub fn take_usage(&mut self) -> Option<Usage> {
+        self.usage
+            .take()
+            .or_else(|| {
+                self.choices
+                    .iter_mut()
+                    .find_map(|choice| choice.usage.take())
+            })
+            .or_else(|| self.x_groq.as_mut().and_then(|x_groq| x_groq.usage.take()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GroqExtension {
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// A whole chat completion, as returned when the request doesn't stream.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChatCompletion {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub model: String,
+    pub choices: Vec<Choice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Choice {
+    pub index: u32,
+    pub message: ResponseMessage,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq)]
+pub struct ResponseMessage {
+    pub role: Option<Role>,
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    #[serde(default, skip_serializing_if = "is_none_or_empty")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<FunctionContent>,
+}
+
+/// Turns the completion into the single event that would have streamed all
+/// of it, so that both are mapped alike.
+impl From<ChatCompletion> for ResponseStreamEvent {
+    fn from(completion: ChatCompletion) -> Self {
+        let choices = completion
+            .choices
+            .into_iter()
+            .map(|choice| {
+                let message = choice.message;
+                let tool_calls = message.tool_calls.map(|tool_calls| {
+                    tool_calls
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, tool_call)| {
+                            let ToolCallContent::Function { function } = tool_call.content;
+                            ToolCallChunk {
+                                index,
+                                id: Some(tool_call.id),
+                                function: Some(function.into()),
+                            }
+                        })
+                        .collect()
+                });
+                ChoiceDelta {
+                    index: choice.index,
+                    delta: ResponseMessageDelta {
+                        role: message.role,
+                        content: message.content,
+                        reasoning_content: message.reasoning_content,
+                        reasoning: message.reasoning,
+                        tool_calls,
+                        function_call: message.function_call.map(Into::into),
+                    },
+                    finish_reason: choice.finish_reason,
+                    usage: None,
+                }
+            })
+            .collect();
+        Self {
+            model: completion.model,
+            choices,
+            usage: completion.usage,
+            x_groq: None,
+        }
+    }
+}
+
+impl From<FunctionContent> for FunctionChunk {
+    fn from(function: FunctionContent) -> Self {
+        Self {
+            name: Some(function.name),
+            arguments: Some(function.arguments),
+        }
+    }
+}
+
+/// An individual rate limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    /// Time until the limit resets to its initial state.
+    pub reset: Duration,
+}
+
+impl RateLimit {
+    fn from_headers(resource: &str, headers: &HeaderMap<HeaderValue>) -> Result<Self> {
+        let limit = get_header(&format!("x-ratelimit-limit-{resource}"), headers)?.parse()?;
+        let remaining =
+            get_header(&format!("x-ratelimit-remaining-{resource}"), headers)?.parse()?;
+        let reset = parse_reset_duration(get_header(
+            &format!("x-ratelimit-reset-{resource}"),
+            headers,
+        )?)?;
+
+        Ok(Self {
+            limit,
+            remaining,
+            reset,
+        })
+    }
+
+    fn to_reported(&self) -> ReportedRateLimit {
+        ReportedRateLimit {
+            limit: self.limit,
+            remaining: self.remaining,
+            reset_in: self.reset,
+        }
+    }
+}
+
+/// <https://platform.openai.com/docs/guides/rate-limits#rate-limits-in-headers>
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimitInfo {
+    pub retry_after: Option<Duration>,
+    pub requests: Option<RateLimit>,
+    pub tokens: Option<RateLimit>,
+}
+
+impl RateLimitInfo {
+    pub fn from_headers(headers: &HeaderMap<HeaderValue>) -> Self {
+        Self {
+            retry_after: headers
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<f64>().ok())
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok()),
+            requests: RateLimit::from_headers("requests", headers).ok(),
+            tokens: RateLimit::from_headers("tokens", headers).ok(),
+        }
+    }
+}
+
+impl RateLimitInfo {
+    /// Returns how long to wait before retrying: the `retry-after` header, or
+    /// else the time until the exhausted limits reset.
+    pub fn retry_delay(&self) -> Option<Duration> {
+        self.retry_after.or_else(|| {
+            [&self.requests, &self.tokens]
+                .into_iter()
+                .flatten()
+                .filter(|limit| limit.remaining == 0)
+                .map(|limit| limit.reset)
+                .max()
+        })
+    }
+}
+
+impl From<&RateLimitInfo> for ReportedRateLimits {
+    fn from(info: &RateLimitInfo) -> Self {
+        Self {
+            retry_after: info.retry_after,
+            requests: info.requests.as_ref().map(RateLimit::to_reported),
+            tokens: info.tokens.as_ref().map(RateLimit::to_reported),
+            input_tokens: None,
+            output_tokens: None,
+        }
+    }
+}
+
+/// Parses the Go-style durations OpenAI uses in `x-ratelimit-reset-*` headers,
+/// such as `1s`, `6m0s` or `20ms`.
+pub fn parse_reset_duration(value: &str) -> Result<Duration> {
+    let mut total = 0.0;
+    let mut rest = value.trim();
+    anyhow::ensure!(!rest.is_empty(), "empty duration");
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .with_context(|| format!("missing unit in duration `{value}`"))?;
+        let (number, tail) = rest.split_at(number_len);
+        let number: f64 = number
+            .parse()
+            .with_context(|| format!("invalid duration `{value}`"))?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        total += number
+            * match unit {
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                "ms" => 0.001,
+                _ => anyhow::bail!("invalid unit `{unit}` in duration `{value}`"),
+            };
+        rest = tail;
+    }
+    Ok(Duration::from_secs_f64(total))
+}
+
+fn get_header<'a>(key: &str, headers: &'a HeaderMap) -> Result<&'a str> {
+    Ok(headers
+        .get(key)
+        .with_context(|| format!("missing header `{key}`"))?
+        .to_str()?)
+}
+
+/// Streams a chat completion, sending `extra_headers` (e.g. `OpenAI-Organization`)
+/// with the request, and returns the `x-ratelimit-*` headers of the response.
+pub async fn stream_completion_with_rate_limit_info(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<
+    (
+        BoxStream<'static, Result<ResponseStreamEvent, OpenAiError>>,
+        Option<RateLimitInfo>,
+    ),
+    OpenAiError,
+> {
+    let request_builder = chat_completions_request(api_url, api_key)?;
+    send_completion_request(client, request_builder, extra_headers, request).await
 }
 
-pub async fn stream_completion(
+/// Like [`stream_completion_with_rate_limit_info`], but for a request that
+/// doesn't stream, as some models and servers require.
+pub async fn complete_with_rate_limit_info(
     client: &dyn HttpClient,
     api_url: &str,
-    api_key: &str,
+    api_key: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
     request: Request,
-) -> Result<BoxStream<'static, Result<ResponseStreamEvent>>> {
+) -> Result<(ChatCompletion, Option<RateLimitInfo>), OpenAiError> {
+    let request_builder = chat_completions_request(api_url, api_key)?;
+    send_complete_request(client, request_builder, extra_headers, request).await
+}
+
+fn chat_completions_request(
+    api_url: &str,
+    api_key: &SecretString,
+) -> Result<RequestBuilder, OpenAiError> {
     let uri = format!("{api_url}/chat/completions");
-    // println!("{}", uri);
-    let request_builder = HttpRequest::builder()
+    let mut request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Content-Type", "application/json");
+    // Local servers like vLLM or LM Studio may run without a key.
+    if !api_key.is_empty() {
+        request_builder = request_builder.header(
+            "Authorization",
+            sensitive_header_value(&format!("Bearer {}", api_key.expose()))
+                .map_err(|error| OpenAiError::BuildRequestBody(error.into()))?,
+        );
+    }
+    Ok(request_builder)
+}
+
+/// The API version used for Azure OpenAI when none is configured.
+pub const AZURE_API_VERSION: &str = "2024-10-21";
+
+/// How Azure OpenAI requests are authenticated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AzureAuth {
+    /// A key of the Azure resource, sent in the `api-key` header.
+    #[default]
+    ApiKey,
+    /// A Microsoft Entra ID access token, sent as `Authorization: Bearer`.
+    BearerToken,
+}
+
+/// Addresses a model deployed on an Azure OpenAI resource.
+#[derive(Clone, Copy, Debug)]
+pub struct AzureDeployment<'a> {
+    pub deployment: &'a str,
+    pub api_version: &'a str,
+    pub auth: AzureAuth,
+}
+
+/// Like [`stream_completion_with_rate_limit_info`], but for a deployment on
+/// the Azure OpenAI resource at `api_url`, e.g. `https://example.openai.azure.com`.
+pub async fn stream_azure_completion_with_rate_limit_info(
+    client: &dyn HttpClient,
+    api_url: &str,
+    deployment: &AzureDeployment<'_>,
+    api_key: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<
+    (
+        BoxStream<'static, Result<ResponseStreamEvent, OpenAiError>>,
+        Option<RateLimitInfo>,
+    ),
+    OpenAiError,
+> {
+    let request_builder = azure_chat_completions_request(api_url, deployment, api_key)?;
+    send_completion_request(client, request_builder, extra_headers, request).await
+}
+
+/// Like [`complete_with_rate_limit_info`], but for a deployment on the Azure
+/// OpenAI resource at `api_url`.
+pub async fn complete_azure_with_rate_limit_info(
+    client: &dyn HttpClient,
+    api_url: &str,
+    deployment: &AzureDeployment<'_>,
+    api_key: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<(ChatCompletion, Option<RateLimitInfo>), OpenAiError> {
+    let request_builder = azure_chat_completions_request(api_url, deployment, api_key)?;
+    send_complete_request(client, request_builder, extra_headers, request).await
+}
+
+fn azure_chat_completions_request(
+    api_url: &str,
+    deployment: &AzureDeployment<'_>,
+    api_key: &SecretString,
+) -> Result<RequestBuilder, OpenAiError> {
+    let uri = format!(
+        "{api_url}/openai/deployments/{}/chat/completions?api-version={}",
+        uri_encode(deployment.deployment),
+        uri_encode(deployment.api_version)
+    );
+    let (name, value) = match deployment.auth {
+        AzureAuth::ApiKey => ("api-key", api_key.expose().to_string()),
+        AzureAuth::BearerToken => ("Authorization", format!("Bearer {}", api_key.expose())),
+    };
+    let value = sensitive_header_value(&value)
+        .map_err(|error| OpenAiError::BuildRequestBody(error.into()))?;
+    Ok(HttpRequest::builder()
         .method(Method::POST)
         .uri(uri)
         .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key));
+        .header(name, value))
+}
+
+async fn send_request(
+    client: &dyn HttpClient,
+    mut request_builder: RequestBuilder,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<HttpResponse<AsyncBody>, OpenAiError> {
+    for (name, value) in extra_headers {
+        request_builder = request_builder.header(name, value);
+    }
+
+    let body = serde_json::to_string(&request).map_err(OpenAiError::SerializeRequest)?;
+    let request = request_builder
+        .body(AsyncBody::from(body))
+        .map_err(OpenAiError::BuildRequestBody)?;
+    client.send(request).await.map_err(OpenAiError::HttpSend)
+}
 
-    let request = request_builder.body(AsyncBody::from(serde_json::to_string(&request)?))?;
-    let mut response = client.send(request).await?;
+async fn send_complete_request(
+    client: &dyn HttpClient,
+    request_builder: RequestBuilder,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<(ChatCompletion, Option<RateLimitInfo>), OpenAiError> {
+    let mut response = send_request(client, request_builder, extra_headers, request).await?;
+    let rate_limits = RateLimitInfo::from_headers(response.headers());
+    if response.status().is_success() {
+        let mut body = String::new();
+        response
+            .body_mut()
+            .read_to_string(&mut body)
+            .await
+            .map_err(OpenAiError::ReadResponse)?;
+        let completion = serde_json::from_str(&body).map_err(OpenAiError::DeserializeResponse)?;
+        Ok((completion, Some(rate_limits)))
+    } else {
+        Err(read_error_response(response).await)
+    }
+}
+
+async fn send_completion_request(
+    client: &dyn HttpClient,
+    request_builder: RequestBuilder,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<
+    (
+        BoxStream<'static, Result<ResponseStreamEvent, OpenAiError>>,
+        Option<RateLimitInfo>,
+    ),
+    OpenAiError,
+> {
+    let response = send_request(client, request_builder, extra_headers, request).await?;
+    let rate_limits = RateLimitInfo::from_headers(response.headers());
     if response.status().is_success() {
         let reader = BufReader::new(response.into_body());
-        Ok(reader
+        let stream = reader
             .lines()
             .filter_map(|line| async move {
                 match line {
@@ -420,44 +895,65 @@ pub async fn stream_completion(
                             match serde_json::from_str(line) {
                                 Ok(ResponseStreamResult::Ok(response)) => Some(Ok(response)),
                                 Ok(ResponseStreamResult::Err { error }) => {
-                                    Some(Err(anyhow!(error)))
+                                    Some(Err(OpenAiError::ApiError {
+                                        status_code: None,
+                                        error,
+                                    }))
                                 }
-                                Err(error) => Some(Err(anyhow!(error))),
+                                Err(error) => Some(Err(OpenAiError::DeserializeResponse(error))),
                             }
                         }
                     }
-                    Err(error) => Some(Err(anyhow!(error))),
+                    Err(error) => Some(Err(OpenAiError::ReadResponse(error))),
                 }
             })
-            .boxed())
+            .boxed();
+        Ok((stream, Some(rate_limits)))
     } else {
-        let mut body = String::new();
-        response.body_mut().read_to_string(&mut body).await?;
+        Err(read_error_response(response).await)
+    }
+}
 
-        #[derive(Deserialize)]
-        struct OpenAiResponse {
-            error: OpenAiError,
-        }
+/// Turns an unsuccessful response into an error, from the `error` object in
+/// the body if there is one.
+pub(crate) async fn read_error_response(mut response: HttpResponse<AsyncBody>) -> OpenAiError {
+    let status_code = response.status();
+    let rate_limits = RateLimitInfo::from_headers(response.headers());
+    let mut body = String::new();
+    if let Err(error) = response.body_mut().read_to_string(&mut body).await {
+        return OpenAiError::ReadResponse(error);
+    }
 
-        #[derive(Deserialize)]
-        struct OpenAiError {
-            message: String,
-        }
+    #[derive(Deserialize)]
+    struct ErrorResponse {
+        error: ApiError,
+    }
 
-        match serde_json::from_str::<OpenAiResponse>(&body) {
-            Ok(response) if !response.error.message.is_empty() => Err(anyhow!(
-                "API request to {} failed: {}",
-                api_url,
-                response.error.message,
-            )),
-
-            _ => anyhow::bail!(
-                "API request to {} failed with status {}: {}",
-                api_url,
-                response.status(),
-                body,
-            ),
-        }
+    let error = serde_json::from_str::<ErrorResponse>(&body)
+        .ok()
+        .map(|response| response.error);
+    let insufficient_quota = error
+        .as_ref()
+        .is_some_and(|error| error.code.as_deref() == Some("insufficient_quota"));
+    if status_code == StatusCode::TOO_MANY_REQUESTS && !insufficient_quota {
+        return OpenAiError::RateLimit {
+            retry_after: rate_limits.retry_delay(),
+        };
+    }
+    if status_code == StatusCode::SERVICE_UNAVAILABLE {
+        return OpenAiError::ServerOverloaded {
+            retry_after: rate_limits.retry_after,
+        };
+    }
+    match error {
+        Some(error) => OpenAiError::ApiError {
+            status_code: Some(status_code),
+            error,
+        },
+        None => OpenAiError::HttpResponseError {
+            status_code,
+            message: body,
+        },
     }
 }
 
@@ -488,7 +984,7 @@ pub struct OpenAiEmbedding {
 pub fn embed<'a>(
     client: &dyn HttpClient,
     api_url: &str,
-    api_key: &str,
+    api_key: &SecretString,
     model: OpenAiEmbeddingModel,
     texts: impl IntoIterator<Item = &'a str>,
 ) -> impl 'static + Future<Output = Result<OpenAiEmbeddingResponse>> {
@@ -499,12 +995,16 @@ pub fn embed<'a>(
         input: texts.into_iter().collect(),
     };
     let body = AsyncBody::from(serde_json::to_string(&request).unwrap());
-    let request = HttpRequest::builder()
-        .method(Method::POST)
-        .uri(uri)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .body(body)
+    let request = sensitive_header_value(&format!("Bearer {}", api_key.expose()))
+        .map_err(anyhow::Error::from)
+        .and_then(|authorization| {
+            Ok(HttpRequest::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .header("Authorization", authorization)
+                .body(body)?)
+        })
         .map(|request| client.send(request));
 
     async move {
@@ -523,3 +1023,14 @@ pub fn embed<'a>(
         Ok(response)
     }
 }
+
+#[test]
+fn test_parse_reset_duration() {
+    assert_eq!(parse_reset_duration("1s").unwrap(), Duration::from_secs(1));
+    assert_eq!(parse_reset_duration("6m0s").unwrap(), Duration::from_secs(360));
+    assert_eq!(parse_reset_duration("20ms").unwrap(), Duration::from_millis(20));
+    assert_eq!(parse_reset_duration("1h2m0.5s").unwrap(), Duration::from_secs_f64(3720.5));
+    assert!(parse_reset_duration("").is_err());
+    assert!(parse_reset_duration("10").is_err());
+    assert!(parse_reset_duration("3d").is_err());
+}
@@ -0,0 +1,398 @@
+//! The Responses API, which unlike Chat Completions streams reasoning
+//! summaries, returns encrypted reasoning for stateless multi-turn
+//! conversations and offers built-in tools.
+//!
+//! <https://platform.openai.com/docs/api-reference/responses>
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use futures::{AsyncBufReadExt, StreamExt, io::BufReader, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::common::SecretString;
+use crate::http_client::{
+    AsyncBody, HttpClient, Method, Request as HttpRequest, sensitive_header_value,
+};
+use crate::openai::{
+    ApiError, OpenAiError, RateLimitInfo, ReasoningEffort, Role, read_error_response,
+};
+
+/// Asks for the encrypted content of reasoning items, which lets them be
+/// passed back in the next request without storing the response.
+pub const INCLUDE_REASONING_ENCRYPTED_CONTENT: &str = "reasoning.encrypted_content";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Request {
+    pub model: String,
+    pub input: Vec<InputItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    pub stream: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Tool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningConfig>,
+    /// Additional output to include, e.g. [`INCLUDE_REASONING_ENCRYPTED_CONTENT`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Continues the conversation of a stored response, so only the new input
+    /// needs to be sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+    /// Whether the response is stored for later retrieval. Defaults to `true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputItem {
+    Message {
+        role: Role,
+        content: Vec<InputContent>,
+    },
+    FunctionCall {
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+    FunctionCallOutput {
+        call_id: String,
+        output: String,
+    },
+    /// A reasoning item of an earlier response, passed back as-is.
+    Reasoning {
+        id: String,
+        #[serde(default)]
+        summary: Vec<SummaryText>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encrypted_content: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputContent {
+    InputText {
+        text: String,
+    },
+    InputImage {
+        image_url: String,
+    },
+    /// Text of an earlier assistant message.
+    OutputText {
+        text: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Tool {
+    Function {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parameters: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
+    WebSearchPreview {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        search_context_size: Option<String>,
+    },
+    FileSearch {
+        vector_store_ids: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_num_results: Option<u32>,
+    },
+    CodeInterpreter {
+        /// A container id, or `{"type": "auto"}`.
+        container: Value,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoice {
+    Auto,
+    Required,
+    None,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReasoningConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effort: Option<ReasoningEffort>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<ReasoningSummary>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningSummary {
+    Auto,
+    Concise,
+    Detailed,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SummaryText {
+    SummaryText { text: String },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputItem {
+    Message {
+        id: String,
+        #[serde(default)]
+        content: Vec<OutputContent>,
+    },
+    FunctionCall {
+        #[serde(default)]
+        id: Option<String>,
+        call_id: String,
+        name: String,
+        #[serde(default)]
+        arguments: String,
+    },
+    Reasoning {
+        id: String,
+        #[serde(default)]
+        summary: Vec<SummaryText>,
+        #[serde(default)]
+        encrypted_content: Option<String>,
+    },
+    /// The calls of built-in tools, which the server executes itself.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputContent {
+    OutputText { text: String },
+    Refusal { refusal: String },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Response {
+    pub id: String,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub output: Vec<OutputItem>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    #[serde(default)]
+    pub incomplete_details: Option<IncompleteDetails>,
+    #[serde(default)]
+    pub error: Option<ApiError>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct IncompleteDetails {
+    /// `max_output_tokens` or `content_filter`.
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Usage {
+    /// Includes the cached tokens.
+    pub input_tokens: u64,
+    /// Includes the reasoning tokens.
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub input_tokens_details: Option<InputTokensDetails>,
+    #[serde(default)]
+    pub output_tokens_details: Option<OutputTokensDetails>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct InputTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct OutputTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+}
+
+/// The events of a streamed response that callers act on. Others, like
+/// `response.in_progress`, are [`StreamEvent::Unknown`].
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum StreamEvent {
+    #[serde(rename = "response.created")]
+    Created { response: Response },
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded {
+        output_index: usize,
+        item: OutputItem,
+    },
+    #[serde(rename = "response.output_item.done")]
+    OutputItemDone {
+        output_index: usize,
+        item: OutputItem,
+    },
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta {
+        item_id: String,
+        output_index: usize,
+        delta: String,
+    },
+    #[serde(rename = "response.refusal.delta")]
+    RefusalDelta {
+        item_id: String,
+        output_index: usize,
+        delta: String,
+    },
+    #[serde(rename = "response.reasoning_summary_text.delta")]
+    ReasoningSummaryTextDelta {
+        item_id: String,
+        output_index: usize,
+        summary_index: usize,
+        delta: String,
+    },
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta {
+        item_id: String,
+        output_index: usize,
+        delta: String,
+    },
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCallArgumentsDone {
+        item_id: String,
+        output_index: usize,
+        arguments: String,
+    },
+    #[serde(rename = "response.completed")]
+    Completed { response: Response },
+    #[serde(rename = "response.incomplete")]
+    Incomplete { response: Response },
+    #[serde(rename = "response.failed")]
+    Failed { response: Response },
+    #[serde(rename = "error")]
+    Error {
+        #[serde(default)]
+        code: Option<String>,
+        message: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// Streams a response from `{api_url}/responses`, sending `extra_headers`
+/// with the request, and returns the `x-ratelimit-*` headers of the response.
+pub async fn stream_response(
+    client: &dyn HttpClient,
+    api_url: &str,
+    api_key: &SecretString,
+    extra_headers: &BTreeMap<String, String>,
+    request: Request,
+) -> Result<
+    (
+        BoxStream<'static, Result<StreamEvent, OpenAiError>>,
+        Option<RateLimitInfo>,
+    ),
+    OpenAiError,
+> {
+    let uri = format!("{api_url}/responses");
+    let mut request_builder = HttpRequest::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("Content-Type", "application/json");
+    if !api_key.is_empty() {
+        request_builder = request_builder.header(
+            "Authorization",
+            sensitive_header_value(&format!("Bearer {}", api_key.expose()))
+                .map_err(|error| OpenAiError::BuildRequestBody(error.into()))?,
+        );
+    }
+    for (name, value) in extra_headers {
+        request_builder = request_builder.header(name, value);
+    }
+
+    let body = serde_json::to_string(&request).map_err(OpenAiError::SerializeRequest)?;
+    let request = request_builder
+        .body(AsyncBody::from(body))
+        .map_err(OpenAiError::BuildRequestBody)?;
+    let response = client.send(request).await.map_err(OpenAiError::HttpSend)?;
+    let rate_limits = RateLimitInfo::from_headers(response.headers());
+    if response.status().is_success() {
+        let reader = BufReader::new(response.into_body());
+        // The `event:` lines repeat the `type` of the data, so only the
+        // `data:` lines are read.
+        let stream = reader
+            .lines()
+            .filter_map(|line| async move {
+                match line {
+                    Ok(line) => {
+                        let line = line.strip_prefix("data: ")?;
+                        Some(serde_json::from_str(line).map_err(OpenAiError::DeserializeResponse))
+                    }
+                    Err(error) => Some(Err(OpenAiError::ReadResponse(error))),
+                }
+            })
+            .boxed();
+        Ok((stream, Some(rate_limits)))
+    } else {
+        Err(read_error_response(response).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream_events() {
+        let event: StreamEvent = serde_json::from_str(
+            r#"{"type":"response.output_item.done","output_index":0,"sequence_number":7,"item":{"id":"rs_1","type":"reasoning","summary":[{"type":"summary_text","text":"Thinking"}],"encrypted_content":"gAAAA"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            event,
+            StreamEvent::OutputItemDone {
+                output_index: 0,
+                item: OutputItem::Reasoning {
+                    id: "rs_1".into(),
+                    summary: vec![SummaryText::SummaryText {
+                        text: "Thinking".into()
+                    }],
+                    encrypted_content: Some("gAAAA".into()),
+                },
+            }
+        );
+
+        let event: StreamEvent = serde_json::from_str(
+            r#"{"type":"response.output_item.added","output_index":1,"item":{"id":"ws_1","type":"web_search_call","status":"in_progress"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            event,
+            StreamEvent::OutputItemAdded {
+                item: OutputItem::Other,
+                ..
+            }
+        ));
+
+        let event: StreamEvent =
+            serde_json::from_str(r#"{"type":"response.in_progress","sequence_number":1}"#).unwrap();
+        assert_eq!(event, StreamEvent::Unknown);
+    }
+}
@@ -214,6 +214,12 @@ impl http_client::HttpClient for ReqwestClient {
         anyhow::Result<http_client::Response<http_client::AsyncBody>>,
     > {
         let (parts, body) = req.into_parts();
+        log::trace!(
+            "{} {} {:?}",
+            parts.method,
+            parts.uri,
+            http_client::RedactedHeaders(&parts.headers)
+        );
 
         let mut request = self.client.request(parts.method, parts.uri.to_string());
         request = request.headers(parts.headers);
@@ -1,5 +1,7 @@
 mod tool;
 mod tool_registry;
+mod tool_schema;
 
 pub use tool::*;
-pub use tool_registry::*;
\ No newline at end of file
+pub use tool_registry::*;
+pub use tool_schema::*;
\ No newline at end of file
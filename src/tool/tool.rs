@@ -1,4 +1,5 @@
 use crate::common::SharedString;
+use crate::tool::adapt_schema_to_format;
 use crate::{
     LanguageModel, LanguageModelImage, LanguageModelRequest, LanguageModelToolSchemaFormat,
 };
@@ -41,6 +42,9 @@ pub trait Tool: Sized + Send + Sync {
     fn may_perform_edits(&self) -> bool;
 
     /// Returns the JSON schema that describes the tool's input.
+    ///
+    /// The schema is passed through [`adapt_schema_to_format`] before it is
+    /// sent to the model, so a schemars-generated schema works for every format.
     fn input_schema(&self, _: LanguageModelToolSchemaFormat) -> anyhow::Result<serde_json::Value> {
         Ok(serde_json::Value::Object(serde_json::Map::default()))
     }
@@ -72,9 +76,11 @@ impl<T: Tool> ToolDyn for T {
     }
     fn input_schema(
         &self,
-        schema: LanguageModelToolSchemaFormat,
+        format: LanguageModelToolSchemaFormat,
     ) -> anyhow::Result<serde_json::Value> {
-        self.input_schema(schema)
+        let mut schema = self.input_schema(format)?;
+        adapt_schema_to_format(&mut schema, format)?;
+        Ok(schema)
     }
     fn ui_text(&self, input: &serde_json::Value) -> String {
         self.ui_text(input)
@@ -1,5 +1,10 @@
-use crate::{Tool, ToolDyn};
+use crate::model::{
+    LanguageModelResponse, LanguageModelToolResult, LanguageModelToolResultContent,
+    LanguageModelToolUse,
+};
+use crate::{Tool, ToolDyn, ToolResultContent};
 use derive_more::{Deref, DerefMut};
+use futures::future;
 use parking_lot::RwLock;
 use rustc_hash::FxHashMap;
 use std::sync::Arc;
@@ -34,4 +39,52 @@ impl ToolRegistry {
     pub fn tools(&self) -> Vec<Arc<dyn ToolDyn>> {
         self.state.read().tools.values().cloned().collect()
     }
+
+    /// Runs every tool use of `response` concurrently, and returns the results
+    /// in the order of the tool uses, which is how the model expects them back.
+    ///
+    /// Failures, including tool uses whose input was not valid JSON, become
+    /// error results for the model to see.
+    pub async fn run_tool_uses(
+        &self,
+        response: &LanguageModelResponse,
+    ) -> Vec<LanguageModelToolResult> {
+        future::join_all(response.tool_uses().map(|tool_use| {
+            let parse_error = response.tool_use_parse_errors.get(&tool_use.id);
+            self.run_tool_use(tool_use, parse_error.map(String::as_str))
+        }))
+        .await
+    }
+
+    /// Runs a single tool use. `parse_error` is the error of its input, if
+    /// it was not valid JSON.
+    pub async fn run_tool_use(
+        &self,
+        tool_use: &LanguageModelToolUse,
+        parse_error: Option<&str>,
+    ) -> LanguageModelToolResult {
+        let output = match parse_error {
+            Some(error) => Err(anyhow::anyhow!("Error parsing input JSON: {error}")),
+            None => match self.tool(&tool_use.name) {
+                Some(tool) => tool.run(tool_use.input.clone()).await,
+                None => Err(anyhow::anyhow!("No tool named {} exists", tool_use.name)),
+            },
+        };
+
+        let (is_error, content) = match output {
+            Ok(ToolResultContent::Text(text)) => (false, text),
+            Ok(ToolResultContent::Image(_)) => (
+                true,
+                "Tool returned an image, which is not supported in tool results".to_string(),
+            ),
+            Err(error) => (true, error.to_string()),
+        };
+        LanguageModelToolResult {
+            tool_use_id: tool_use.id.clone(),
+            tool_name: tool_use.name.clone(),
+            is_error,
+            content: LanguageModelToolResultContent::from(content),
+            output: None,
+        }
+    }
 }
@@ -0,0 +1,372 @@
+use crate::LanguageModelToolSchemaFormat;
+use anyhow::{Context as _, Result, anyhow, bail};
+use schemars::JsonSchema;
+use serde_json::{Map, Value, json};
+
+/// Keywords understood by models that only accept
+/// [`LanguageModelToolSchemaFormat::JsonSchemaSubset`], i.e. the OpenAPI 3.0
+/// schema object used by Gemini. Everything else is dropped.
+const SUBSET_KEYWORDS: &[&str] = &[
+    "type",
+    "format",
+    "description",
+    "nullable",
+    "enum",
+    "items",
+    "properties",
+    "required",
+    "anyOf",
+    "minItems",
+    "maxItems",
+    "minimum",
+    "maximum",
+];
+
+/// String formats that survive the conversion; all other `format`s are removed.
+const SUBSET_STRING_FORMATS: &[&str] = &["enum", "date-time"];
+
+/// Generates the input schema of `T` in the given format.
+pub fn json_schema_for<T: JsonSchema>(format: LanguageModelToolSchemaFormat) -> Result<Value> {
+    let mut schema = serde_json::to_value(schemars::schema_for!(T))?;
+    adapt_schema_to_format(&mut schema, format)?;
+    Ok(schema)
+}
+
+/// Rewrites a JSON Schema, as generated by schemars, so that models using
+/// `format` accept it.
+///
+/// For [`LanguageModelToolSchemaFormat::JsonSchemaSubset`] this inlines
+/// `$ref`s, turns `null` alternatives into `nullable`, replaces `const` and
+/// `oneOf` with `enum` and `anyOf`, and strips unsupported keywords such as
+/// `additionalProperties` and most `format`s. Recursive types can't be
+/// expressed in the subset and are reported as errors.
+pub fn adapt_schema_to_format(
+    schema: &mut Value,
+    format: LanguageModelToolSchemaFormat,
+) -> Result<()> {
+    match format {
+        LanguageModelToolSchemaFormat::JsonSchema => Ok(()),
+        LanguageModelToolSchemaFormat::JsonSchemaSubset => {
+            let definitions = match schema.as_object_mut() {
+                Some(object) => {
+                    let mut definitions = Map::new();
+                    for key in ["$defs", "definitions"] {
+                        if let Some(Value::Object(defs)) = object.remove(key) {
+                            definitions.extend(defs);
+                        }
+                    }
+                    definitions
+                }
+                None => Map::new(),
+            };
+            let mut converter = SubsetConverter {
+                definitions,
+                resolving: Vec::new(),
+            };
+            *schema = converter.convert(schema)?;
+            Ok(())
+        }
+    }
+}
+
+struct SubsetConverter {
+    definitions: Map<String, Value>,
+    /// The `$ref`s being inlined, used to detect recursive types.
+    resolving: Vec<String>,
+}
+
+impl SubsetConverter {
+    fn convert(&mut self, schema: &Value) -> Result<Value> {
+        let mut object = match schema {
+            Value::Object(object) => object.clone(),
+            // `true` accepts anything, which is the closest the subset gets.
+            Value::Bool(true) => return Ok(json!({})),
+            Value::Bool(false) => bail!("schemas that accept nothing are not supported"),
+            _ => bail!("invalid schema: {schema}"),
+        };
+
+        if let Some(reference) = object.remove("$ref") {
+            let resolved = self.resolve(&reference)?;
+            // Keywords next to a `$ref`, like `description`, take precedence.
+            for (key, value) in resolved {
+                object.entry(key).or_insert(value);
+            }
+        }
+
+        if let Some(Value::Array(schemas)) = object.remove("allOf") {
+            for schema in schemas {
+                let schema = self.convert(&schema)?;
+                merge_all_of(&mut object, schema);
+            }
+        }
+
+        if let Some(value) = object.remove("const") {
+            object.insert("enum".into(), Value::Array(vec![value]));
+        }
+
+        if let Some(Value::Array(types)) = object.get("type").cloned() {
+            let mut types = types;
+            let nullable = remove_null(&mut types, |ty| ty == "null");
+            if nullable {
+                object.insert("nullable".into(), true.into());
+            }
+            match types.len() {
+                0 => {
+                    object.remove("type");
+                }
+                1 => {
+                    object.insert("type".into(), types.remove(0));
+                }
+                _ => {
+                    object.remove("type");
+                    let alternatives = types.into_iter().map(|ty| json!({ "type": ty }));
+                    object.insert("anyOf".into(), alternatives.collect());
+                }
+            }
+        }
+
+        let mut alternatives = Vec::new();
+        for key in ["anyOf", "oneOf"] {
+            if let Some(Value::Array(schemas)) = object.remove(key) {
+                for schema in &schemas {
+                    alternatives.push(self.convert(schema)?);
+                }
+            }
+        }
+        if !alternatives.is_empty() {
+            self.merge_alternatives(&mut object, alternatives);
+        }
+
+        if let Some(Value::Object(properties)) = object.get("properties") {
+            let properties = properties
+                .iter()
+                .map(|(name, schema)| {
+                    let schema = self
+                        .convert(schema)
+                        .with_context(|| format!("invalid schema for property `{name}`"))?;
+                    Ok((name.clone(), schema))
+                })
+                .collect::<Result<Map<_, _>>>()?;
+            object.insert("properties".into(), Value::Object(properties));
+        }
+
+        if let Some(items) = object.get("items") {
+            let items = match items {
+                // Tuples become arrays of their first element's type.
+                Value::Array(items) => match items.first() {
+                    Some(item) => self.convert(item)?,
+                    None => json!({}),
+                },
+                items => self.convert(items)?,
+            };
+            object.insert("items".into(), items);
+        }
+
+        if object.get("type").and_then(Value::as_str) != Some("string")
+            || object
+                .get("format")
+                .and_then(Value::as_str)
+                .is_none_or(|format| !SUBSET_STRING_FORMATS.contains(&format))
+        {
+            object.remove("format");
+        }
+
+        object.retain(|key, _| SUBSET_KEYWORDS.contains(&key.as_str()));
+        Ok(Value::Object(object))
+    }
+
+    fn resolve(&mut self, reference: &Value) -> Result<Map<String, Value>> {
+        let reference = reference
+            .as_str()
+            .ok_or_else(|| anyhow!("invalid $ref: {reference}"))?;
+        let name = reference
+            .strip_prefix("#/$defs/")
+            .or_else(|| reference.strip_prefix("#/definitions/"))
+            .ok_or_else(|| anyhow!("unsupported $ref: {reference}"))?;
+        if self.resolving.iter().any(|resolving| resolving == name) {
+            bail!("recursive type `{name}` can't be inlined");
+        }
+        let definition = self
+            .definitions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("undefined $ref: {reference}"))?;
+
+        self.resolving.push(name.to_string());
+        let resolved = self.convert(&definition);
+        self.resolving.pop();
+        match resolved? {
+            Value::Object(object) => Ok(object),
+            _ => unreachable!("converted schemas are objects"),
+        }
+    }
+
+    /// Folds converted `anyOf`/`oneOf` alternatives into `object`.
+    fn merge_alternatives(&self, object: &mut Map<String, Value>, mut alternatives: Vec<Value>) {
+        let nullable = remove_null(&mut alternatives, |schema| {
+            schema.get("type").and_then(Value::as_str) == Some("null")
+                || schema.get("enum") == Some(&json!([null]))
+        });
+        if nullable {
+            object.insert("nullable".into(), true.into());
+        }
+
+        let string_values = alternatives
+            .iter()
+            .map(|schema| match schema.get("enum") {
+                Some(Value::Array(values)) if values.iter().all(Value::is_string) => {
+                    Some(values.clone())
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+
+        match string_values {
+            // Unit enum variants with doc comments are generated as
+            // `oneOf` string constants.
+            Some(values) if !values.is_empty() => {
+                object.insert("type".into(), "string".into());
+                object.insert("enum".into(), values.concat().into());
+            }
+            _ if alternatives.len() == 1 => {
+                let Value::Object(alternative) = alternatives.remove(0) else {
+                    return;
+                };
+                for (key, value) in alternative {
+                    object.entry(key).or_insert(value);
+                }
+            }
+            _ if alternatives.is_empty() => {}
+            _ => {
+                object.insert("anyOf".into(), Value::Array(alternatives));
+            }
+        }
+    }
+}
+
+/// Removes the entries matching `is_null`, returning whether there were any.
+fn remove_null(values: &mut Vec<Value>, is_null: impl Fn(&Value) -> bool) -> bool {
+    let len = values.len();
+    values.retain(|value| !is_null(value));
+    values.len() != len
+}
+
+fn merge_all_of(object: &mut Map<String, Value>, schema: Value) {
+    let Value::Object(schema) = schema else {
+        return;
+    };
+    for (key, value) in schema {
+        match (key.as_str(), object.get_mut(&key), value) {
+            ("properties", Some(Value::Object(properties)), Value::Object(other)) => {
+                properties.extend(other);
+            }
+            ("required", Some(Value::Array(required)), Value::Array(other)) => {
+                for name in other {
+                    if !required.contains(&name) {
+                        required.push(name);
+                    }
+                }
+            }
+            (_, Some(_), _) => {}
+            (_, None, value) => {
+                object.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    struct EditInput {
+        /// The file to edit.
+        path: String,
+        mode: EditMode,
+        range: Option<Range>,
+        #[serde(default)]
+        tags: Vec<String>,
+        timeout_ms: Option<u32>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    enum EditMode {
+        /// Replace the whole file.
+        Overwrite,
+        /// Append to the file.
+        Append,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    struct Range {
+        start: u32,
+        end: u32,
+    }
+
+    #[test]
+    fn test_json_schema_subset() {
+        let schema =
+            json_schema_for::<EditInput>(LanguageModelToolSchemaFormat::JsonSchemaSubset).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The file to edit."
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["Overwrite", "Append"]
+                    },
+                    "range": {
+                        "type": "object",
+                        "nullable": true,
+                        "properties": {
+                            "start": { "type": "integer", "minimum": 0 },
+                            "end": { "type": "integer", "minimum": 0 }
+                        },
+                        "required": ["start", "end"]
+                    },
+                    "tags": {
+                        "type": "array",
+                        "items": { "type": "string" }
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "nullable": true,
+                        "minimum": 0
+                    }
+                },
+                "required": ["path", "mode"]
+            })
+        );
+
+        let mut adapted = schema.clone();
+        adapt_schema_to_format(
+            &mut adapted,
+            LanguageModelToolSchemaFormat::JsonSchemaSubset,
+        )
+        .unwrap();
+        assert_eq!(adapted, schema);
+    }
+
+    #[test]
+    fn test_recursive_schema_is_rejected() {
+        #[allow(dead_code)]
+        #[derive(Deserialize, JsonSchema)]
+        struct Node {
+            children: Vec<Node>,
+        }
+
+        assert!(json_schema_for::<Node>(LanguageModelToolSchemaFormat::JsonSchema).is_ok());
+        assert!(json_schema_for::<Node>(LanguageModelToolSchemaFormat::JsonSchemaSubset).is_err());
+    }
+}